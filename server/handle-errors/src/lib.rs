@@ -11,20 +11,39 @@ use tracing::{event, instrument, Level};
 
 #[derive(Debug)]
 pub enum Error {
+    /// Seconds until the login can be tried again.
+    AccountLocked(i64),
+    AccountNotVerified,
     ArgonLibraryError(ArgonError),
     CannotDecryptToken,
     ClientError(APILayerError),
     DatabaseQueryError(sqlx::Error),
     ExternalAPIError(ReqwestError),
+    /// Scope the session lacks.
+    Forbidden(String),
+    InvalidEmail(String),
+    InvalidParameter(String),
+    InvalidResetToken,
+    IoError(std::io::Error),
+    KeyringError(String),
+    MailError(String),
     MigrationError(sqlx::migrate::MigrateError),
     MiddlewareReqwestAPIError(MiddlewareReqwestError),
     MissingParameters,
+    /// Question, answer or comment that doesn't exist, or whose question
+    /// is deleted.
+    NotFound(String),
     ParseError(std::num::ParseIntError),
     ReqwestAPIError(ReqwestError),
+    SelfVote,
     ServerError(APILayerError),
     StartGreaterThanEnd,
+    TagExists(String),
     Unauthorized,
+    UnknownRevision(i32),
+    UnknownTag(String),
     WrongPassword,
+    WrongTotpCode,
 }
 
 
@@ -43,20 +62,39 @@ impl std::fmt::Display for APILayerError {
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match &*self {
+            Error::AccountLocked(seconds) => write!(
+                f,
+                "Too many failed logins, try again in {} seconds",
+                seconds
+            ),
+            Error::AccountNotVerified => write!(f, "Account not verified, confirm your email first"),
             Error::ArgonLibraryError(_) => write!(f, "Cannot verifiy password"),
             Error::CannotDecryptToken => write!(f, "Cannot decrypt error"),
             Error::ClientError(err) => write!(f, "External Client error: {}", err),
             Error::DatabaseQueryError(_) => write!(f, "Cannot update, invalid data"),
             Error::ExternalAPIError(err) => write!(f, "External API error: {}", err),
+            Error::Forbidden(scope) => write!(f, "Forbidden, the session lacks the scope {}", scope),
+            Error::InvalidEmail(email) => write!(f, "Invalid email: {}", email),
+            Error::InvalidParameter(err) => write!(f, "Invalid parameter: {}", err),
+            Error::InvalidResetToken => write!(f, "Invalid or expired reset token"),
+            Error::IoError(err) => write!(f, "Cannot access file: {}", err),
+            Error::KeyringError(err) => write!(f, "Invalid keyring: {}", err),
+            Error::MailError(err) => write!(f, "Cannot send email: {}", err),
             Error::MiddlewareReqwestAPIError(err) => write!(f, "External API error: {}", err),
             Error::MigrationError(_) => write!(f, "Cannot migrate data"),
             Error::MissingParameters => write!(f, "Missing parameter"),
+            Error::NotFound(what) => write!(f, "Not found: {}", what),
             Error::ParseError(ref err) => write!(f, "Cannot parse parameter: {}", err),
             Error::ReqwestAPIError(err) => write!(f, "External API error: {}", err),
+            Error::SelfVote => write!(f, "Cannot vote on your own post"),
             Error::ServerError(err) => write!(f, "External Server error: {}", err),
             Error::StartGreaterThanEnd => write!(f, "The start is greater than the end"),
+            Error::TagExists(name) => write!(f, "Tag already exists: {}", name),
             Error::Unauthorized => write!(f, "No permission to change the underlying resource"),
-            Error::WrongPassword => write!(f, "Wrong password")
+            Error::UnknownRevision(number) => write!(f, "Unknown revision: {}", number),
+            Error::UnknownTag(name) => write!(f, "Unknown tag: {}", name),
+            Error::WrongPassword => write!(f, "Wrong password"),
+            Error::WrongTotpCode => write!(f, "Wrong or already used two-factor code")
             }
         }
 }
@@ -125,6 +163,12 @@ pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
             "Internal Server Error".to_string(),
             StatusCode::INTERNAL_SERVER_ERROR,
         ))
+    } else if let Some(crate::Error::MailError(e)) = r.find() {
+        event!(Level::ERROR, "Cannot send email: {}", e);
+        Ok(warp::reply::with_status(
+            "Internal Server Error".to_string(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ))
     } else if let Some(crate::Error::ServerError(e)) = r.find() {
         event!(Level::ERROR, "{}", e);
         Ok(warp::reply::with_status(
@@ -149,6 +193,36 @@ pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
         "Wrong E-Mail/Password combination".to_string(),
         StatusCode::UNAUTHORIZED,
     ))
+    } else if let Some(crate::Error::WrongTotpCode) = r.find() {
+        event!(Level::ERROR, "Entered wrong two-factor code");
+        Ok(warp::reply::with_status(
+        crate::Error::WrongTotpCode.to_string(),
+        StatusCode::UNAUTHORIZED,
+    ))
+    } else if let Some(error @ crate::Error::AccountLocked(_)) = r.find() {
+        event!(Level::WARN, "Login locked");
+        Ok(warp::reply::with_status(
+        error.to_string(),
+        StatusCode::TOO_MANY_REQUESTS,
+    ))
+    } else if let Some(crate::Error::AccountNotVerified) = r.find() {
+        event!(Level::ERROR, "Account not verified");
+        Ok(warp::reply::with_status(
+        crate::Error::AccountNotVerified.to_string(),
+        StatusCode::FORBIDDEN,
+    ))
+    } else if let Some(error @ crate::Error::Forbidden(_)) = r.find() {
+        event!(Level::ERROR, "{}", error);
+        Ok(warp::reply::with_status(
+        error.to_string(),
+        StatusCode::FORBIDDEN,
+    ))
+    } else if let Some(error @ crate::Error::NotFound(_)) = r.find() {
+        event!(Level::WARN, "{}", error);
+        Ok(warp::reply::with_status(
+        error.to_string(),
+        StatusCode::NOT_FOUND,
+    ))
     } else if let Some(crate::Error::Unauthorized) = r.find() {
         event!(Level::ERROR, "Not matching account id");
         Ok(warp::reply::with_status(
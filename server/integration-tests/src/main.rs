@@ -1,6 +1,7 @@
 use std::io::{self, Write};
 use std::process::Command;
 
+use rust_web_dev::mail::InMemoryMailer;
 use rust_web_dev::{config, handle_errors, oneshot, setup_store};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -60,7 +61,7 @@ async fn main() -> Result<(), handle_errors::Error> {
 
     // Start the web server via the oneshot function
     // and listen for a sender signal to shut it down.
-    let handler = oneshot(store).await;
+    let handler = oneshot(store, InMemoryMailer::new()).await;
 
     let u = User {
         email: "test@email.com".to_string(),
@@ -0,0 +1,2 @@
+ALTER TABLE questions
+ADD COLUMN accepted_answer_id integer REFERENCES answers ON DELETE SET NULL;
@@ -0,0 +1,12 @@
+-- One row per account and voted question or answer.
+CREATE TABLE IF NOT EXISTS votes (
+   id serial PRIMARY KEY,
+   account_id integer NOT NULL,
+   question_id integer REFERENCES questions ON DELETE CASCADE,
+   answer_id integer REFERENCES answers ON DELETE CASCADE,
+   value smallint NOT NULL CHECK (value IN (-1, 1)),
+   created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+   CHECK ((question_id IS NULL) <> (answer_id IS NULL)),
+   UNIQUE (account_id, question_id),
+   UNIQUE (account_id, answer_id)
+);
@@ -0,0 +1,8 @@
+-- Title words weigh more than content words in the search ranking.
+ALTER TABLE questions
+ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+   setweight(to_tsvector('english', title), 'A') ||
+   setweight(to_tsvector('english', content), 'B')
+) STORED;
+
+CREATE INDEX questions_search_vector_idx ON questions USING GIN (search_vector);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS answers_search_vector_idx;
+
+ALTER TABLE answers
+DROP COLUMN search_vector;
@@ -0,0 +1,7 @@
+-- Answers keep the default weight (D), below question titles and contents.
+ALTER TABLE answers
+ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+   to_tsvector('english', content)
+) STORED;
+
+CREATE INDEX answers_search_vector_idx ON answers USING GIN (search_vector);
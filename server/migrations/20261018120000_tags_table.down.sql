@@ -0,0 +1,6 @@
+ALTER TABLE questions ADD COLUMN tags TEXT [];
+UPDATE questions SET tags = tag_names(id);
+DROP FUNCTION tag_names(integer);
+DROP TABLE IF EXISTS question_tags;
+DROP TABLE IF EXISTS tag_synonyms;
+DROP TABLE IF EXISTS tags;
@@ -0,0 +1,42 @@
+-- Tag catalogue. Synonyms are other names of a tag, resolved when
+-- questions are tagged.
+CREATE TABLE IF NOT EXISTS tags (
+   id serial PRIMARY KEY,
+   name TEXT NOT NULL UNIQUE,
+   description TEXT,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS tag_synonyms (
+   name TEXT PRIMARY KEY,
+   tag_id integer NOT NULL REFERENCES tags ON DELETE CASCADE
+);
+
+-- Tags of the questions, in the order they were given.
+CREATE TABLE IF NOT EXISTS question_tags (
+   question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+   tag_id integer NOT NULL REFERENCES tags ON DELETE CASCADE,
+   position integer NOT NULL,
+   PRIMARY KEY (question_id, tag_id)
+);
+
+CREATE INDEX question_tags_tag_id_idx ON question_tags (tag_id);
+
+INSERT INTO tags (name)
+SELECT DISTINCT name FROM questions, unnest(questions.tags) AS name
+WHERE name <> '';
+
+INSERT INTO question_tags (question_id, tag_id, position)
+SELECT questions.id, tags.id, MIN(tag.position)
+FROM questions, unnest(questions.tags) WITH ORDINALITY AS tag(name, position)
+JOIN tags ON tags.name = tag.name
+GROUP BY questions.id, tags.id;
+
+ALTER TABLE questions DROP COLUMN tags;
+
+-- Tag names of a question, NULL when it has none, like the former column.
+CREATE FUNCTION tag_names(question_id integer) RETURNS TEXT [] AS $$
+   SELECT array_agg(tags.name ORDER BY question_tags.position)
+   FROM question_tags JOIN tags ON tags.id = question_tags.tag_id
+   WHERE question_tags.question_id = $1
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,13 @@
+-- Comments are written on either a question or an answer.
+CREATE TABLE IF NOT EXISTS comments (
+   id serial PRIMARY KEY,
+   content TEXT NOT NULL,
+   question_id integer REFERENCES questions ON DELETE CASCADE,
+   answer_id integer REFERENCES answers ON DELETE CASCADE,
+   account_id integer NOT NULL,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+   CHECK ((question_id IS NULL) <> (answer_id IS NULL))
+);
+
+CREATE INDEX comments_question_id_idx ON comments (question_id);
+CREATE INDEX comments_answer_id_idx ON comments (answer_id);
@@ -0,0 +1,11 @@
+-- Title, content and tags of questions before each of their edits.
+CREATE TABLE IF NOT EXISTS question_revisions (
+   question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+   number integer NOT NULL,
+   title VARCHAR (255) NOT NULL,
+   content TEXT NOT NULL,
+   tags TEXT [],
+   account_id integer NOT NULL,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (question_id, number)
+);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS questions_deleted_at_idx;
+ALTER TABLE questions
+DROP COLUMN IF EXISTS deleted_at;
@@ -0,0 +1,5 @@
+-- Deleted questions are kept until they are purged.
+ALTER TABLE questions
+ADD COLUMN deleted_at TIMESTAMP;
+
+CREATE INDEX questions_deleted_at_idx ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,4 @@
+-- Accounts are users unless an admin gives them another role.
+ALTER TABLE accounts
+ADD COLUMN role VARCHAR (16) NOT NULL DEFAULT 'user'
+CHECK (role IN ('user', 'moderator', 'admin'));
@@ -0,0 +1,18 @@
+-- Refresh tokens are stored as SHA-256 hashes. The tokens rotated from the
+-- same login share its sid.
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+   token_hash CHAR (64) PRIMARY KEY,
+   account_id integer NOT NULL,
+   sid TEXT NOT NULL,
+   expires_at TIMESTAMP NOT NULL,
+   used_at TIMESTAMP,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX refresh_tokens_sid_idx ON refresh_tokens (sid);
+
+-- Access tokens revoked before they expire, by token id.
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+   jti TEXT PRIMARY KEY,
+   expires_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,8 @@
+-- Reset tokens are stored as SHA-256 hashes, like the refresh tokens.
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+   token_hash CHAR (64) PRIMARY KEY,
+   account_id integer NOT NULL,
+   expires_at TIMESTAMP NOT NULL,
+   used_at TIMESTAMP,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS email_verification_tokens;
+ALTER TABLE accounts
+DROP COLUMN IF EXISTS verified;
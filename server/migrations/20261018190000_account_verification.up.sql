@@ -0,0 +1,13 @@
+-- Accounts registered before verification existed are trusted.
+ALTER TABLE accounts
+ADD COLUMN verified BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE accounts SET verified = TRUE;
+
+-- Verification tokens are stored as SHA-256 hashes, like the reset tokens.
+CREATE TABLE IF NOT EXISTS email_verification_tokens (
+   token_hash CHAR (64) PRIMARY KEY,
+   account_id integer NOT NULL,
+   expires_at TIMESTAMP NOT NULL,
+   used_at TIMESTAMP,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
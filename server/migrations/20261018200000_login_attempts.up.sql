@@ -0,0 +1,7 @@
+-- Failed logins by account ("account:<id>") or IP address ("ip:<address>"),
+-- kept here so that lockouts survive restarts.
+CREATE TABLE IF NOT EXISTS login_attempts (
+   key TEXT PRIMARY KEY,
+   failures integer NOT NULL,
+   last_failure_at TIMESTAMP NOT NULL
+);
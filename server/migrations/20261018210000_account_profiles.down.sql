@@ -0,0 +1,3 @@
+ALTER TABLE accounts
+DROP COLUMN IF EXISTS display_name,
+DROP COLUMN IF EXISTS bio;
@@ -0,0 +1,3 @@
+ALTER TABLE accounts
+ADD COLUMN display_name VARCHAR (50),
+ADD COLUMN bio VARCHAR (500);
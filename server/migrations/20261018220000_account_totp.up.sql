@@ -0,0 +1,11 @@
+-- Two-factor secrets of the accounts, enabled once confirmed with a first
+-- code. The recovery codes are stored as SHA-256 hashes and removed when
+-- used.
+CREATE TABLE IF NOT EXISTS account_totp (
+   account_id integer PRIMARY KEY,
+   secret TEXT NOT NULL,
+   enabled BOOLEAN NOT NULL DEFAULT FALSE,
+   last_used_step BIGINT,
+   recovery_code_hashes TEXT[] NOT NULL DEFAULT '{}',
+   created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,13 @@
+-- Personal API keys, stored as SHA-256 hashes like the refresh tokens. The
+-- scopes are names like 'answers:write'.
+CREATE TABLE IF NOT EXISTS api_keys (
+   id serial PRIMARY KEY,
+   account_id integer NOT NULL,
+   name VARCHAR (100) NOT NULL,
+   key_hash CHAR (64) NOT NULL UNIQUE,
+   scopes TEXT[] NOT NULL,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+   last_used_at TIMESTAMP
+);
+
+CREATE INDEX api_keys_account_id_idx ON api_keys (account_id);
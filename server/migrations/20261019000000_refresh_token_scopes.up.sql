@@ -0,0 +1,4 @@
+-- Scopes asked for at login, kept for the tokens rotated from it. NULL for
+-- all the scopes of the role, like the logins from before scopes.
+ALTER TABLE refresh_tokens
+ADD COLUMN scopes TEXT[];
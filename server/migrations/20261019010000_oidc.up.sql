@@ -0,0 +1,22 @@
+-- Logins started at an identity provider, by SHA-256 hash of their state,
+-- with the PKCE verifier and the nonce to check when it calls back.
+CREATE TABLE IF NOT EXISTS oidc_states (
+   state_hash CHAR (64) PRIMARY KEY,
+   provider TEXT NOT NULL,
+   code_verifier TEXT NOT NULL,
+   nonce TEXT NOT NULL,
+   expires_at TIMESTAMP NOT NULL,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Accounts of the identity providers, by issuer and subject, linked to the
+-- local accounts they sign in.
+CREATE TABLE IF NOT EXISTS account_identities (
+   issuer TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   account_id integer NOT NULL,
+   created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (issuer, subject)
+);
+
+CREATE INDEX account_identities_account_id_idx ON account_identities (account_id);
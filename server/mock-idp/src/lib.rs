@@ -0,0 +1,223 @@
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, oneshot::Sender};
+use warp::{http, Filter, Reply};
+
+/// Client registered at the mock identity provider.
+pub const CLIENT_ID: &str = "qa";
+pub const CLIENT_SECRET: &str = "mock secret";
+
+/// Account signed in at the identity provider, which every authorization
+/// request gets without asking.
+#[derive(Clone, Debug)]
+pub struct MockUser {
+    pub subject: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+impl Default for MockUser {
+    fn default() -> MockUser {
+        MockUser {
+            subject: "248289761001".to_string(),
+            email: "jane@example.com".to_string(),
+            email_verified: true,
+            name: Some("Jane Doe".to_string()),
+        }
+    }
+}
+
+// Authorization request a code was given for.
+#[derive(Clone, Debug)]
+struct Grant {
+    redirect_uri: String,
+    code_challenge: String,
+    nonce: Option<String>,
+    user: MockUser,
+}
+
+#[derive(Clone, Debug)]
+pub struct MockIdp {
+    socket: SocketAddr,
+    user: Arc<Mutex<MockUser>>,
+    grants: Arc<Mutex<HashMap<String, Grant>>>,
+}
+
+pub struct OneshotHandler {
+    pub sender: Sender<i32>,
+}
+
+impl MockIdp {
+    pub fn new(bind_addr: SocketAddr) -> MockIdp {
+        MockIdp {
+            socket: bind_addr,
+            user: Arc::new(Mutex::new(MockUser::default())),
+            grants: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn issuer(&self) -> String {
+        format!("http://{}", self.socket)
+    }
+
+    /// Signs in another account for the next authorization requests.
+    pub fn set_user(&self, user: MockUser) {
+        *self.user.lock().unwrap() = user;
+    }
+
+    fn discovery(issuer: String) -> impl Reply {
+        warp::reply::json(&json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "response_types_supported": ["code"],
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": ["HS256"],
+            "code_challenge_methods_supported": ["S256"],
+        }))
+    }
+
+    // Sends the browser back with a code at once, as if the user signed in.
+    fn authorize(&self, query: HashMap<String, String>) -> Box<dyn Reply> {
+        let param = |name: &str| query.get(name).cloned().unwrap_or_default();
+        if param("response_type") != "code"
+            || param("client_id") != CLIENT_ID
+            || param("code_challenge_method") != "S256"
+            || param("code_challenge").is_empty()
+        {
+            return Box::new(error("invalid_request"));
+        }
+        let mut grants = self.grants.lock().unwrap();
+        let code = format!("code-{}", grants.len() + 1);
+        grants.insert(
+            code.clone(),
+            Grant {
+                redirect_uri: param("redirect_uri"),
+                code_challenge: param("code_challenge"),
+                nonce: query.get("nonce").cloned(),
+                user: self.user.lock().unwrap().clone(),
+            },
+        );
+        let location = format!(
+            "{}?code={}&state={}",
+            param("redirect_uri"),
+            code,
+            param("state")
+        );
+        Box::new(warp::redirect::found(
+            location.parse::<http::Uri>().expect("Not a valid redirect URI"),
+        ))
+    }
+
+    // Each code is exchanged once, by the client that asked for it, with the
+    // verifier of its challenge.
+    fn token(&self, form: HashMap<String, String>) -> Box<dyn Reply> {
+        let param = |name: &str| form.get(name).cloned().unwrap_or_default();
+        if param("client_id") != CLIENT_ID || param("client_secret") != CLIENT_SECRET {
+            return Box::new(error("invalid_client"));
+        }
+        let grant = match self.grants.lock().unwrap().remove(&param("code")) {
+            Some(grant) => grant,
+            None => return Box::new(error("invalid_grant")),
+        };
+        let challenge = base64::encode_config(
+            Sha256::digest(param("code_verifier").as_bytes()),
+            base64::URL_SAFE_NO_PAD,
+        );
+        if param("grant_type") != "authorization_code"
+            || param("redirect_uri") != grant.redirect_uri
+            || challenge != grant.code_challenge
+        {
+            return Box::new(error("invalid_grant"));
+        }
+        let claims = json!({
+            "iss": self.issuer(),
+            "sub": grant.user.subject,
+            "aud": CLIENT_ID,
+            "exp": now() + 300,
+            "iat": now(),
+            "nonce": grant.nonce,
+            "email": grant.user.email,
+            "email_verified": grant.user.email_verified,
+            "name": grant.user.name,
+        });
+        Box::new(warp::reply::json(&json!({
+            "access_token": "mock access token",
+            "token_type": "Bearer",
+            "expires_in": 300,
+            "id_token": id_token(&claims.to_string()),
+        })))
+    }
+
+    fn build_routes(&self) -> impl Filter<Extract = impl Reply> + Clone {
+        let issuer = self.issuer();
+        let discovery = warp::get()
+            .and(warp::path(".well-known"))
+            .and(warp::path("openid-configuration"))
+            .and(warp::path::end())
+            .map(move || Self::discovery(issuer.clone()));
+        let idp = self.clone();
+        let authorize = warp::get()
+            .and(warp::path("authorize"))
+            .and(warp::path::end())
+            .and(warp::query())
+            .map(move |query| idp.authorize(query));
+        let idp = self.clone();
+        let token = warp::post()
+            .and(warp::path("token"))
+            .and(warp::path::end())
+            .and(warp::body::form())
+            .map(move |form| idp.token(form));
+        discovery.or(authorize).or(token)
+    }
+
+    pub fn oneshot(&self) -> OneshotHandler {
+        let (tx, rx) = oneshot::channel::<i32>();
+        let routes = Self::build_routes(self);
+
+        let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(self.socket, async {
+            rx.await.ok();
+        });
+
+        tokio::task::spawn(server);
+
+        OneshotHandler { sender: tx }
+    }
+}
+
+fn error(code: &str) -> impl Reply {
+    warp::reply::with_status(
+        warp::reply::json(&json!({ "error": code })),
+        http::StatusCode::BAD_REQUEST,
+    )
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("time before the epoch")
+        .as_secs()
+}
+
+// Signed with the client secret, like the HS256 tokens of real providers.
+fn id_token(claims: &str) -> String {
+    let encode = |bytes: &[u8]| base64::encode_config(bytes, base64::URL_SAFE_NO_PAD);
+    let signing_input = format!(
+        "{}.{}",
+        encode(json!({ "alg": "HS256", "typ": "JWT" }).to_string().as_bytes()),
+        encode(claims.as_bytes())
+    );
+    let mut mac = Hmac::<Sha256>::new_from_slice(CLIENT_SECRET.as_bytes())
+        .expect("HMAC takes keys of any length");
+    mac.update(signing_input.as_bytes());
+    format!(
+        "{}.{}",
+        signing_input,
+        encode(&mac.finalize().into_bytes())
+    )
+}
@@ -1,15 +1,88 @@
-use rust_web_dev::config; // rust_web_dev is the project name.
-use rust_web_dev::{run, setup_store};
+// The chain of route filters built by `rust_web_dev::run` has a deep type.
+#![recursion_limit = "256"]
 
-use dotenv;
+use rust_web_dev::config::{self, Command}; // rust_web_dev is the project name.
+use rust_web_dev::mail::{FileMailer, SmtpMailer};
+use rust_web_dev::store::QaStore;
+use rust_web_dev::types::export::ExportOptions;
+use rust_web_dev::{export, import, run, setup_store};
 
 #[tokio::main]
 async fn main() -> Result<(), handle_errors::Error> {
     // Initialize the .env file via the dotenv crate.
     dotenv::dotenv().ok();
     let config = config::Config::new().expect("Config can't be set");
+    // Rotating the keys doesn't need the database.
+    if let Some(Command::RotateKeys { version, retire }) = &config.command {
+        let kid = config.rotate_keys(*version, retire)?;
+        println!("Tokens are now signed with the key {}", kid);
+        return Ok(());
+    }
+    let keyring = config.keyring()?;
     let store = setup_store(&config).await?;
     tracing::info!("Q&A service build ID {}", env!("RUST_WEB_DEV_VERSION"));
-    run(config, store).await;
+    match &config.command {
+        Some(Command::Import {
+            file,
+            format,
+            account_email,
+            on_duplicate,
+        }) => {
+            let account = store.get_account(account_email.clone()).await?;
+            let account_id = account.id.expect("id not found");
+            let report =
+                import::import_file(&store, file, *format, account_id, *on_duplicate).await?;
+            println!(
+                "Inserted: {}, updated: {}, skipped: {}, failed: {}",
+                report.inserted,
+                report.updated,
+                report.skipped,
+                report.failures.len()
+            );
+            for failure in report.failures {
+                println!("{}: {}", failure.record, failure.message);
+            }
+        }
+        Some(Command::Export {
+            file,
+            format,
+            table,
+            include_passwords,
+        }) => {
+            let options = ExportOptions {
+                format: *format,
+                table: *table,
+                include_passwords: *include_passwords,
+            };
+            let lines = export::export_file(&store, file, options).await?;
+            println!("Exported {} lines to {}", lines, file.display());
+        }
+        Some(Command::SetRole { email, role }) => {
+            let account = store.get_account(email.clone()).await?;
+            store
+                .set_account_role(account.id.expect("id not found"), *role)
+                .await?;
+            println!("{} is now {}", email, role);
+        }
+        Some(Command::RotateKeys { .. }) => {}
+        None => {
+            let oidc_providers = config.oidc_providers().await?;
+            match config.smtp_host.clone() {
+                Some(host) => {
+                    let mailer = SmtpMailer::new(
+                        &host,
+                        config.smtp_port,
+                        &config.mail_from,
+                        config.smtp_credentials(),
+                    )?;
+                    run(config, store, mailer, keyring, oidc_providers).await
+                }
+                None => {
+                    let mailer = FileMailer::new(config.mail_file.clone());
+                    run(config, store, mailer, keyring, oidc_providers).await
+                }
+            }
+        }
+    }
     Ok(())
 }
@@ -1,7 +1,15 @@
 #![warn(clippy::all)]
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::env;
+use std::path::PathBuf;
+
+use crate::import::ImportFormat;
+use crate::keyring::{Keyring, KeyringFile, TokenVersion};
+use crate::oidc::OidcProviders;
+use crate::types::account::{DeletedAccountPosts, Role};
+use crate::types::export::{ExportFormat, ExportTable};
+use crate::types::import::OnDuplicate;
 
 /// Q&A web service API
 #[derive(Parser, Debug, PartialEq)]
@@ -32,25 +40,163 @@ pub struct Config {
     /// Log level warp
     #[clap(long, default_value = "error")]
     pub log_level_warp: String,
+    /// Days deleted questions are kept before they are purged
+    #[clap(long, default_value = "30")]
+    pub deleted_questions_retention_days: u32,
+    /// What happens to the posts of deleted accounts (anonymize or remove)
+    #[clap(long, default_value = "anonymize")]
+    pub deleted_account_posts: DeletedAccountPosts,
+    /// Sender of the emails
+    #[clap(long, default_value = "Q&A <noreply@localhost>")]
+    pub mail_from: String,
+    /// File the emails are written to when no SMTP host is set
+    #[clap(long, default_value = "mail.jsonl")]
+    pub mail_file: PathBuf,
+    /// SMTP relay delivering the emails, with the SMTP_USERNAME and
+    /// SMTP_PASSWORD credentials when set
+    #[clap(long)]
+    pub smtp_host: Option<String>,
+    /// PORT number of the SMTP relay
+    #[clap(long, default_value = "587")]
+    pub smtp_port: u16,
+    /// Keyring of the access tokens, written by the rotate-keys command.
+    /// Without it, tokens are encrypted with the PASETO_KEY secret
+    #[clap(long)]
+    pub keyring_file: Option<PathBuf>,
+    /// JSON list of the OpenID Connect identity providers accounts can sign
+    /// in with, one per issuer
+    #[clap(long)]
+    pub oidc_providers_file: Option<PathBuf>,
     /// Which PORT the web server is listening to
     #[clap(long, default_value = "3030")]
     pub web_server_port: u16,
+    /// Task to run instead of the web server
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Import questions and answers from a JSON or JSON Lines file
+    Import {
+        /// File to import
+        file: PathBuf,
+        /// File format (json or jsonl), guessed from the extension by default
+        #[clap(long)]
+        format: Option<ImportFormat>,
+        /// Email of the account that owns the imported questions
+        #[clap(long)]
+        account_email: String,
+        /// What to do with questions whose id already exists (skip or upsert)
+        #[clap(long, default_value = "skip")]
+        on_duplicate: OnDuplicate,
+    },
+    /// Export accounts, questions and answers as JSON Lines, CSV or an archive
+    Export {
+        /// File to write
+        file: PathBuf,
+        /// Output format (jsonl, csv or archive). The archive can be imported back
+        #[clap(long, default_value = "jsonl")]
+        format: ExportFormat,
+        /// Only export this table (accounts, questions or answers); required for csv
+        #[clap(long)]
+        table: Option<ExportTable>,
+        /// Include the password hashes of the accounts
+        #[clap(long)]
+        include_passwords: bool,
+    },
+    /// Add a signing key to the keyring of the access tokens, keeping the
+    /// previous keys to verify the tokens they signed
+    RotateKeys {
+        /// Kind of the new key (v4.public or v2.local)
+        #[clap(long, default_value = "v4.public")]
+        version: TokenVersion,
+        /// Id of a previous key to remove, invalidating its tokens
+        #[clap(long)]
+        retire: Vec<String>,
+    },
+    /// Set the role of an account, to make the first admin of a database.
+    /// Admins then manage the roles with `PUT /admin/accounts/{id}/role`
+    SetRole {
+        /// Email of the account
+        email: String,
+        /// New role (user, moderator or admin)
+        role: Role,
+    },
 }
 
 impl Config {
+    /// Username and password of the SMTP relay, from the environment so that
+    /// they don't show in the command line.
+    pub fn smtp_credentials(&self) -> Option<(String, String)> {
+        match (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            (Ok(username), Ok(password)) => Some((username, password)),
+            _ => None,
+        }
+    }
+
+    /// Keys of the access tokens: the keyring file, or else the PASETO_KEY
+    /// secret. They are read once, at startup.
+    pub fn keyring(&self) -> Result<Keyring, handle_errors::Error> {
+        match &self.keyring_file {
+            Some(path) => Keyring::from_file(&KeyringFile::read(path)?),
+            None => Ok(Keyring::from_secret(
+                &env::var("PASETO_KEY").expect("PASETO_KEY not set"),
+            )),
+        }
+    }
+
+    /// Adds a signing key to the keyring file, which starts with the
+    /// PASETO_KEY secret so that the tokens it encrypted stay valid.
+    /// Returns the id of the new key.
+    pub fn rotate_keys(
+        &self,
+        version: TokenVersion,
+        retire: &[String],
+    ) -> Result<String, handle_errors::Error> {
+        let path = self
+            .keyring_file
+            .as_ref()
+            .ok_or_else(|| handle_errors::Error::KeyringError("no keyring file set".to_string()))?;
+        let mut file = KeyringFile::read(path)?;
+        if file.keys.is_empty() {
+            if let Ok(secret) = env::var("PASETO_KEY") {
+                file = KeyringFile::from_secret(&secret);
+            }
+        }
+        let kid = file.rotate(version, retire)?;
+        // Checked before it's written, so that the server can start with it.
+        Keyring::from_file(&file)?;
+        file.write(path)?;
+        Ok(kid)
+    }
+
+    /// Identity providers of the providers file, with their endpoints
+    /// discovered at startup. There are none without the file.
+    pub async fn oidc_providers(&self) -> Result<OidcProviders, handle_errors::Error> {
+        match &self.oidc_providers_file {
+            Some(path) => OidcProviders::load(path).await,
+            None => Ok(OidcProviders::default()),
+        }
+    }
+
     pub fn new() -> Result<Config, handle_errors::Error> {
         let config = Config::parse();
-        if let Err(_) = env::var("BAD_WORDS_API_KEY") {
+        if env::var("BAD_WORDS_API_KEY").is_err() {
             panic!("BadWords API key not set");
         }
-        if let Err(_) = env::var("PASETO_KEY") {
+        let keyring_file = env::var("PASETO_KEYRING_FILE")
+            .ok()
+            .map(PathBuf::from)
+            .or(config.keyring_file);
+        if env::var("PASETO_KEY").is_err() && keyring_file.is_none() {
             panic!("PASETO_KEY not set");
         }
         let web_server_port = std::env::var("PORT")
             .ok()
             .map(|val| val.parse::<u16>())
             .unwrap_or(Ok(config.web_server_port))
-            .map_err(|e| handle_errors::Error::ParseError(e))?;
+            .map_err(handle_errors::Error::ParseError)?;
         // TODO .map_err(|e| handle_errors::Error::ParseError(e))
         // TODO .expect("Cannot parse port");
 
@@ -60,6 +206,11 @@ impl Config {
         let database_host = env::var("POSTGRES_HOST").unwrap_or(config.database_host.to_owned());
         let database_port = env::var("POSTGRES_PORT").unwrap_or(config.database_port.to_string());
         let database_name = env::var("POSTGRES_DB").unwrap_or(config.database_name.to_owned());
+        let smtp_host = env::var("SMTP_HOST").ok().or(config.smtp_host);
+        let oidc_providers_file = env::var("OIDC_PROVIDERS_FILE")
+            .ok()
+            .map(PathBuf::from)
+            .or(config.oidc_providers_file);
         Ok(Config {
             web_server_port,
             database_user,
@@ -67,11 +218,20 @@ impl Config {
             database_host,
             database_port: database_port
                 .parse::<u16>()
-                .map_err(|e| handle_errors::Error::ParseError(e))?,
+                .map_err(handle_errors::Error::ParseError)?,
             database_name,
             log_level_handle_errors: config.log_level_handle_errors,
             log_level_rust_web_dev: config.log_level_rust_web_dev,
             log_level_warp: config.log_level_warp,
+            deleted_questions_retention_days: config.deleted_questions_retention_days,
+            deleted_account_posts: config.deleted_account_posts,
+            mail_from: config.mail_from,
+            mail_file: config.mail_file,
+            smtp_host,
+            smtp_port: config.smtp_port,
+            keyring_file,
+            oidc_providers_file,
+            command: config.command,
         })
     }
 }
@@ -98,7 +258,7 @@ mod config_tests {
     fn unset_and_set_api_key() {
         // The env variables are not set.
         // catch_unwind: captures panics without bringing down the program.
-        let result = std::panic::catch_unwind(|| Config::new());
+        let result = std::panic::catch_unwind(Config::new);
         assert!(result.is_err());
 
         // Now we set the env variables.
@@ -113,7 +273,16 @@ mod config_tests {
             log_level_handle_errors: "warn".to_string(),
             log_level_rust_web_dev: "info".to_string(),
             log_level_warp: "error".to_string(),
+            deleted_questions_retention_days: 30,
+            deleted_account_posts: DeletedAccountPosts::Anonymize,
+            mail_from: "Q&A <noreply@localhost>".to_string(),
+            mail_file: PathBuf::from("mail.jsonl"),
+            smtp_host: None,
+            smtp_port: 587,
+            keyring_file: None,
+            oidc_providers_file: None,
             web_server_port: 3030,
+            command: None,
         };
 
         let config = Config::new().unwrap();
@@ -0,0 +1,386 @@
+use std::path::Path;
+use std::pin::Pin;
+
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::store::QaStore;
+use crate::types::account::Account;
+use crate::types::export::{
+    AccountRecord, AnswerRecord, ExportFormat, ExportOptions, ExportRecord, ExportTable,
+    QuestionRecord,
+};
+use crate::types::import::{ImportAnswer, ImportQuestion};
+use handle_errors::Error;
+
+type Lines = BoxStream<'static, Result<String, Error>>;
+
+/// Streams the export as lines ending in a newline.
+/// Rows are read from the store while the lines are consumed.
+pub fn export<S: QaStore>(store: &S, options: ExportOptions) -> Result<Lines, Error> {
+    match options.format {
+        ExportFormat::JsonLines => Ok(json_lines(store, &options)),
+        ExportFormat::Csv => match options.table {
+            Some(table) => Ok(csv(store, table, options.include_passwords)),
+            None => Err(Error::InvalidParameter(
+                "csv exports need a table".to_string(),
+            )),
+        },
+        ExportFormat::Archive => Ok(archive(store)),
+    }
+}
+
+/// Writes the export to a file and returns the number of lines written.
+pub async fn export_file<S: QaStore>(
+    store: &S,
+    path: &Path,
+    options: ExportOptions,
+) -> Result<u64, Error> {
+    let mut lines = export(store, options)?;
+    let file = tokio::fs::File::create(path)
+        .await
+        .map_err(Error::IoError)?;
+    let mut writer = tokio::io::BufWriter::new(file);
+    let mut count = 0;
+    while let Some(line) = lines.try_next().await? {
+        writer
+            .write_all(line.as_bytes())
+            .await
+            .map_err(Error::IoError)?;
+        count += 1;
+    }
+    writer.flush().await.map_err(Error::IoError)?;
+    Ok(count)
+}
+
+fn json_lines<S: QaStore>(store: &S, options: &ExportOptions) -> Lines {
+    let include = |table| options.table.is_none_or(|selected| selected == table);
+    let include_passwords = options.include_passwords;
+    let mut tables: Vec<BoxStream<'static, Result<ExportRecord, Error>>> = Vec::new();
+    if include(ExportTable::Accounts) {
+        tables.push(
+            store
+                .stream_accounts()
+                .map_ok(move |account| {
+                    ExportRecord::Accounts(account_record(account, include_passwords))
+                })
+                .boxed(),
+        );
+    }
+    if include(ExportTable::Questions) {
+        tables.push(
+            store
+                .stream_questions()
+                .map_ok(ExportRecord::Questions)
+                .boxed(),
+        );
+    }
+    if include(ExportTable::Answers) {
+        tables.push(store.stream_answers().map_ok(ExportRecord::Answers).boxed());
+    }
+    stream::iter(tables)
+        .flatten()
+        .map_ok(|record| to_line(&record))
+        .boxed()
+}
+
+fn csv<S: QaStore>(store: &S, table: ExportTable, include_passwords: bool) -> Lines {
+    let (header, rows) = match table {
+        ExportTable::Accounts => {
+            let header = if include_passwords {
+                "id,email,password"
+            } else {
+                "id,email"
+            };
+            let rows = store
+                .stream_accounts()
+                .map_ok(move |account| {
+                    let account = account_record(account, include_passwords);
+                    let mut fields = vec![account.id.0.to_string(), account.email];
+                    fields.extend(account.password);
+                    fields
+                })
+                .boxed();
+            (header, rows)
+        }
+        ExportTable::Questions => {
+            let rows = store
+                .stream_questions()
+                .map_ok(|question: QuestionRecord| {
+                    vec![
+                        question.id.0.to_string(),
+                        question.title,
+                        question.content,
+                        question.tags.unwrap_or_default().join(";"),
+                        question
+                            .accepted_answer_id
+                            .map(|id| id.0.to_string())
+                            .unwrap_or_default(),
+                        question.account_id.0.to_string(),
+                        question
+                            .deleted_at
+                            .map(|at| at.to_string())
+                            .unwrap_or_default(),
+                    ]
+                })
+                .boxed();
+            (
+                "id,title,content,tags,accepted_answer_id,account_id,deleted_at",
+                rows,
+            )
+        }
+        ExportTable::Answers => {
+            let rows = store
+                .stream_answers()
+                .map_ok(|answer: AnswerRecord| {
+                    vec![
+                        answer.id.0.to_string(),
+                        answer.content,
+                        answer.question_id.0.to_string(),
+                        answer.account_id.0.to_string(),
+                    ]
+                })
+                .boxed();
+            ("id,content,question_id,account_id", rows)
+        }
+    };
+    stream::once(async move { Ok(format!("{}\n", header)) })
+        .chain(rows.map_ok(|fields| csv_line(&fields)))
+        .boxed()
+}
+
+/// Questions with their answers, in the layout of `ImportQuestion`, so the
+/// file can be loaded again with the import command.
+fn archive<S: QaStore>(store: &S) -> Lines {
+    let mut questions = store.stream_questions();
+    let mut answers = store.stream_answers().peekable();
+    Box::pin(async_stream::try_stream! {
+        while let Some(question) = questions.try_next().await? {
+            let mut archived = ImportQuestion {
+                id: Some(question.id.clone()),
+                title: question.title,
+                content: question.content,
+                tags: question.tags,
+                accepted_answer_id: question.accepted_answer_id,
+                deleted_at: question.deleted_at,
+                answers: Vec::new(),
+            };
+            // Both streams are sorted by question id, so the answers of this
+            // question are the next ones. Answers whose question isn't
+            // exported are skipped.
+            loop {
+                match Pin::new(&mut answers).peek().await {
+                    Some(Ok(answer)) if answer.question_id.0 <= question.id.0 => {}
+                    Some(Err(_)) => {}
+                    _ => break,
+                }
+                let answer = answers.try_next().await?.expect("peeked answer");
+                if answer.question_id == question.id {
+                    archived.answers.push(ImportAnswer {
+                        id: Some(answer.id),
+                        content: answer.content,
+                    });
+                }
+            }
+            yield to_line(&archived);
+        }
+    })
+}
+
+fn account_record(account: Account, include_passwords: bool) -> AccountRecord {
+    AccountRecord {
+        id: account.id.expect("id not found"),
+        email: account.email,
+        password: if include_passwords {
+            Some(account.password)
+        } else {
+            None
+        },
+    }
+}
+
+fn to_line<T: Serialize>(record: &T) -> String {
+    let mut line = serde_json::to_string(record).expect("records serialize to JSON");
+    line.push('\n');
+    line
+}
+
+fn csv_line(fields: &[String]) -> String {
+    let mut line = fields
+        .iter()
+        .map(|field| {
+            if field.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod export_tests {
+    use futures::TryStreamExt;
+
+    use super::export;
+    use crate::import::{import_str, ImportFormat};
+    use crate::store::{InMemoryStore, QaStore};
+    use crate::types::account::{Account, AccountId, Role};
+    use crate::types::answer::AnswerId;
+    use crate::types::export::{ExportFormat, ExportOptions, ExportTable};
+    use crate::types::import::OnDuplicate;
+
+    async fn store_with_data() -> InMemoryStore {
+        let store = InMemoryStore::new();
+        store
+            .add_account(Account {
+                id: None,
+                email: "foo@bar.com".to_string(),
+                password: "hash".to_string(),
+                role: Role::User,
+                verified: true,
+                display_name: None,
+                bio: None,
+            })
+            .await
+            .unwrap();
+        let input = r#"
+{"id": 2, "title": "Second", "content": "No answers", "tags": null}
+{"id": 1, "title": "First, \"quoted\"", "content": "Two answers", "tags": ["a", "b"], "accepted_answer_id": 2, "answers": [{"content": "One"}, {"id": 2, "content": "Two"}]}
+"#;
+        import_str(
+            &store,
+            input,
+            ImportFormat::JsonLines,
+            AccountId(1),
+            OnDuplicate::Skip,
+        )
+        .await
+        .unwrap();
+        store
+    }
+
+    async fn collect(store: &InMemoryStore, options: ExportOptions) -> String {
+        let lines: Vec<String> = export(store, options).unwrap().try_collect().await.unwrap();
+        lines.concat()
+    }
+
+    #[tokio::test]
+    async fn passwords_are_excluded_by_default() {
+        let store = store_with_data().await;
+        let options = ExportOptions {
+            format: ExportFormat::JsonLines,
+            table: Some(ExportTable::Accounts),
+            include_passwords: false,
+        };
+        let output = collect(&store, options).await;
+        assert_eq!(
+            output,
+            "{\"table\":\"accounts\",\"id\":1,\"email\":\"foo@bar.com\"}\n"
+        );
+
+        let options = ExportOptions {
+            format: ExportFormat::Csv,
+            table: Some(ExportTable::Accounts),
+            include_passwords: true,
+        };
+        let output = collect(&store, options).await;
+        assert_eq!(output, "id,email,password\n1,foo@bar.com,hash\n");
+    }
+
+    #[tokio::test]
+    async fn csv_fields_are_escaped() {
+        let store = store_with_data().await;
+        let options = ExportOptions {
+            format: ExportFormat::Csv,
+            table: Some(ExportTable::Questions),
+            include_passwords: false,
+        };
+        let output = collect(&store, options).await;
+        let expected = "id,title,content,tags,accepted_answer_id,account_id,deleted_at
+1,\"First, \"\"quoted\"\"\",Two answers,a;b,2,1,
+2,Second,No answers,,,1,
+";
+        assert_eq!(output, expected);
+    }
+
+    #[tokio::test]
+    async fn archive_can_be_imported_back() {
+        let store = store_with_data().await;
+        let options = ExportOptions {
+            format: ExportFormat::Archive,
+            table: None,
+            include_passwords: false,
+        };
+        let archive = collect(&store, options).await;
+
+        let copy = InMemoryStore::new();
+        let report = import_str(
+            &copy,
+            &archive,
+            ImportFormat::JsonLines,
+            AccountId(1),
+            OnDuplicate::Skip,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.inserted, 2);
+        assert_eq!(
+            copy.get_question(1).await.unwrap().title,
+            "First, \"quoted\""
+        );
+        let answers = copy.get_answers_of_question(1, None, 0).await.unwrap();
+        let contents: Vec<&str> = answers.iter().map(|a| a.content.as_str()).collect();
+        assert_eq!(contents, ["Two", "One"]);
+        assert!(copy
+            .get_answers_of_question(2, None, 0)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn deleted_questions_are_archived_and_stay_deleted() {
+        let store = store_with_data().await;
+        store.delete_question(1).await.unwrap();
+        let options = ExportOptions {
+            format: ExportFormat::Archive,
+            table: None,
+            include_passwords: false,
+        };
+        let archive = collect(&store, options).await;
+        let lines: Vec<&str> = archive.lines().collect();
+        assert!(lines[0].contains("\"deleted_at\":"));
+        assert!(!lines[1].contains("deleted_at"));
+
+        let copy = InMemoryStore::new();
+        let report = import_str(
+            &copy,
+            &archive,
+            ImportFormat::JsonLines,
+            AccountId(1),
+            OnDuplicate::Skip,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.inserted, 2);
+        assert!(copy.get_question(1).await.is_err());
+        let question = copy.restore_question(1).await.unwrap();
+        assert_eq!(question.accepted_answer_id, Some(AnswerId(2)));
+        assert_eq!(
+            copy.get_answers_of_question(1, None, 0)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(copy.get_question(2).await.unwrap().title, "Second");
+    }
+}
@@ -0,0 +1,290 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::store::QaStore;
+use crate::types::account::AccountId;
+use crate::types::import::{
+    ImportOutcome, ImportQuestion, ImportReport, OnDuplicate, RecordFailure,
+};
+use handle_errors::Error;
+
+// Length of the questions.title column.
+const MAX_TITLE_LENGTH: usize = 255;
+
+/// Layout of an import file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// One document: an array of questions, a single question or an object
+    /// whose values are questions (the layout of `questions.json`).
+    Json,
+    /// One question per line.
+    JsonLines,
+}
+
+impl ImportFormat {
+    /// `.jsonl` and `.ndjson` files are read as JSON Lines, anything else as JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("jsonl") | Some("ndjson") => ImportFormat::JsonLines,
+            _ => ImportFormat::Json,
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(ImportFormat::Json),
+            "jsonl" => Ok(ImportFormat::JsonLines),
+            _ => Err(format!("expected json or jsonl, got {}", value)),
+        }
+    }
+}
+
+/// Imports the questions of a file. Without `format`, it is guessed from
+/// the file extension.
+pub async fn import_file<S: QaStore>(
+    store: &S,
+    path: &Path,
+    format: Option<ImportFormat>,
+    account_id: AccountId,
+    on_duplicate: OnDuplicate,
+) -> Result<ImportReport, Error> {
+    let input = tokio::fs::read_to_string(path)
+        .await
+        .map_err(Error::IoError)?;
+    let format = format.unwrap_or_else(|| ImportFormat::from_path(path));
+    import_str(store, &input, format, account_id, on_duplicate).await
+}
+
+/// Imports the questions, and their answers, owned by `account_id`.
+/// Records that can't be parsed or stored are listed in the report
+/// instead of stopping the import.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::import::{import_str, ImportFormat};
+/// use rust_web_dev::store::InMemoryStore;
+/// use rust_web_dev::types::{account::AccountId, import::OnDuplicate};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let input = r#"{"title": "How?", "content": "Please help!", "tags": null}
+/// not a question"#;
+/// let store = InMemoryStore::new();
+/// let report = import_str(&store, input, ImportFormat::JsonLines, AccountId(1), OnDuplicate::Skip)
+///     .await
+///     .unwrap();
+/// assert_eq!(report.inserted, 1);
+/// assert_eq!(report.failures[0].record, "line 2");
+/// # }
+/// ```
+pub async fn import_str<S: QaStore>(
+    store: &S,
+    input: &str,
+    format: ImportFormat,
+    account_id: AccountId,
+    on_duplicate: OnDuplicate,
+) -> Result<ImportReport, Error> {
+    let mut report = ImportReport::default();
+    // Position of each failure in the input, to list them in order.
+    let mut failures = Vec::new();
+    let mut records = Vec::new();
+    let mut questions = Vec::new();
+    for (position, (record, question)) in parse_records(input, format).into_iter().enumerate() {
+        match question.and_then(validate) {
+            Ok(question) => {
+                records.push((position, record));
+                questions.push(question);
+            }
+            Err(message) => failures.push((position, RecordFailure { record, message })),
+        }
+    }
+    let outcomes = store
+        .import_questions(questions, account_id, on_duplicate)
+        .await?;
+    for ((position, record), outcome) in records.into_iter().zip(outcomes) {
+        match outcome {
+            Ok(ImportOutcome::Inserted) => report.inserted += 1,
+            Ok(ImportOutcome::Updated) => report.updated += 1,
+            Ok(ImportOutcome::Skipped) => report.skipped += 1,
+            Err(message) => failures.push((position, RecordFailure { record, message })),
+        }
+    }
+    failures.sort_by_key(|(position, _)| *position);
+    report.failures = failures.into_iter().map(|(_, failure)| failure).collect();
+    Ok(report)
+}
+
+type ParsedRecord = (String, Result<ImportQuestion, String>);
+
+fn parse_records(input: &str, format: ImportFormat) -> Vec<ParsedRecord> {
+    match format {
+        ImportFormat::JsonLines => input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                (
+                    format!("line {}", index + 1),
+                    serde_json::from_str(line).map_err(|e| e.to_string()),
+                )
+            })
+            .collect(),
+        ImportFormat::Json => match serde_json::from_str::<Value>(input) {
+            Ok(Value::Array(values)) => values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| (format!("index {}", index), from_value(value)))
+                .collect(),
+            Ok(Value::Object(object)) if object.contains_key("title") => {
+                vec![("document".to_string(), from_value(Value::Object(object)))]
+            }
+            Ok(Value::Object(object)) => object
+                .into_iter()
+                .map(|(key, value)| (format!("key {:?}", key), from_value(value)))
+                .collect(),
+            Ok(_) => vec![(
+                "document".to_string(),
+                Err("expected an array or an object of questions".to_string()),
+            )],
+            Err(e) => vec![("document".to_string(), Err(e.to_string()))],
+        },
+    }
+}
+
+fn from_value(value: Value) -> Result<ImportQuestion, String> {
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+fn validate(question: ImportQuestion) -> Result<ImportQuestion, String> {
+    if question.title.trim().is_empty() {
+        return Err("title is empty".to_string());
+    }
+    if question.title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(format!(
+            "title is longer than {} characters",
+            MAX_TITLE_LENGTH
+        ));
+    }
+    if question.content.trim().is_empty() {
+        return Err("content is empty".to_string());
+    }
+    if question
+        .answers
+        .iter()
+        .any(|answer| answer.content.trim().is_empty())
+    {
+        return Err("answer content is empty".to_string());
+    }
+    if let Some(accepted_answer_id) = &question.accepted_answer_id {
+        if !question
+            .answers
+            .iter()
+            .any(|answer| answer.id.as_ref() == Some(accepted_answer_id))
+        {
+            return Err(format!(
+                "accepted answer {} is not one of the answers",
+                accepted_answer_id.0
+            ));
+        }
+    }
+    Ok(question)
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::{import_str, ImportFormat};
+    use crate::store::{InMemoryStore, QaStore};
+    use crate::types::{account::AccountId, import::OnDuplicate};
+
+    #[tokio::test]
+    async fn import_questions_json() {
+        let store = InMemoryStore::new();
+        let input = include_str!("../questions.json");
+        let report = import_str(
+            &store,
+            input,
+            ImportFormat::Json,
+            AccountId(7),
+            OnDuplicate::Skip,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.inserted, 1);
+        assert!(report.failures.is_empty());
+        assert_eq!(store.get_question(0).await.unwrap().title, "How?");
+        assert!(store.is_question_owner(0, &AccountId(7)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn failures_are_reported_per_record() {
+        let store = InMemoryStore::new();
+        let input = r#"
+{"title": "First", "content": "With answers", "tags": null, "answers": [{"content": "Yes"}]}
+{"title": "", "content": "No title", "tags": null}
+{"title": "Broken"
+{"title": "Last", "content": "Still imported", "tags": ["general"]}
+"#;
+        let report = import_str(
+            &store,
+            input,
+            ImportFormat::JsonLines,
+            AccountId(1),
+            OnDuplicate::Skip,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.inserted, 2);
+        let records: Vec<&str> = report
+            .failures
+            .iter()
+            .map(|failure| failure.record.as_str())
+            .collect();
+        assert_eq!(records, ["line 3", "line 4"]);
+        assert_eq!(report.failures[0].message, "title is empty");
+        assert_eq!(
+            store
+                .get_answers_of_question(1, None, 0)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicates_are_skipped_or_upserted() {
+        let store = InMemoryStore::new();
+        let first = r#"[{"id": 3, "title": "Old", "content": "Old", "tags": null}]"#;
+        let second = r#"[{"id": 3, "title": "New", "content": "New", "tags": null}]"#;
+        for (input, on_duplicate) in [(first, OnDuplicate::Skip), (second, OnDuplicate::Skip)] {
+            import_str(
+                &store,
+                input,
+                ImportFormat::Json,
+                AccountId(1),
+                on_duplicate,
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(store.get_question(3).await.unwrap().title, "Old");
+
+        let report = import_str(
+            &store,
+            second,
+            ImportFormat::Json,
+            AccountId(2),
+            OnDuplicate::Upsert,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.updated, 1);
+        assert_eq!(store.get_question(3).await.unwrap().title, "New");
+        assert!(store.is_question_owner(3, &AccountId(2)).await.unwrap());
+    }
+}
@@ -0,0 +1,489 @@
+//! Keys of the access tokens. Tokens are signed, or encrypted, with one key
+//! and verified with any key of the keyring, found by the key id in their
+//! footer, so that keys can be rotated without signing everyone out.
+//!
+//! Local keys (`v2.local`) are secrets shared by whoever verifies the
+//! tokens. Public keys (`v4.public`) sign the tokens with a private key and
+//! are verified with the public key alone, which other services can be
+//! given.
+
+use chrono::Utc;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use handle_errors::Error;
+
+/// Key id of the `PASETO_KEY` secret, also used for the tokens without key
+/// id issued before the keyring.
+pub const DEFAULT_KID: &str = "default";
+const V4_PUBLIC_HEADER: &str = "v4.public.";
+const SIGNATURE_LENGTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub enum TokenVersion {
+    #[serde(rename = "v2.local")]
+    V2Local,
+    #[serde(rename = "v4.public")]
+    V4Public,
+}
+
+impl TokenVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenVersion::V2Local => "v2.local",
+            TokenVersion::V4Public => "v4.public",
+        }
+    }
+}
+
+impl fmt::Display for TokenVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TokenVersion {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "v2.local" => Ok(TokenVersion::V2Local),
+            "v4.public" => Ok(TokenVersion::V4Public),
+            _ => Err(format!("expected v2.local or v4.public, got {}", value)),
+        }
+    }
+}
+
+/// Keyring as written in its file. Keys are base64url without padding.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct KeyringFile {
+    /// Key id of the key new tokens are signed with.
+    pub signing_key: String,
+    pub keys: Vec<KeyEntry>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct KeyEntry {
+    pub kid: String,
+    pub version: TokenVersion,
+    /// Secret of a local key, or PKCS#8 private key of a public key. Public
+    /// keys without it only verify tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// Ed25519 public key of a public key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+/// Public key that services verifying the tokens can be given, listed by
+/// `GET /auth/keys`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct PublicKey {
+    pub kid: String,
+    pub version: TokenVersion,
+    pub public_key: String,
+}
+
+impl KeyringFile {
+    /// Keyring of the `PASETO_KEY` secret, as local key.
+    pub fn from_secret(secret: &str) -> KeyringFile {
+        KeyringFile {
+            signing_key: DEFAULT_KID.to_string(),
+            keys: vec![KeyEntry {
+                kid: DEFAULT_KID.to_string(),
+                version: TokenVersion::V2Local,
+                secret: Some(encode(secret.as_bytes())),
+                public_key: None,
+            }],
+        }
+    }
+
+    /// Reads the keyring, or starts one when there is no file yet.
+    pub fn read(path: &Path) -> Result<KeyringFile, Error> {
+        if !path.exists() {
+            return Ok(KeyringFile::default());
+        }
+        let text = std::fs::read_to_string(path).map_err(Error::IoError)?;
+        serde_json::from_str(&text).map_err(|e| Error::KeyringError(e.to_string()))
+    }
+
+    /// Writes the keyring, readable by its owner only.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let text = serde_json::to_string_pretty(self).expect("keyring is serializable");
+        std::fs::write(path, text).map_err(Error::IoError)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .map_err(Error::IoError)?;
+        }
+        Ok(())
+    }
+
+    /// Adds a new key of the version and signs with it from now on. The
+    /// previous keys are kept to verify the tokens they signed, except the
+    /// retired ones. Returns the id of the new key.
+    pub fn rotate(&mut self, version: TokenVersion, retire: &[String]) -> Result<String, Error> {
+        if retire.contains(&self.signing_key) {
+            return Err(Error::KeyringError(format!(
+                "{} is the signing key",
+                self.signing_key
+            )));
+        }
+        self.keys.retain(|key| !retire.contains(&key.kid));
+        let kid = format!(
+            "{}-{}",
+            version.as_str().replace('.', "-"),
+            Utc::now().format("%Y%m%d%H%M%S")
+        );
+        let entry = match version {
+            TokenVersion::V2Local => KeyEntry {
+                kid: kid.clone(),
+                version,
+                secret: Some(encode(&rand::random::<[u8; 32]>())),
+                public_key: None,
+            },
+            TokenVersion::V4Public => {
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                    .map_err(|_| Error::KeyringError("cannot generate a key".to_string()))?;
+                let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+                    .map_err(|_| Error::KeyringError("cannot generate a key".to_string()))?;
+                KeyEntry {
+                    kid: kid.clone(),
+                    version,
+                    secret: Some(encode(pkcs8.as_ref())),
+                    public_key: Some(encode(key_pair.public_key().as_ref())),
+                }
+            }
+        };
+        if self.keys.iter().any(|key| key.kid == kid) {
+            return Err(Error::KeyringError(format!("{} already exists", kid)));
+        }
+        self.keys.push(entry);
+        self.signing_key = kid.clone();
+        Ok(kid)
+    }
+}
+
+enum KeyMaterial {
+    Local(Vec<u8>),
+    Public {
+        key_pair: Option<Ed25519KeyPair>,
+        public_key: Vec<u8>,
+    },
+}
+
+/// Keys of the access tokens, loaded once at startup and shared by the
+/// routes.
+#[derive(Clone)]
+pub struct Keyring {
+    signing_kid: String,
+    keys: Arc<BTreeMap<String, KeyMaterial>>,
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("signing_kid", &self.signing_kid)
+            .field("kids", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Keyring {
+    /// Keyring of the single `PASETO_KEY` secret, a local key.
+    pub fn from_secret(secret: &str) -> Keyring {
+        let mut keys = BTreeMap::new();
+        keys.insert(
+            DEFAULT_KID.to_string(),
+            KeyMaterial::Local(secret.as_bytes().to_vec()),
+        );
+        Keyring {
+            signing_kid: DEFAULT_KID.to_string(),
+            keys: Arc::new(keys),
+        }
+    }
+
+    pub fn from_file(file: &KeyringFile) -> Result<Keyring, Error> {
+        let invalid = |kid: &str, what: &str| Error::KeyringError(format!("{} of {}", what, kid));
+        let mut keys = BTreeMap::new();
+        for entry in &file.keys {
+            let secret = entry
+                .secret
+                .as_deref()
+                .map(decode)
+                .transpose()
+                .map_err(|_| invalid(&entry.kid, "invalid secret"))?;
+            let material = match entry.version {
+                TokenVersion::V2Local => match secret {
+                    Some(secret) if secret.len() == 32 => KeyMaterial::Local(secret),
+                    _ => return Err(invalid(&entry.kid, "32 bytes secret expected")),
+                },
+                TokenVersion::V4Public => {
+                    let public_key = entry
+                        .public_key
+                        .as_deref()
+                        .map(decode)
+                        .transpose()
+                        .ok()
+                        .flatten()
+                        .filter(|key| key.len() == 32)
+                        .ok_or_else(|| invalid(&entry.kid, "invalid public key"))?;
+                    let key_pair = secret
+                        .map(|secret| Ed25519KeyPair::from_pkcs8(&secret))
+                        .transpose()
+                        .map_err(|_| invalid(&entry.kid, "invalid private key"))?;
+                    if key_pair
+                        .as_ref()
+                        .is_some_and(|pair| pair.public_key().as_ref() != public_key.as_slice())
+                    {
+                        return Err(invalid(&entry.kid, "mismatched public key"));
+                    }
+                    KeyMaterial::Public {
+                        key_pair,
+                        public_key,
+                    }
+                }
+            };
+            if keys.insert(entry.kid.clone(), material).is_some() {
+                return Err(invalid(&entry.kid, "duplicate key id"));
+            }
+        }
+        match keys.get(&file.signing_key) {
+            Some(KeyMaterial::Local(_))
+            | Some(KeyMaterial::Public {
+                key_pair: Some(_), ..
+            }) => {}
+            _ => {
+                return Err(Error::KeyringError(format!(
+                    "no private key for the signing key {}",
+                    file.signing_key
+                )))
+            }
+        }
+        Ok(Keyring {
+            signing_kid: file.signing_key.clone(),
+            keys: Arc::new(keys),
+        })
+    }
+
+    pub fn signing_kid(&self) -> &str {
+        &self.signing_kid
+    }
+
+    /// Token of the claims, with the id of the signing key in its footer.
+    pub fn sign(&self, claims: &Value) -> String {
+        let message = claims.to_string();
+        let footer = serde_json::json!({ "kid": self.signing_kid }).to_string();
+        match &self.keys[&self.signing_kid] {
+            KeyMaterial::Local(secret) => paseto::v2::local_paseto(&message, Some(&footer), secret)
+                .expect("Failed to construct paseto token!"),
+            KeyMaterial::Public { key_pair, .. } => {
+                let key_pair = key_pair.as_ref().expect("signing key has a private key");
+                sign_v4_public(&message, &footer, key_pair)
+            }
+        }
+    }
+
+    /// Claims of the token, when one of the keys signed it and it hasn't
+    /// expired.
+    pub fn verify(&self, token: &str) -> Result<Value, Error> {
+        let parts: Vec<&str> = token.split('.').collect();
+        let footer = match parts.get(3) {
+            Some(footer) => Some(
+                decode(footer)
+                    .ok()
+                    .and_then(|footer| String::from_utf8(footer).ok())
+                    .ok_or(Error::CannotDecryptToken)?,
+            ),
+            None => None,
+        };
+        let kid = match &footer {
+            Some(footer) => serde_json::from_str::<Value>(footer)
+                .ok()
+                .and_then(|footer| footer["kid"].as_str().map(String::from))
+                .ok_or(Error::CannotDecryptToken)?,
+            None => DEFAULT_KID.to_string(),
+        };
+        let message = match (self.keys.get(&kid), parts.first(), parts.get(1)) {
+            (Some(KeyMaterial::Local(secret)), Some(&"v2"), Some(&"local")) => {
+                paseto::v2::decrypt_paseto(token, footer.as_deref(), secret)
+                    .map_err(|_| Error::CannotDecryptToken)?
+            }
+            (Some(KeyMaterial::Public { public_key, .. }), Some(&"v4"), Some(&"public")) => {
+                verify_v4_public(&parts, footer.as_deref().unwrap_or(""), public_key)?
+            }
+            _ => return Err(Error::CannotDecryptToken),
+        };
+        paseto::tokens::validate_potential_json_blob(&message, &paseto::tokens::TimeBackend::Chrono)
+            .map_err(|_| Error::CannotDecryptToken)
+    }
+
+    /// Public keys, for the services verifying `v4.public` tokens.
+    pub fn public_keys(&self) -> Vec<PublicKey> {
+        self.keys
+            .iter()
+            .filter_map(|(kid, material)| match material {
+                KeyMaterial::Public { public_key, .. } => Some(PublicKey {
+                    kid: kid.clone(),
+                    version: TokenVersion::V4Public,
+                    public_key: encode(public_key),
+                }),
+                KeyMaterial::Local(_) => None,
+            })
+            .collect()
+    }
+}
+
+// Pre-authentication encoding of PASETO.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let mut encoded = (pieces.len() as u64).to_le_bytes().to_vec();
+    for piece in pieces {
+        encoded.extend_from_slice(&(piece.len() as u64).to_le_bytes());
+        encoded.extend_from_slice(piece);
+    }
+    encoded
+}
+
+// Without implicit assertions.
+fn sign_v4_public(message: &str, footer: &str, key_pair: &Ed25519KeyPair) -> String {
+    let pre_auth = pae(&[
+        V4_PUBLIC_HEADER.as_bytes(),
+        message.as_bytes(),
+        footer.as_bytes(),
+        b"",
+    ]);
+    let mut body = message.as_bytes().to_vec();
+    body.extend_from_slice(key_pair.sign(&pre_auth).as_ref());
+    format!(
+        "{}{}.{}",
+        V4_PUBLIC_HEADER,
+        encode(&body),
+        encode(footer.as_bytes())
+    )
+}
+
+fn verify_v4_public(parts: &[&str], footer: &str, public_key: &[u8]) -> Result<String, Error> {
+    let body = parts
+        .get(2)
+        .and_then(|body| decode(body).ok())
+        .filter(|body| body.len() > SIGNATURE_LENGTH)
+        .ok_or(Error::CannotDecryptToken)?;
+    let (message, signature) = body.split_at(body.len() - SIGNATURE_LENGTH);
+    let pre_auth = pae(&[V4_PUBLIC_HEADER.as_bytes(), message, footer.as_bytes(), b""]);
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(&pre_auth, signature)
+        .map_err(|_| Error::CannotDecryptToken)?;
+    String::from_utf8(message.to_vec()).map_err(|_| Error::CannotDecryptToken)
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(text: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    base64::decode_config(text, base64::URL_SAFE_NO_PAD)
+}
+
+#[cfg(test)]
+mod keyring_tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn claims(minutes: i64) -> Value {
+        serde_json::json!({
+            "exp": Utc::now() + Duration::minutes(minutes),
+            "account_id": 1,
+        })
+    }
+
+    #[test]
+    fn rotated_keys_still_verify_their_tokens() {
+        let mut file = KeyringFile::default();
+        let local_kid = file.rotate(TokenVersion::V2Local, &[]).unwrap();
+        let local = Keyring::from_file(&file).unwrap();
+        let local_token = local.sign(&claims(5));
+        assert!(local_token.starts_with("v2.local."));
+        // The key id is made of the time, so it has to change.
+        file.keys[0].kid = "old".to_string();
+        file.signing_key = "old".to_string();
+        let local_token = Keyring::from_file(&file).unwrap().sign(&claims(5));
+
+        let public_kid = file.rotate(TokenVersion::V4Public, &[]).unwrap();
+        assert_ne!(public_kid, local_kid);
+        let keyring = Keyring::from_file(&file).unwrap();
+        let public_token = keyring.sign(&claims(5));
+        assert!(public_token.starts_with("v4.public."));
+        assert_eq!(keyring.verify(&local_token).unwrap()["account_id"], 1);
+        assert_eq!(keyring.verify(&public_token).unwrap()["account_id"], 1);
+        assert!(keyring.verify(&keyring.sign(&claims(-1))).is_err());
+
+        // Services with the public key alone verify the public tokens.
+        let public = keyring.public_keys();
+        assert_eq!(public.len(), 1);
+        let verifier = KeyringFile {
+            signing_key: "old".to_string(),
+            keys: vec![
+                file.keys[0].clone(),
+                KeyEntry {
+                    kid: public[0].kid.clone(),
+                    version: TokenVersion::V4Public,
+                    secret: None,
+                    public_key: Some(public[0].public_key.clone()),
+                },
+            ],
+        };
+        let verifier = Keyring::from_file(&verifier).unwrap();
+        assert!(verifier.verify(&public_token).is_ok());
+        let mut tampered = public_token.clone();
+        tampered.replace_range(
+            12..13,
+            if &public_token[12..13] == "A" {
+                "B"
+            } else {
+                "A"
+            },
+        );
+        assert!(verifier.verify(&tampered).is_err());
+
+        file.rotate(TokenVersion::V4Public, &["old".to_string()])
+            .unwrap_err();
+        let retired = KeyringFile {
+            keys: file
+                .keys
+                .iter()
+                .filter(|key| key.kid != "old")
+                .cloned()
+                .collect(),
+            ..file.clone()
+        };
+        let keyring = Keyring::from_file(&retired).unwrap();
+        assert!(keyring.verify(&local_token).is_err());
+        assert!(keyring.verify(&public_token).is_ok());
+    }
+
+    #[test]
+    fn tokens_without_key_id_use_the_default_key() {
+        let secret = "RANDOM WORDS WINTER MACINTOSH PC";
+        let token = paseto::tokens::PasetoBuilder::new()
+            .set_encryption_key(secret.as_bytes())
+            .set_expiration(&(Utc::now() + Duration::minutes(5)))
+            .set_claim("account_id", serde_json::json!(1))
+            .build()
+            .unwrap();
+        let keyring = Keyring::from_secret(secret);
+        assert_eq!(keyring.verify(&token).unwrap()["account_id"], 1);
+        let token = keyring.sign(&claims(5));
+        assert_eq!(keyring.verify(&token).unwrap()["account_id"], 1);
+        assert!(Keyring::from_secret("ANOTHER SECRET OF THIRTY TWO BYT")
+            .verify(&token)
+            .is_err());
+    }
+}
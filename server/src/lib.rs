@@ -1,28 +1,67 @@
 #![warn(clippy::all)]
+// The chain of route filters built by `build_routes` has a deep type.
+#![recursion_limit = "256"]
 
 use tokio::sync::{oneshot, oneshot::Sender};
 // use tracing_subscriber::fmt::format::FmtSpan;
 use warp::{http::Method, Filter, Reply};
 
+use keyring::Keyring;
+use mail::Mailer;
+use oidc::OidcProviders;
+use store::QaStore;
+use types::{
+    account::{DeletedAccountPosts, Role},
+    scope::Scope,
+};
+
 pub use handle_errors;
 
 pub mod config;
+pub mod export;
+pub mod import;
+pub mod keyring;
+pub mod mail;
+pub mod oidc;
 mod profanity;
+pub mod purge;
 mod routes;
-mod store;
+pub mod store;
 pub mod types;
 
 pub struct OneshotHandler {
     pub sender: Sender<i32>,
 }
 
-async fn build_routes(store: store::Store) -> impl Filter<Extract = impl Reply> + Clone {
-    let store_filter = warp::any().map(move || store.clone());
+async fn build_routes<S: QaStore, M: Mailer>(
+    store: S,
+    mailer: M,
+    deleted_account_posts: DeletedAccountPosts,
+    keyring: Keyring,
+    oidc_providers: OidcProviders,
+) -> impl Filter<Extract = impl Reply> + Clone {
+    let store_filter = {
+        let store = store.clone();
+        warp::any().map(move || store.clone())
+    };
+    let mailer_filter = warp::any().map(move || mailer.clone());
+    let deleted_account_posts_filter = warp::any().map(move || deleted_account_posts);
+    let keyring_filter = {
+        let keyring = keyring.clone();
+        warp::any().map(move || keyring.clone())
+    };
+    let oidc_providers_filter = warp::any().map(move || oidc_providers.clone());
 
     let cors = warp::cors()
         .allow_any_origin()
         .allow_header("content-type")
-        .allow_methods(&[Method::PUT, Method::DELETE, Method::GET, Method::POST]);
+        .allow_methods(&[
+            Method::PUT,
+            Method::DELETE,
+            Method::GET,
+            Method::POST,
+            Method::PATCH,
+        ]);
 
     let get_answers = warp::get()
         .and(warp::path("answers"))
@@ -36,6 +75,7 @@ async fn build_routes(store: store::Store) -> impl Filter<Extract = impl Reply>
         .and(warp::path::param::<i32>())
         .and(warp::path("answers"))
         .and(warp::path::end())
+        .and(warp::query())
         .and(store_filter.clone())
         .and_then(routes::answer::get_answers_of_question)
         .with(warp::trace(|info| {
@@ -77,10 +117,28 @@ async fn build_routes(store: store::Store) -> impl Filter<Extract = impl Reply>
             )
         }));
 
+    let search = warp::get()
+        .and(warp::path("search"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::search::search)
+        .with(warp::trace(|info| {
+            tracing::info_span!(
+                  "search request",
+                  method = %info.method(),
+                  path = %info.path(),
+                  id = %uuid::Uuid::new_v4(),
+            )
+        }));
+
     let add_question = warp::post()
         .and(warp::path("questions"))
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::require_scope(
+            Scope::QuestionsWrite,
+            routes::authentication::require_verified(store.clone(), keyring.clone()),
+        ))
         .and(store_filter.clone())
         .and(warp::body::json())
         .and_then(routes::question::add_question);
@@ -89,58 +147,685 @@ async fn build_routes(store: store::Store) -> impl Filter<Extract = impl Reply>
         .and(warp::path("questions"))
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::require_scope(
+            Scope::QuestionsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
         .and(store_filter.clone())
         .and(warp::body::json())
         .and_then(routes::question::update_question);
 
+    let restore_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("restore"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::QuestionsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::question::restore_question);
+
     let delete_question = warp::delete()
         .and(warp::path("questions"))
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::require_scope(
+            Scope::QuestionsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
         .and(store_filter.clone())
         .and_then(routes::question::delete_question);
 
+    let export = warp::get()
+        .and(warp::path("admin"))
+        .and(warp::path("export"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Admin,
+            routes::authentication::require_role(Role::Admin, store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::query())
+        .and_then(routes::export::export);
+
+    let set_role = warp::put()
+        .and(warp::path("admin"))
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("role"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Admin,
+            routes::authentication::require_role(Role::Admin, store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::set_role);
+
+    let unlock_account = warp::post()
+        .and(warp::path("admin"))
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("unlock"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Admin,
+            routes::authentication::require_role(Role::Admin, store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::authentication::unlock_account);
+
+    let accept_answer = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("accept"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::QuestionsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::question::accept_answer);
+
     let add_answer = warp::post()
         .and(warp::path("answers"))
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(routes::authentication::require_scope(
+            Scope::AnswersWrite,
+            routes::authentication::require_verified(store.clone(), keyring.clone()),
+        ))
         .and(store_filter.clone())
         .and(warp::body::form())
         .and_then(routes::answer::add_answer);
 
+    let update_answer = warp::put()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::AnswersWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::answer::update_answer);
+
+    let delete_answer = warp::delete()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::AnswersWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::answer::delete_answer);
+
+    let vote_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::VotesWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::vote::vote_question);
+
+    let delete_question_vote = warp::delete()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::VotesWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::vote::delete_question_vote);
+
+    let vote_answer = warp::post()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::VotesWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::vote::vote_answer);
+
+    let delete_answer_vote = warp::delete()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::VotesWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::vote::delete_answer_vote);
+
+    let get_question_comments = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::comment::get_question_comments);
+
+    let add_question_comment = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::CommentsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::add_question_comment);
+
+    let update_question_comment = warp::put()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::CommentsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::update_question_comment);
+
+    let delete_question_comment = warp::delete()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::CommentsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::comment::delete_question_comment);
+
+    let get_answer_comments = warp::get()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::comment::get_answer_comments);
+
+    let add_answer_comment = warp::post()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::CommentsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::add_answer_comment);
+
+    let update_answer_comment = warp::put()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::CommentsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::comment::update_answer_comment);
+
+    let delete_answer_comment = warp::delete()
+        .and(warp::path("answers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("comments"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::CommentsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::comment::delete_answer_comment);
+
+    let get_revisions = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::revision::get_revisions);
+
+    let get_revision_diff = warp::get()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path("diff"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::revision::get_revision_diff);
+
+    let rollback_question = warp::post()
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("rollback"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::QuestionsWrite,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::revision::rollback_question);
+
+    let get_tags = warp::get()
+        .and(warp::path("tags"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::tag::get_tags);
+
+    let get_tag_questions = warp::get()
+        .and(warp::path("tags"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("questions"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::tag::get_tag_questions);
+
+    let add_tag = warp::post()
+        .and(warp::path("tags"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::TagsWrite,
+            routes::authentication::require_role(Role::Moderator, store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::tag::add_tag);
+
+    let update_tag = warp::put()
+        .and(warp::path("tags"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::TagsWrite,
+            routes::authentication::require_role(Role::Moderator, store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::tag::update_tag);
+
+    let add_tag_synonym = warp::post()
+        .and(warp::path("tags"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("synonyms"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::TagsWrite,
+            routes::authentication::require_role(Role::Moderator, store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::tag::add_tag_synonym);
+
+    let merge_tags = warp::post()
+        .and(warp::path("tags"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("merge"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::TagsWrite,
+            routes::authentication::require_role(Role::Moderator, store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::tag::merge_tags);
+
     let registration = warp::post()
         .and(warp::path("registration"))
         .and(warp::path::end())
         .and(store_filter.clone())
+        .and(mailer_filter.clone())
         .and(warp::body::json())
         .and_then(routes::authentication::register);
 
+    let verify_account = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("verify"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::verification::verify_account);
+
+    let resend_verification = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("verify"))
+        .and(warp::path("resend"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and_then(routes::verification::resend_verification);
+
+    let get_accounts = warp::get()
+        .and(warp::path("accounts"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::account::get_accounts);
+
+    let get_account = warp::get()
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(routes::account::get_account);
+
+    let get_me = warp::get()
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth(store.clone(), keyring.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::account::get_me);
+
+    let update_me = warp::patch()
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::account::update_me);
+
+    let delete_me = warp::delete()
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(deleted_account_posts_filter)
+        .and(warp::body::json())
+        .and_then(routes::account::delete_me);
+
     let login = warp::post()
         .and(warp::path("login"))
         .and(warp::path::end())
         .and(store_filter.clone())
+        .and(keyring_filter.clone())
+        .and(warp::addr::remote())
         .and(warp::body::json())
         .and_then(routes::authentication::login);
 
-    add_answer
+    let change_password = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("password"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::password::change_password);
+
+    let request_password_reset = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("password"))
+        .and(warp::path("reset"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::password::request_password_reset);
+
+    let reset_password = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("password"))
+        .and(warp::path("reset"))
+        .and(warp::path("confirm"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::password::reset_password);
+
+    let refresh_token = warp::post()
+        .and(warp::path("token"))
+        .and(warp::path("refresh"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(keyring_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::refresh);
+
+    let logout = warp::post()
+        .and(warp::path("logout"))
+        .and(warp::path::end())
+        .and(routes::authentication::auth(store.clone(), keyring.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::authentication::logout);
+
+    let login_totp = warp::post()
+        .and(warp::path("login"))
+        .and(warp::path("totp"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(keyring_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::totp::login_totp);
+
+    let get_public_keys = warp::get()
+        .and(warp::path("auth"))
+        .and(warp::path("keys"))
+        .and(warp::path::end())
+        .and(keyring_filter.clone())
+        .and_then(routes::authentication::get_public_keys);
+
+    let start_oidc_login = warp::get()
+        .and(warp::path("auth"))
+        .and(warp::path("oidc"))
+        .and(warp::path("start"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and(oidc_providers_filter.clone())
+        .and_then(routes::oidc::start_oidc_login);
+
+    let finish_oidc_login = warp::get()
+        .and(warp::path("auth"))
+        .and(warp::path("oidc"))
+        .and(warp::path("callback"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and(keyring_filter.clone())
+        .and(oidc_providers_filter.clone())
+        .and_then(routes::oidc::finish_oidc_login);
+
+    let enroll_totp = warp::post()
+        .and(warp::path("me"))
+        .and(warp::path("totp"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::totp::enroll_totp);
+
+    let confirm_totp = warp::post()
+        .and(warp::path("me"))
+        .and(warp::path("totp"))
+        .and(warp::path("confirm"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::totp::confirm_totp);
+
+    let disable_totp = warp::delete()
+        .and(warp::path("me"))
+        .and(warp::path("totp"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::totp::disable_totp);
+
+    let get_api_keys = warp::get()
+        .and(warp::path("me"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::api_key::get_api_keys);
+
+    let add_api_key = warp::post()
+        .and(warp::path("me"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::api_key::add_api_key);
+
+    let delete_api_key = warp::delete()
+        .and(warp::path("me"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(routes::authentication::require_scope(
+            Scope::Account,
+            routes::authentication::auth(store.clone(), keyring.clone()),
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::api_key::delete_api_key);
+
+    // The routes are chained by group: warp ranks the rejections of a chain
+    // of `or` in a time exponential in its length, which is paid on every
+    // rejected request.
+    let question_routes = accept_answer
         .or(add_question)
         .or(delete_question)
-        .or(get_answers)
-        .or(get_answers_of_question)
         .or(get_question)
         .or(get_questions)
+        .or(restore_question)
+        .or(search)
+        .or(update_question);
+    let answer_routes = add_answer
+        .or(delete_answer)
+        .or(get_answers)
+        .or(get_answers_of_question)
+        .or(update_answer);
+    let vote_routes = delete_answer_vote
+        .or(delete_question_vote)
+        .or(vote_answer)
+        .or(vote_question);
+    let revision_routes = get_revision_diff.or(get_revisions).or(rollback_question);
+    let comment_routes = add_answer_comment
+        .or(add_question_comment)
+        .or(delete_answer_comment)
+        .or(delete_question_comment)
+        .or(get_answer_comments)
+        .or(get_question_comments)
+        .or(update_answer_comment)
+        .or(update_question_comment);
+    let tag_routes = add_tag
+        .or(add_tag_synonym)
+        .or(get_tag_questions)
+        .or(get_tags)
+        .or(merge_tags)
+        .or(update_tag);
+    let account_routes = change_password
         .or(login)
+        .or(logout)
+        .or(refresh_token)
         .or(registration)
-        .or(update_question)
+        .or(request_password_reset)
+        .or(resend_verification)
+        .or(reset_password)
+        .or(verify_account);
+    let profile_routes = delete_me
+        .or(get_account)
+        .or(get_accounts)
+        .or(get_me)
+        .or(update_me);
+    let totp_routes = confirm_totp.or(disable_totp).or(enroll_totp).or(login_totp);
+    let key_routes = add_api_key
+        .or(delete_api_key)
+        .or(get_api_keys)
+        .or(get_public_keys);
+    let oidc_routes = finish_oidc_login.or(start_oidc_login);
+    let admin_routes = export.or(set_role).or(unlock_account);
+
+    question_routes
+        .or(answer_routes)
+        .or(vote_routes)
+        .or(revision_routes)
+        .or(comment_routes)
+        .or(tag_routes)
+        .or(account_routes)
+        .or(profile_routes)
+        .or(totp_routes)
+        .or(key_routes)
+        .or(oidc_routes)
+        .or(admin_routes)
         .with(cors)
         .with(warp::trace::request())
         .recover(handle_errors::return_error)
 }
 
-pub async fn setup_store(config: &config::Config) -> Result<store::Store, handle_errors::Error> {
-    let store = store::Store::new(&format!(
+pub async fn setup_store(
+    config: &config::Config,
+) -> Result<store::PostgresStore, handle_errors::Error> {
+    let store = store::PostgresStore::new(&format!(
         "postgres://{}:{}@{}:{}/{}",
         config.database_user,
         config.database_password,
@@ -149,12 +834,12 @@ pub async fn setup_store(config: &config::Config) -> Result<store::Store, handle
         config.database_name
     ))
     .await
-    .map_err(|e| handle_errors::Error::DatabaseQueryError(e))?;
+    .map_err(handle_errors::Error::DatabaseQueryError)?;
     // https://docs.rs/sqlx/latest/sqlx/macro.migrate.html
     sqlx::migrate!()
         .run(&store.clone().connection)
         .await
-        .map_err(|e| handle_errors::Error::MigrationError(e))?;
+        .map_err(handle_errors::Error::MigrationError)?;
     // Set log level for the application.
     // We pass three:
     // - One for the server implementation: indicated by the
@@ -175,16 +860,45 @@ pub async fn setup_store(config: &config::Config) -> Result<store::Store, handle
     Ok(store)
 }
 
-pub async fn run(config: config::Config, store: store::Store) {
-    let routes = build_routes(store).await;
+pub async fn run<S: QaStore, M: Mailer>(
+    config: config::Config,
+    store: S,
+    mailer: M,
+    keyring: Keyring,
+    oidc_providers: OidcProviders,
+) {
+    tokio::spawn(purge::purge_deleted_questions(
+        store.clone(),
+        chrono::Duration::days(config.deleted_questions_retention_days.into()),
+    ));
+    tokio::spawn(purge::purge_expired_tokens(store.clone()));
+    tokio::spawn(purge::purge_login_attempts(store.clone()));
+    let routes = build_routes(
+        store,
+        mailer,
+        config.deleted_account_posts,
+        keyring,
+        oidc_providers,
+    )
+    .await;
     // We use the address 0.0.0.0 (means all IP4 addresses on the local machine) because when operating within a container, we need access from the outside.
     warp::serve(routes)
         .run(([0, 0, 0, 0], config.web_server_port))
         .await;
 }
 
-pub async fn oneshot(store: store::Store) -> OneshotHandler {
-    let routes = build_routes(store).await;
+/// Serves the routes for the integration tests, with the `PASETO_KEY`
+/// secret as keyring and no identity provider.
+pub async fn oneshot<S: QaStore, M: Mailer>(store: S, mailer: M) -> OneshotHandler {
+    let keyring = Keyring::from_secret(&std::env::var("PASETO_KEY").expect("PASETO_KEY not set"));
+    let routes = build_routes(
+        store,
+        mailer,
+        DeletedAccountPosts::default(),
+        keyring,
+        OidcProviders::default(),
+    )
+    .await;
     let (tx, rx) = oneshot::channel::<i32>();
 
     let socket: std::net::SocketAddr = "127.0.0.1:3030"
@@ -200,3 +914,2208 @@ pub async fn oneshot(store: store::Store) -> OneshotHandler {
 
     OneshotHandler { sender: tx }
 }
+
+#[cfg(test)]
+mod routes_tests {
+    use super::{
+        build_routes,
+        keyring::{KeyEntry, Keyring, KeyringFile, PublicKey, TokenVersion},
+        mail::InMemoryMailer,
+        oidc::{OidcProvider, OidcProviders},
+        store::{InMemoryStore, QaStore},
+        types::{
+            account::{DeletedAccountPosts, Role},
+            oidc::{OidcProviderConfig, DEFAULT_OIDC_SCOPES},
+            tag::NewTag,
+            token::TokenPair,
+            totp::{code_at, time_step, LoginChallenge, RecoveryCodes, TotpEnrollment},
+        },
+    };
+    use mock_idp::{MockIdp, MockUser};
+    use serde_json::{json, Value};
+    use warp::{http::StatusCode, Filter, Reply};
+
+    fn keyring() -> Keyring {
+        Keyring::from_secret("RANDOM WORDS WINTER MACINTOSH PC")
+    }
+
+    /// Registers and verifies the account, and returns its access token.
+    async fn register_and_login<F>(routes: &F, mailer: &InMemoryMailer, email: &str) -> String
+    where
+        F: Filter + 'static,
+        F::Extract: Reply + Send,
+    {
+        let account = json!({ "email": email, "password": "pw" });
+        warp::test::request()
+            .method("POST")
+            .path("/registration")
+            .json(&account)
+            .reply(routes)
+            .await;
+        verify_email(routes, mailer, email).await;
+        let res = warp::test::request()
+            .method("POST")
+            .path("/login")
+            .json(&account)
+            .reply(routes)
+            .await;
+        let pair: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        pair.access_token
+    }
+
+    /// Registers and verifies the account, makes it an admin in the store
+    /// like `server set-role` does, and returns its access token.
+    async fn register_admin<F>(
+        routes: &F,
+        mailer: &InMemoryMailer,
+        store: &InMemoryStore,
+        email: &str,
+    ) -> String
+    where
+        F: Filter + 'static,
+        F::Extract: Reply + Send,
+    {
+        register_and_login(routes, mailer, email).await;
+        let account = store.get_account(email.to_string()).await.unwrap();
+        store
+            .set_account_role(account.id.unwrap(), Role::Admin)
+            .await
+            .unwrap();
+        let res = warp::test::request()
+            .method("POST")
+            .path("/login")
+            .json(&json!({ "email": email, "password": "pw" }))
+            .reply(routes)
+            .await;
+        let pair: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        pair.access_token
+    }
+
+    /// Confirms the email with the token of the last email sent to it.
+    async fn verify_email<F>(routes: &F, mailer: &InMemoryMailer, email: &str)
+    where
+        F: Filter + 'static,
+        F::Extract: Reply + Send,
+    {
+        let sent = mailer.sent().await;
+        let body = &sent
+            .iter()
+            .rev()
+            .find(|sent| sent.to == email)
+            .unwrap()
+            .body;
+        let token = body.lines().nth(2).unwrap();
+        let res = warp::test::request()
+            .method("POST")
+            .path("/account/verify")
+            .json(&json!({ "token": token }))
+            .reply(routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    async fn store_with_tags(names: &[&str]) -> InMemoryStore {
+        let store = InMemoryStore::new();
+        for name in names {
+            let tag = NewTag {
+                name: name.to_string(),
+                description: None,
+            };
+            store.add_tag(tag).await.unwrap();
+        }
+        store
+    }
+
+    #[tokio::test]
+    async fn question_lifecycle_without_database() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let account = json!({ "email": "foo@bar.com", "password": "pw" });
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/registration")
+            .json(&account)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/registration")
+            .json(&account)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.body(), "Account already exists");
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/registration")
+            .json(&json!({ "email": "foo@bar", "password": "pw" }))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.body(), "Invalid email: foo@bar");
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/login")
+            .json(&account)
+            .reply(&routes)
+            .await;
+        let token = serde_json::from_slice::<TokenPair>(res.body())
+            .unwrap()
+            .access_token;
+
+        let add_question = || {
+            warp::test::request()
+                .method("POST")
+                .path("/questions")
+                .header("Authorization", &token)
+                .json(&json!({ "title": "How?", "content": "Please help!" }))
+                .reply(&routes)
+        };
+        // Unverified accounts can't post.
+        assert_eq!(add_question().await.status(), StatusCode::FORBIDDEN);
+        verify_email(&routes, &mailer, "foo@bar.com").await;
+        assert_eq!(add_question().await.status(), StatusCode::OK);
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/answers")
+            .header("Authorization", &token)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("content=Like+this&question_id=1")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = warp::test::request()
+            .path("/questions?limit=10&offset=0")
+            .reply(&routes)
+            .await;
+        let questions: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(questions["items"][0]["title"], "How?");
+
+        let res = warp::test::request()
+            .path("/questions/1/answers")
+            .reply(&routes)
+            .await;
+        let answers: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(answers["items"][0]["content"], "Like this");
+    }
+
+    #[tokio::test]
+    async fn export_needs_an_admin() {
+        let store = InMemoryStore::new();
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store.clone(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let tokens = [
+            register_and_login(&routes, &mailer, "user@bar.com").await,
+            register_admin(&routes, &mailer, &store, "admin@bar.com").await,
+        ];
+
+        let res = warp::test::request()
+            .path("/admin/export?table=accounts")
+            .header("Authorization", &tokens[0])
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = warp::test::request()
+            .path("/admin/export?table=accounts")
+            .header("Authorization", &tokens[1])
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "application/x-ndjson");
+        let body = String::from_utf8(res.body().to_vec()).unwrap();
+        assert_eq!(body.lines().count(), 2);
+        assert!(!body.contains("password"));
+    }
+
+    #[tokio::test]
+    async fn answers_are_changed_only_by_their_owner() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "owner@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "other@bar.com").await;
+        warp::test::request()
+            .method("POST")
+            .path("/questions")
+            .header("Authorization", &other)
+            .json(&json!({ "title": "How?", "content": "Please help!" }))
+            .reply(&routes)
+            .await;
+        warp::test::request()
+            .method("POST")
+            .path("/answers")
+            .header("Authorization", &owner)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("content=Like+this&question_id=1")
+            .reply(&routes)
+            .await;
+        let update = json!({ "id": 1, "content": "Like that", "question_id": 1 });
+
+        let res = warp::test::request()
+            .method("PUT")
+            .path("/answers/1")
+            .header("Authorization", &other)
+            .json(&update)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = warp::test::request()
+            .method("PUT")
+            .path("/answers/1")
+            .header("Authorization", &owner)
+            .json(&update)
+            .reply(&routes)
+            .await;
+        let answer: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(answer["content"], "Like that");
+
+        let res = warp::test::request()
+            .method("DELETE")
+            .path("/answers/1")
+            .header("Authorization", &other)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = warp::test::request()
+            .method("DELETE")
+            .path("/answers/1")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.body(), "Answer 1 deleted");
+
+        let res = warp::test::request()
+            .path("/questions/1/answers")
+            .reply(&routes)
+            .await;
+        assert_eq!(
+            res.body(),
+            r#"{"items":[],"total":0,"limit":null,"offset":0}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn question_owner_accepts_an_answer() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "asker@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "helper@bar.com").await;
+        for title in ["First", "Second"] {
+            warp::test::request()
+                .method("POST")
+                .path("/questions")
+                .header("Authorization", &owner)
+                .json(&json!({ "title": title, "content": "Please help!" }))
+                .reply(&routes)
+                .await;
+        }
+        for body in [
+            "content=Maybe&question_id=1",
+            "content=Solved&question_id=1",
+            "content=Elsewhere&question_id=2",
+        ] {
+            warp::test::request()
+                .method("POST")
+                .path("/answers")
+                .header("Authorization", &other)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(body)
+                .reply(&routes)
+                .await;
+        }
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/questions/1/accept/2")
+            .header("Authorization", &other)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/questions/1/accept/3")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            res.body(),
+            "Invalid parameter: answer 3 is not an answer of question 1"
+        );
+        let res = warp::test::request()
+            .method("POST")
+            .path("/questions/1/accept/9")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: answer 9");
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/questions/1/accept/2")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+        let question: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(question["accepted_answer_id"], 2);
+
+        let res = warp::test::request()
+            .path("/questions/1/answers")
+            .reply(&routes)
+            .await;
+        let answers: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(answers["items"][0]["content"], "Solved");
+        assert_eq!(answers["items"][1]["content"], "Maybe");
+    }
+
+    #[tokio::test]
+    async fn votes_are_counted_once_per_account() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "asker@bar.com").await;
+        let voters = [
+            register_and_login(&routes, &mailer, "first@bar.com").await,
+            register_and_login(&routes, &mailer, "second@bar.com").await,
+        ];
+        for title in ["Unpopular", "Popular"] {
+            warp::test::request()
+                .method("POST")
+                .path("/questions")
+                .header("Authorization", &owner)
+                .json(&json!({ "title": title, "content": "Please help!" }))
+                .reply(&routes)
+                .await;
+        }
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/questions/2/vote")
+            .header("Authorization", &owner)
+            .json(&json!({ "direction": "up" }))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.body(), "Cannot vote on your own post");
+
+        for voter in &voters {
+            for _ in 0..2 {
+                warp::test::request()
+                    .method("POST")
+                    .path("/questions/2/vote")
+                    .header("Authorization", voter)
+                    .json(&json!({ "direction": "up" }))
+                    .reply(&routes)
+                    .await;
+            }
+        }
+        let res = warp::test::request()
+            .method("POST")
+            .path("/questions/1/vote")
+            .header("Authorization", &voters[0])
+            .json(&json!({ "direction": "down" }))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.body(), r#"{"score":-1}"#);
+
+        let res = warp::test::request()
+            .path("/questions?sort=score")
+            .reply(&routes)
+            .await;
+        let questions: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(questions["items"][0]["title"], "Popular");
+        assert_eq!(questions["items"][0]["score"], 2);
+        assert_eq!(questions["items"][1]["score"], -1);
+
+        let res = warp::test::request()
+            .method("DELETE")
+            .path("/questions/2/vote")
+            .header("Authorization", &voters[1])
+            .reply(&routes)
+            .await;
+        assert_eq!(res.body(), r#"{"score":1}"#);
+
+        // Missing posts have no score.
+        for (method, path, body) in [
+            ("POST", "/questions/9/vote", "Not found: question 9"),
+            ("DELETE", "/questions/9/vote", "Not found: question 9"),
+            ("POST", "/answers/9/vote", "Not found: answer 9"),
+            ("DELETE", "/answers/9/vote", "Not found: answer 9"),
+        ] {
+            let res = warp::test::request()
+                .method(method)
+                .path(path)
+                .header("Authorization", &voters[0])
+                .json(&json!({ "direction": "up" }))
+                .reply(&routes)
+                .await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{} {}", method, path);
+            assert_eq!(res.body(), body);
+        }
+
+        let res = warp::test::request()
+            .path("/questions?sort=votes")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn search_questions_and_answers() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store_with_tags(&["warp", "tokio"]).await,
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let token = register_and_login(&routes, &mailer, "searcher@bar.com").await;
+        for question in [
+            json!({ "title": "Borrowing", "content": "Why does warp clone the store?", "tags": ["warp"] }),
+            json!({ "title": "Warp filters", "content": "How to combine them?", "tags": ["warp"] }),
+        ] {
+            warp::test::request()
+                .method("POST")
+                .path("/questions")
+                .header("Authorization", &token)
+                .json(&question)
+                .reply(&routes)
+                .await;
+        }
+
+        let res = warp::test::request()
+            .path("/search?q=warp&tags=warp&limit=1&offset=0")
+            .reply(&routes)
+            .await;
+        assert_eq!(
+            res.headers()["Link"],
+            "</search?q=warp&tags=warp&limit=1&offset=0>; rel=\"first\", \
+             </search?q=warp&tags=warp&limit=1&offset=1>; rel=\"next\", \
+             </search?q=warp&tags=warp&limit=1&offset=1>; rel=\"last\""
+        );
+        let results: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(results["total"], 2);
+        assert_eq!(results["items"].as_array().unwrap().len(), 1);
+        assert_eq!(results["items"][0]["question_id"], 2);
+        assert_eq!(results["items"][0]["title"], "<b>Warp</b> filters");
+
+        let res = warp::test::request()
+            .path("/search?q=warp&tags=tokio")
+            .reply(&routes)
+            .await;
+        assert!(res.headers().get("Link").is_none());
+        assert_eq!(
+            res.body(),
+            r#"{"items":[],"total":0,"limit":null,"offset":0}"#
+        );
+
+        let res = warp::test::request().path("/search").reply(&routes).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn questions_are_filtered_and_sorted() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store_with_tags(&["rust", "warp"]).await,
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let asker = register_and_login(&routes, &mailer, "asker@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "other@bar.com").await;
+        for (token, title, tags) in [
+            (&asker, "Rust", json!(["rust"])),
+            (&asker, "Rust and Warp", json!(["rust", "warp"])),
+            (&other, "Untagged", json!(null)),
+        ] {
+            warp::test::request()
+                .method("POST")
+                .path("/questions")
+                .header("Authorization", token)
+                .json(&json!({ "title": title, "content": "Please help!", "tags": tags }))
+                .reply(&routes)
+                .await;
+        }
+        for body in ["content=Yes&question_id=2", "content=No&question_id=2"] {
+            warp::test::request()
+                .method("POST")
+                .path("/answers")
+                .header("Authorization", &other)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(body)
+                .reply(&routes)
+                .await;
+        }
+        let titles = |path: &'static str| {
+            let routes = routes.clone();
+            async move {
+                let res = warp::test::request().path(path).reply(&routes).await;
+                let questions: Value = serde_json::from_slice(res.body()).unwrap();
+                questions["items"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|question| question["title"].as_str().unwrap().to_string())
+                    .collect::<Vec<String>>()
+            }
+        };
+
+        assert_eq!(titles("/questions?tags=rust,warp").await, ["Rust and Warp"]);
+        assert_eq!(
+            titles("/questions?tags=warp,go&tag_match=any").await,
+            ["Rust and Warp"]
+        );
+        assert_eq!(titles("/questions?author=2").await, ["Untagged"]);
+        assert_eq!(
+            titles("/questions?has_answers=false&created_after=2000-01-01").await,
+            ["Rust", "Untagged"]
+        );
+        assert!(titles("/questions?created_before=2000-01-01")
+            .await
+            .is_empty());
+        assert_eq!(
+            titles("/questions?sort=answers&limit=1&offset=0").await,
+            ["Rust and Warp"]
+        );
+        assert_eq!(
+            titles("/questions?sort=created_on").await,
+            ["Untagged", "Rust and Warp", "Rust"]
+        );
+
+        let res = warp::test::request()
+            .path("/questions?tag=rust")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(String::from_utf8(res.body().to_vec())
+            .unwrap()
+            .starts_with("Invalid parameter: unknown parameter tag"));
+    }
+
+    #[tokio::test]
+    async fn cursors_are_stable_across_inserts() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let token = register_and_login(&routes, &mailer, "pager@bar.com").await;
+        let add_question = |title: &'static str| {
+            let routes = routes.clone();
+            let token = token.clone();
+            async move {
+                warp::test::request()
+                    .method("POST")
+                    .path("/questions")
+                    .header("Authorization", &token)
+                    .json(&json!({ "title": title, "content": "Please help!" }))
+                    .reply(&routes)
+                    .await;
+            }
+        };
+        let get_page = |path: String| {
+            let routes = routes.clone();
+            async move {
+                let res = warp::test::request().path(&path).reply(&routes).await;
+                serde_json::from_slice::<Value>(res.body()).unwrap()
+            }
+        };
+        let titles = |page: &Value| -> Vec<String> {
+            page["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|question| question["title"].as_str().unwrap().to_string())
+                .collect()
+        };
+        for title in ["First", "Second", "Third"] {
+            add_question(title).await;
+        }
+
+        let page = get_page("/questions?sort=created_on&after=&limit=2".to_string()).await;
+        assert_eq!(titles(&page), ["Third", "Second"]);
+        assert!(page["prev"].is_null());
+
+        // With offsets, the new question would push "Second" to the next page.
+        add_question("Fourth").await;
+        let next = page["next"].as_str().unwrap();
+        let page = get_page(format!("/questions?sort=created_on&after={}&limit=2", next)).await;
+        assert_eq!(titles(&page), ["First"]);
+        assert!(page["next"].is_null());
+
+        let prev = page["prev"].as_str().unwrap();
+        let page = get_page(format!(
+            "/questions?sort=created_on&before={}&limit=2",
+            prev
+        ))
+        .await;
+        assert_eq!(titles(&page), ["Third", "Second"]);
+        assert!(page["prev"].is_string());
+
+        let res = warp::test::request()
+            .path(&format!(
+                "/questions?sort=created_on&after={}&limit=2",
+                next
+            ))
+            .reply(&routes)
+            .await;
+        assert_eq!(
+            res.headers()["Link"],
+            format!(
+                "</questions?sort=created_on&after=&limit=2>; rel=\"first\", \
+                 </questions?sort=created_on&before={}&limit=2>; rel=\"prev\"",
+                prev
+            )
+        );
+
+        let res = warp::test::request()
+            .path(&format!("/questions?after={}&limit=2", next))
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            res.body(),
+            "Invalid parameter: cursor of a list sorted by created_on, not by id"
+        );
+        // Lists with the same kind of sort key don't share their cursors.
+        let page = get_page("/questions?sort=score&after=&limit=1".to_string()).await;
+        let next = page["next"].as_str().unwrap();
+        let res = warp::test::request()
+            .path(&format!("/questions?sort=answers&after={}&limit=1", next))
+            .reply(&routes)
+            .await;
+        assert_eq!(
+            res.body(),
+            "Invalid parameter: cursor of a list sorted by score, not by answers"
+        );
+        let res = warp::test::request()
+            .path("/questions?after=&limit=101")
+            .reply(&routes)
+            .await;
+        assert_eq!(
+            res.body(),
+            "Invalid parameter: limit is at most 100, got 101"
+        );
+
+        for body in ["content=One&question_id=1", "content=Two&question_id=1"] {
+            warp::test::request()
+                .method("POST")
+                .path("/answers")
+                .header("Authorization", &token)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(body)
+                .reply(&routes)
+                .await;
+        }
+        let page = get_page("/answers?after=&limit=1".to_string()).await;
+        assert_eq!(page["items"][0]["content"], "One");
+        let page = get_page("/questions/1/answers?after=&limit=1".to_string()).await;
+        let next = page["next"].as_str().unwrap();
+        let page = get_page(format!("/questions/1/answers?after={}&limit=1", next)).await;
+        assert_eq!(page["items"][0]["content"], "Two");
+    }
+
+    #[tokio::test]
+    async fn tags_are_checked_and_merged_by_moderators() {
+        let store = InMemoryStore::new();
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store.clone(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let user = register_and_login(&routes, &mailer, "user@bar.com").await;
+        let moderator = register_admin(&routes, &mailer, &store, "moderator@bar.com").await;
+        let post = |path: &'static str, token: &String, body: serde_json::Value| {
+            let routes = routes.clone();
+            let token = token.clone();
+            async move {
+                warp::test::request()
+                    .method("POST")
+                    .path(path)
+                    .header("Authorization", &token)
+                    .json(&body)
+                    .reply(&routes)
+                    .await
+            }
+        };
+
+        let question = json!({ "title": "Borrow?", "content": "Help!", "tags": ["rs"] });
+        let res = post("/questions", &user, question.clone()).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.body(), "Unknown tag: rs");
+
+        let rust = json!({ "name": "rust", "description": "The Rust language" });
+        let res = post("/tags", &user, rust.clone()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        for tag in [rust.clone(), json!({ "name": "rustlang" })] {
+            let res = post("/tags", &moderator, tag).await;
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+        let res = post("/tags", &moderator, rust).await;
+        assert_eq!(res.body(), "Tag already exists: rust");
+        let res = post("/tags/rust/synonyms", &moderator, json!({ "name": "rs" })).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        post("/questions", &user, question).await;
+        let question = json!({ "title": "Lifetimes?", "content": "Help!", "tags": ["rustlang"] });
+        post("/questions", &user, question).await;
+        let res = warp::test::request()
+            .path("/questions/1")
+            .reply(&routes)
+            .await;
+        let question: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(question["tags"], json!(["rust"]));
+
+        let res = warp::test::request().path("/tags").reply(&routes).await;
+        let tags: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(tags["total"], 2);
+        assert_eq!(tags["items"][0]["description"], "The Rust language");
+        assert_eq!(tags["items"][0]["question_count"], 1);
+
+        let res = post("/tags/rustlang/merge", &moderator, json!({ "into": "rs" })).await;
+        let tag: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(tag["name"], "rust");
+        assert_eq!(tag["question_count"], 2);
+        assert_eq!(tag["synonyms"], json!(["rs", "rustlang"]));
+
+        let res = warp::test::request()
+            .path("/tags/rustlang/questions?limit=1&offset=1")
+            .reply(&routes)
+            .await;
+        let questions: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(questions["total"], 2);
+        assert_eq!(questions["items"][0]["title"], "Lifetimes?");
+
+        let res = warp::test::request()
+            .path("/tags/go/questions")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.body(), "Unknown tag: go");
+    }
+
+    #[tokio::test]
+    async fn comments_are_changed_only_by_their_owner() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "commenter@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "other@bar.com").await;
+        warp::test::request()
+            .method("POST")
+            .path("/questions")
+            .header("Authorization", &other)
+            .json(&json!({ "title": "How?", "content": "Please help!" }))
+            .reply(&routes)
+            .await;
+        warp::test::request()
+            .method("POST")
+            .path("/answers")
+            .header("Authorization", &other)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("content=Like+this&question_id=1")
+            .reply(&routes)
+            .await;
+        let send = |method: &'static str, path: &'static str, token: &String, content: String| {
+            let routes = routes.clone();
+            let token = token.clone();
+            async move {
+                warp::test::request()
+                    .method(method)
+                    .path(path)
+                    .header("Authorization", &token)
+                    .json(&json!({ "content": content }))
+                    .reply(&routes)
+                    .await
+            }
+        };
+
+        let res = send(
+            "POST",
+            "/questions/1/comments",
+            &owner,
+            "Which version?".into(),
+        )
+        .await;
+        let comment: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(comment["question_id"], 1);
+        assert!(comment["answer_id"].is_null());
+        let res = send("POST", "/answers/1/comments", &owner, "Thanks!".into()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = send("POST", "/questions/1/comments", &owner, "a".repeat(601)).await;
+        assert_eq!(
+            res.body(),
+            "Invalid parameter: comments have 1 to 600 characters, got 601"
+        );
+        let res = send("POST", "/questions/9/comments", &owner, "Hello?".into()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: question 9");
+
+        let res = send("PUT", "/questions/1/comments/1", &other, "Mine now".into()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        // The comment belongs to the question, not to the answer.
+        let res = send("PUT", "/answers/1/comments/1", &owner, "Which warp?".into()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: comment 1");
+        let res = send("PUT", "/questions/1/comments/9", &owner, "Hello?".into()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: comment 9");
+        let res = send(
+            "PUT",
+            "/questions/1/comments/1",
+            &owner,
+            "Which warp?".into(),
+        )
+        .await;
+        let comment: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(comment["content"], "Which warp?");
+
+        let res = warp::test::request()
+            .path("/questions/1/comments")
+            .reply(&routes)
+            .await;
+        let comments: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(comments["total"], 1);
+        assert_eq!(comments["items"][0]["content"], "Which warp?");
+
+        let delete = |path: &'static str| {
+            warp::test::request()
+                .method("DELETE")
+                .path(path)
+                .header("Authorization", &owner)
+                .reply(&routes)
+        };
+        let res = delete("/questions/1/comments/2").await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: comment 2");
+        let res = delete("/answers/1/comments/2").await;
+        assert_eq!(res.body(), "Comment 2 deleted");
+        let res = warp::test::request()
+            .path("/answers/1/comments")
+            .reply(&routes)
+            .await;
+        assert_eq!(
+            res.body(),
+            r#"{"items":[],"total":0,"limit":null,"offset":0}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn edits_are_kept_as_revisions_and_rolled_back() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store_with_tags(&["rust", "warp"]).await,
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "editor@bar.com").await;
+        // Account 2 is an admin in the tests, and admins can roll back too.
+        register_and_login(&routes, &mailer, "admin@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "reader@bar.com").await;
+        warp::test::request()
+            .method("POST")
+            .path("/questions")
+            .header("Authorization", &owner)
+            .json(&json!({ "title": "How?", "content": "Line one\nLine two", "tags": ["rust"] }))
+            .reply(&routes)
+            .await;
+        let edit = |title: &'static str, content: &'static str, tags: Value| {
+            let routes = routes.clone();
+            let owner = owner.clone();
+            async move {
+                warp::test::request()
+                    .method("PUT")
+                    .path("/questions/1")
+                    .header("Authorization", &owner)
+                    .json(&json!({
+                        "id": 1,
+                        "title": title,
+                        "content": content,
+                        "tags": tags,
+                        "accepted_answer_id": null
+                    }))
+                    .reply(&routes)
+                    .await
+            }
+        };
+        edit("How so?", "Line one\nLine three", json!(["rust", "warp"])).await;
+        edit("How so?", "Line three", json!(["warp"])).await;
+
+        let res = warp::test::request()
+            .path("/questions/1/revisions")
+            .reply(&routes)
+            .await;
+        let revisions: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(revisions["total"], 2);
+        assert_eq!(revisions["items"][0]["number"], 1);
+        assert_eq!(revisions["items"][0]["title"], "How?");
+        assert_eq!(revisions["items"][1]["content"], "Line one\nLine three");
+
+        let res = warp::test::request()
+            .path("/questions/1/revisions/diff?from=1&to=2")
+            .reply(&routes)
+            .await;
+        let diff: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(
+            diff["content"],
+            json!([
+                { "op": "kept", "text": "Line one" },
+                { "op": "removed", "text": "Line two" },
+                { "op": "added", "text": "Line three" }
+            ])
+        );
+        assert_eq!(diff["added_tags"], json!(["warp"]));
+        // Without `to` the revision is compared with the current question.
+        let res = warp::test::request()
+            .path("/questions/1/revisions/diff?from=2")
+            .reply(&routes)
+            .await;
+        let diff: Value = serde_json::from_slice(res.body()).unwrap();
+        assert!(diff["to"].is_null());
+        assert_eq!(diff["removed_tags"], json!(["rust"]));
+        let res = warp::test::request()
+            .path("/questions/1/revisions/diff?from=5")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.body(), "Unknown revision: 5");
+
+        let rollback = |token: &String| {
+            warp::test::request()
+                .method("POST")
+                .path("/questions/1/revisions/1/rollback")
+                .header("Authorization", token)
+                .reply(&routes)
+        };
+        let res = rollback(&other).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = rollback(&owner).await;
+        let question: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(question["title"], "How?");
+        assert_eq!(question["content"], "Line one\nLine two");
+        assert_eq!(question["tags"], json!(["rust"]));
+        let res = warp::test::request()
+            .path("/questions/1/revisions")
+            .reply(&routes)
+            .await;
+        let revisions: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(revisions["total"], 3);
+        assert_eq!(revisions["items"][2]["content"], "Line three");
+    }
+
+    #[tokio::test]
+    async fn deleted_questions_are_hidden_until_restored_or_purged() {
+        let store = store_with_tags(&["rust"]).await;
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store.clone(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "owner@bar.com").await;
+        let admin = register_admin(&routes, &mailer, &store, "admin@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "other@bar.com").await;
+        for title in ["Kept", "Deleted"] {
+            warp::test::request()
+                .method("POST")
+                .path("/questions")
+                .header("Authorization", &owner)
+                .json(&json!({ "title": title, "content": "Please help!", "tags": ["rust"] }))
+                .reply(&routes)
+                .await;
+        }
+        // Questions with answers can be deleted too.
+        warp::test::request()
+            .method("POST")
+            .path("/answers")
+            .header("Authorization", &other)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("content=Like+this&question_id=2")
+            .reply(&routes)
+            .await;
+        let delete = |path: &'static str, token: &String| {
+            warp::test::request()
+                .method("DELETE")
+                .path(path)
+                .header("Authorization", token)
+                .reply(&routes)
+        };
+        let res = delete("/questions/2", &owner).await;
+        assert_eq!(res.body(), "Question 2 deleted");
+        // Deleting it again, or a missing question, deletes nothing.
+        let res = delete("/questions/2", &owner).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: question 2");
+        let res = delete("/questions/9", &admin).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: question 9");
+
+        let res = warp::test::request()
+            .path("/questions/2")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: question 2");
+        let res = warp::test::request()
+            .path("/questions")
+            .reply(&routes)
+            .await;
+        let questions: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(questions["total"], 1);
+        assert_eq!(questions["items"][0]["title"], "Kept");
+        let res = warp::test::request().path("/tags").reply(&routes).await;
+        let tags: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(tags["items"][0]["question_count"], 1);
+        let res = warp::test::request()
+            .path("/search?q=deleted")
+            .reply(&routes)
+            .await;
+        let results: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(results["total"], 0);
+
+        let restore = |token: &String| {
+            warp::test::request()
+                .method("POST")
+                .path("/questions/2/restore")
+                .header("Authorization", token)
+                .reply(&routes)
+        };
+        let res = restore(&other).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = restore(&admin).await;
+        let question: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(question["title"], "Deleted");
+        // Only deleted questions can be restored.
+        let res = restore(&owner).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: question 2");
+
+        warp::test::request()
+            .method("DELETE")
+            .path("/questions/2")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+        let now = chrono::Utc::now().naive_utc();
+        assert_eq!(
+            store
+                .purge_questions(now - chrono::Duration::days(1))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(store.purge_questions(now).await.unwrap(), 1);
+        let res = warp::test::request().path("/answers").reply(&routes).await;
+        let answers: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(answers["total"], 0);
+        let res = restore(&owner).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn deleted_questions_hide_and_lock_their_answers_and_comments() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "owner@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "other@bar.com").await;
+        warp::test::request()
+            .method("POST")
+            .path("/questions")
+            .header("Authorization", &owner)
+            .json(&json!({ "title": "How?", "content": "Please help!" }))
+            .reply(&routes)
+            .await;
+        let answer = || {
+            warp::test::request()
+                .method("POST")
+                .path("/answers")
+                .header("Authorization", &other)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body("content=Like+this&question_id=1")
+                .reply(&routes)
+        };
+        answer().await;
+        let post = |path: &'static str, body: Value| {
+            warp::test::request()
+                .method("POST")
+                .path(path)
+                .header("Authorization", &other)
+                .json(&body)
+                .reply(&routes)
+        };
+        post(
+            "/questions/1/comments",
+            json!({ "content": "Which version?" }),
+        )
+        .await;
+        post("/answers/1/comments", json!({ "content": "See also" })).await;
+        warp::test::request()
+            .method("DELETE")
+            .path("/questions/1")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+
+        for path in [
+            "/answers",
+            "/answers?after=&limit=10",
+            "/questions/1/answers",
+            "/questions/1/answers?after=&limit=10",
+            "/questions/1/comments",
+            "/answers/1/comments",
+        ] {
+            let res = warp::test::request().path(path).reply(&routes).await;
+            let page: Value = serde_json::from_slice(res.body()).unwrap();
+            assert_eq!(page["items"], json!([]), "{}", path);
+        }
+        let res = warp::test::request().path("/answers").reply(&routes).await;
+        let answers: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(answers["total"], 0);
+
+        // Nothing is added to them, as if they didn't exist.
+        let res = answer().await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.body(), "Not found: question 1");
+        for (path, body) in [
+            ("/questions/1/vote", "Not found: question 1"),
+            ("/answers/1/vote", "Not found: answer 1"),
+        ] {
+            let res = post(path, json!({ "direction": "up" })).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", path);
+            assert_eq!(res.body(), body);
+        }
+        for (path, body) in [
+            ("/questions/1/comments", "Not found: question 1"),
+            ("/answers/1/comments", "Not found: answer 1"),
+        ] {
+            let res = post(path, json!({ "content": "Hello?" })).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", path);
+            assert_eq!(res.body(), body);
+        }
+    }
+
+    #[tokio::test]
+    async fn deleted_questions_lock_the_edits_of_their_answers_and_comments() {
+        let store = InMemoryStore::new();
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store.clone(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "owner@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "other@bar.com").await;
+        let admin = register_admin(&routes, &mailer, &store, "admin@bar.com").await;
+        warp::test::request()
+            .method("POST")
+            .path("/questions")
+            .header("Authorization", &owner)
+            .json(&json!({ "title": "How?", "content": "Please help!" }))
+            .reply(&routes)
+            .await;
+        warp::test::request()
+            .method("POST")
+            .path("/answers")
+            .header("Authorization", &other)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("content=Like+this&question_id=1")
+            .reply(&routes)
+            .await;
+        for path in ["/questions/1/comments", "/answers/1/comments"] {
+            warp::test::request()
+                .method("POST")
+                .path(path)
+                .header("Authorization", &other)
+                .json(&json!({ "content": "See also" }))
+                .reply(&routes)
+                .await;
+        }
+        warp::test::request()
+            .method("DELETE")
+            .path("/questions/1")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+
+        let answer = json!({ "id": 1, "content": "Like that", "question_id": 1 });
+        let comment = json!({ "content": "See that" });
+        for token in [&other, &admin] {
+            let requests = [
+                ("PUT", "/answers/1", answer.clone(), "Not found: answer 1"),
+                ("DELETE", "/answers/1", Value::Null, "Not found: answer 1"),
+                (
+                    "PUT",
+                    "/questions/1/comments/1",
+                    comment.clone(),
+                    "Not found: comment 1",
+                ),
+                (
+                    "DELETE",
+                    "/answers/1/comments/2",
+                    Value::Null,
+                    "Not found: comment 2",
+                ),
+            ];
+            for (method, path, body, error) in requests {
+                let res = warp::test::request()
+                    .method(method)
+                    .path(path)
+                    .header("Authorization", token)
+                    .json(&body)
+                    .reply(&routes)
+                    .await;
+                assert_eq!(res.status(), StatusCode::NOT_FOUND, "{} {}", method, path);
+                assert_eq!(res.body(), error);
+            }
+        }
+
+        // They are back with the question.
+        warp::test::request()
+            .method("POST")
+            .path("/questions/1/restore")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+        let res = warp::test::request()
+            .method("PUT")
+            .path("/questions/1/comments/1")
+            .header("Authorization", &other)
+            .json(&comment)
+            .reply(&routes)
+            .await;
+        let comment: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(comment["content"], "See that");
+        let res = warp::test::request()
+            .method("DELETE")
+            .path("/answers/1")
+            .header("Authorization", &admin)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.body(), "Answer 1 deleted");
+    }
+
+    #[tokio::test]
+    async fn moderators_change_posts_of_others() {
+        let store = InMemoryStore::new();
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store.clone(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "owner@bar.com").await;
+        let admin = register_admin(&routes, &mailer, &store, "admin@bar.com").await;
+        // The role in the registration is ignored.
+        let account = json!({ "email": "mod@bar.com", "password": "pw", "role": "admin" });
+        warp::test::request()
+            .method("POST")
+            .path("/registration")
+            .json(&account)
+            .reply(&routes)
+            .await;
+        let login = || {
+            warp::test::request()
+                .method("POST")
+                .path("/login")
+                .json(&account)
+                .reply(&routes)
+        };
+        let res = login().await;
+        let user = serde_json::from_slice::<TokenPair>(res.body())
+            .unwrap()
+            .access_token;
+        warp::test::request()
+            .method("POST")
+            .path("/questions")
+            .header("Authorization", &owner)
+            .json(&json!({ "title": "How?", "content": "Please help!" }))
+            .reply(&routes)
+            .await;
+        warp::test::request()
+            .method("POST")
+            .path("/answers")
+            .header("Authorization", &owner)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("content=Buy+my+product&question_id=1")
+            .reply(&routes)
+            .await;
+        let delete_answer = |token: &String| {
+            warp::test::request()
+                .method("DELETE")
+                .path("/answers/1")
+                .header("Authorization", token)
+                .reply(&routes)
+        };
+        let res = delete_answer(&user).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let set_role = |token: &String, id: i32| {
+            warp::test::request()
+                .method("PUT")
+                .path(&format!("/admin/accounts/{}/role", id))
+                .header("Authorization", token)
+                .json(&json!({ "role": "moderator" }))
+                .reply(&routes)
+        };
+        let res = set_role(&user, 3).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = set_role(&admin, 9).await;
+        assert_eq!(res.body(), "Invalid parameter: no account 9");
+        let res = set_role(&admin, 3).await;
+        assert_eq!(res.body(), r#"{"role":"moderator"}"#);
+
+        // The role is read from the token, which has to be issued again.
+        let res = login().await;
+        let moderator = serde_json::from_slice::<TokenPair>(res.body())
+            .unwrap()
+            .access_token;
+        let res = warp::test::request()
+            .method("PUT")
+            .path("/questions/1")
+            .header("Authorization", &moderator)
+            .json(&json!({ "id": 1, "title": "How to?", "content": "Please help!", "tags": null }))
+            .reply(&routes)
+            .await;
+        let question: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(question["title"], "How to?");
+        let res = delete_answer(&moderator).await;
+        assert_eq!(res.body(), "Answer 1 deleted");
+        // Moderators don't export data.
+        let res = warp::test::request()
+            .path("/admin/export")
+            .header("Authorization", &moderator)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = warp::test::request()
+            .path("/questions/1/revisions")
+            .reply(&routes)
+            .await;
+        let revisions: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(revisions["items"][0]["account_id"], 3);
+    }
+
+    #[tokio::test]
+    async fn refresh_tokens_rotate_and_logout_revokes_the_login() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let account = json!({ "email": "refresh@bar.com", "password": "pw" });
+        warp::test::request()
+            .method("POST")
+            .path("/registration")
+            .json(&account)
+            .reply(&routes)
+            .await;
+        verify_email(&routes, &mailer, "refresh@bar.com").await;
+        let res = warp::test::request()
+            .method("POST")
+            .path("/login")
+            .json(&account)
+            .reply(&routes)
+            .await;
+        let first: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(first.expires_in, 15 * 60);
+        let refresh = |token: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/token/refresh")
+                .json(&json!({ "refresh_token": token }))
+                .reply(&routes)
+        };
+
+        let res = refresh(&first.refresh_token).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let second: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        assert_ne!(second.refresh_token, first.refresh_token);
+        let res = refresh(&second.refresh_token).await;
+        let third: TokenPair = serde_json::from_slice(res.body()).unwrap();
+
+        // Reusing a rotated token revokes the whole login.
+        let res = refresh(&first.refresh_token).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = refresh(&third.refresh_token).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = refresh("unknown").await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let log_in = || {
+            warp::test::request()
+                .method("POST")
+                .path("/login")
+                .json(&account)
+                .reply(&routes)
+        };
+        let res = log_in().await;
+        let login: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        let res = log_in().await;
+        let other: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        let add_question = |token: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/questions")
+                .header("Authorization", token)
+                .json(&json!({ "title": "How?", "content": "Please help!" }))
+                .reply(&routes)
+        };
+        assert_eq!(
+            add_question(&login.access_token).await.status(),
+            StatusCode::OK
+        );
+        let res = warp::test::request()
+            .method("POST")
+            .path("/logout")
+            .header("Authorization", &login.access_token)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            add_question(&login.access_token).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        let res = refresh(&login.refresh_token).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        // Other logins are left alone.
+        assert_eq!(
+            add_question(&other.access_token).await.status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn passwords_are_changed_and_reset_once() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let token = register_and_login(&routes, &mailer, "reset@bar.com").await;
+        let login = |password: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/login")
+                .json(&json!({ "email": "reset@bar.com", "password": password }))
+                .reply(&routes)
+        };
+        let change = |old: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/account/password")
+                .header("Authorization", &token)
+                .json(&json!({ "old_password": old, "new_password": "new" }))
+                .reply(&routes)
+        };
+        assert_eq!(change("wrong").await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(change("pw").await.status(), StatusCode::OK);
+        assert_eq!(login("pw").await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(login("new").await.status(), StatusCode::OK);
+
+        let request_reset = |email: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/account/password/reset")
+                .json(&json!({ "email": email }))
+                .reply(&routes)
+        };
+        // Unknown accounts get the same reply, but no email.
+        let res = request_reset("nobody@bar.com").await;
+        assert_eq!(res.status(), StatusCode::OK);
+        // Only the verification email was sent so far.
+        assert_eq!(mailer.sent().await.len(), 1);
+        assert_eq!(
+            request_reset("reset@bar.com").await.status(),
+            StatusCode::OK
+        );
+        let sent = mailer.sent().await;
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].to, "reset@bar.com");
+        let reset_token = sent[1].body.lines().nth(2).unwrap().to_string();
+
+        let reset = |token: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/account/password/reset/confirm")
+                .json(&json!({ "token": token, "new_password": "reset" }))
+                .reply(&routes)
+        };
+        let res = reset("unknown").await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.body(), "Invalid or expired reset token");
+        assert_eq!(reset(&reset_token).await.status(), StatusCode::OK);
+        assert_eq!(login("reset").await.status(), StatusCode::OK);
+        // The token can only be used once.
+        let res = reset(&reset_token).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn failed_logins_lock_until_an_admin_unlocks() {
+        let store = InMemoryStore::new();
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            store.clone(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let user = register_and_login(&routes, &mailer, "locked@bar.com").await;
+        let admin = register_admin(&routes, &mailer, &store, "admin@bar.com").await;
+        let login = |email: &str, password: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/login")
+                .remote_addr("127.0.0.1:4000".parse().unwrap())
+                .json(&json!({ "email": email, "password": password }))
+                .reply(&routes)
+        };
+        for _ in 0..5 {
+            let res = login("locked@bar.com", "wrong").await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+        // Even the right password is refused during the delay.
+        let res = login("locked@bar.com", "pw").await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            res.body(),
+            "Too many failed logins, try again in 15 seconds"
+        );
+
+        let unlock = |token: &String| {
+            warp::test::request()
+                .method("POST")
+                .path("/admin/accounts/1/unlock")
+                .header("Authorization", token)
+                .reply(&routes)
+        };
+        assert_eq!(unlock(&user).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(unlock(&admin).await.status(), StatusCode::OK);
+        assert_eq!(login("locked@bar.com", "pw").await.status(), StatusCode::OK);
+
+        // The address already failed 5 times, and it's allowed 20 failures
+        // whatever the accounts.
+        for _ in 0..15 {
+            let res = login("nobody@bar.com", "pw").await;
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        let res = login("admin@bar.com", "pw").await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn profiles_are_public_and_accounts_deleted_by_their_owner() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::Anonymize,
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let owner = register_and_login(&routes, &mailer, "owner@bar.com").await;
+        let other = register_and_login(&routes, &mailer, "other@bar.com").await;
+        warp::test::request()
+            .method("POST")
+            .path("/questions")
+            .header("Authorization", &owner)
+            .json(&json!({ "title": "How?", "content": "Please help!" }))
+            .reply(&routes)
+            .await;
+        for token in [&owner, &other] {
+            warp::test::request()
+                .method("POST")
+                .path("/answers")
+                .header("Authorization", token)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body("content=Like+this&question_id=1")
+                .reply(&routes)
+                .await;
+        }
+
+        let update = |body: Value| {
+            warp::test::request()
+                .method("PATCH")
+                .path("/me")
+                .header("Authorization", &owner)
+                .json(&body)
+                .reply(&routes)
+        };
+        let res = update(json!({ "display_name": "Owner", "bio": "Asks a lot" })).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = update(json!({ "display_name": "O".repeat(51) })).await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        // The bio is left out, so it's kept.
+        let res = update(json!({ "display_name": "" })).await;
+        let me: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(me["email"], "owner@bar.com");
+        assert_eq!(me["display_name"], Value::Null);
+        assert_eq!(me["bio"], "Asks a lot");
+
+        let res = warp::test::request()
+            .path("/accounts/1")
+            .reply(&routes)
+            .await;
+        let profile: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(
+            profile,
+            json!({
+                "id": 1,
+                "display_name": null,
+                "bio": "Asks a lot",
+                "role": "user",
+                "question_count": 1,
+                "answer_count": 1
+            })
+        );
+        let res = warp::test::request()
+            .path("/accounts?limit=1&offset=1")
+            .reply(&routes)
+            .await;
+        let page: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(page["total"], 2);
+        assert_eq!(page["items"][0]["id"], 2);
+
+        let delete = |password: &str| {
+            warp::test::request()
+                .method("DELETE")
+                .path("/me")
+                .header("Authorization", &owner)
+                .json(&json!({ "password": password }))
+                .reply(&routes)
+        };
+        assert_eq!(delete("wrong").await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(delete("pw").await.status(), StatusCode::OK);
+        // The token of the deletion is revoked.
+        let res = warp::test::request()
+            .path("/me")
+            .header("Authorization", &owner)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = warp::test::request()
+            .path("/accounts/1")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.body(), "Invalid parameter: no account 1");
+        // The posts are kept.
+        let res = warp::test::request()
+            .path("/questions/1/answers")
+            .reply(&routes)
+            .await;
+        let page: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(page["total"], 2);
+    }
+
+    #[tokio::test]
+    async fn two_factor_logins_need_a_code_once_confirmed() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let token = register_and_login(&routes, &mailer, "totp@bar.com").await;
+        let totp = |method: &str, path: &str, body: Value| {
+            warp::test::request()
+                .method(method)
+                .path(path)
+                .header("Authorization", &token)
+                .json(&body)
+                .reply(&routes)
+        };
+        let login = || {
+            warp::test::request()
+                .method("POST")
+                .path("/login")
+                .json(&json!({ "email": "totp@bar.com", "password": "pw" }))
+                .reply(&routes)
+        };
+        let login_totp = |challenge_token: &str, code: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/login/totp")
+                .json(&json!({ "challenge_token": challenge_token, "code": code }))
+                .reply(&routes)
+        };
+
+        let res = totp("POST", "/me/totp", json!({})).await;
+        let enrollment: TotpEnrollment = serde_json::from_slice(res.body()).unwrap();
+        assert!(enrollment
+            .otpauth_uri
+            .starts_with("otpauth://totp/Q%26A:totp%40bar%2Ecom?secret="));
+        // Logins don't ask for codes until the enrollment is confirmed.
+        let res = login().await;
+        assert!(serde_json::from_slice::<TokenPair>(res.body()).is_ok());
+        let step = time_step(chrono::Utc::now());
+        let code = |step: i64| code_at(&enrollment.secret, step).unwrap();
+        let res = totp(
+            "POST",
+            "/me/totp/confirm",
+            json!({ "code": code(step + 5) }),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = totp("POST", "/me/totp/confirm", json!({ "code": code(step) })).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let recovery: RecoveryCodes = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(recovery.recovery_codes.len(), 10);
+        let res = totp("POST", "/me/totp", json!({})).await;
+        assert_eq!(
+            res.body(),
+            "Invalid parameter: two-factor authentication is already enabled"
+        );
+
+        let res = login().await;
+        let challenge: LoginChallenge = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(challenge.expires_in, 300);
+        // The code of the confirmation was used.
+        let res = login_totp(&challenge.challenge_token, &code(step)).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = login_totp(&challenge.challenge_token, &code(step + 1)).await;
+        let pair: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        // Access tokens aren't challenge tokens, and the other way round.
+        let res = login_totp(&pair.access_token, &code(step + 1)).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = warp::test::request()
+            .path("/me")
+            .header("Authorization", &challenge.challenge_token)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let recovery_code = recovery.recovery_codes[0].to_uppercase();
+        let res = login_totp(&challenge.challenge_token, &recovery_code).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = login_totp(&challenge.challenge_token, &recovery_code).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = totp("DELETE", "/me/totp", json!({ "code": "wrong" })).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let code = &recovery.recovery_codes[1];
+        let res = totp("DELETE", "/me/totp", json!({ "code": code })).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = login().await;
+        assert!(serde_json::from_slice::<TokenPair>(res.body()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn api_keys_act_for_their_account_within_their_scopes() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let token = register_and_login(&routes, &mailer, "bot@bar.com").await;
+        let add_key = |authorization: (&'static str, String), body: Value| {
+            warp::test::request()
+                .method("POST")
+                .path("/me/api-keys")
+                .header(authorization.0, authorization.1)
+                .json(&body)
+                .reply(&routes)
+        };
+        let res = add_key(
+            ("Authorization", token.clone()),
+            json!({ "name": "Bot", "scopes": ["questions:write", "account"] }),
+        )
+        .await;
+        let created: Value = serde_json::from_slice(res.body()).unwrap();
+        let key = created["key"].as_str().unwrap().to_string();
+        assert!(key.starts_with("qa_"));
+        assert_eq!(created["last_used_at"], Value::Null);
+        let res = add_key(
+            ("Authorization", token.clone()),
+            json!({ "name": "Bot", "scopes": ["everything"] }),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let post = |path: &str, body: &str| {
+            warp::test::request()
+                .method("POST")
+                .path(path)
+                .header("X-API-Key", &key)
+                .header("Content-Type", "application/json")
+                .body(body)
+                .reply(&routes)
+        };
+        let res = post(
+            "/questions",
+            r#"{"title": "How?", "content": "Please help!"}"#,
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = post("/questions/1/comments", r#"{"content": "Me too"}"#).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        // Keys can't make keys with more scopes than their own.
+        let res = add_key(
+            ("X-API-Key", key.clone()),
+            json!({ "name": "Other", "scopes": ["answers:write"] }),
+        )
+        .await;
+        assert_eq!(
+            res.body(),
+            "Invalid parameter: the session doesn't have the scope answers:write"
+        );
+
+        let res = warp::test::request()
+            .path("/me/api-keys")
+            .header("Authorization", &token)
+            .reply(&routes)
+            .await;
+        let keys: Value = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(keys.as_array().unwrap().len(), 1);
+        assert_eq!(keys[0]["name"], "Bot");
+        assert_eq!(keys[0]["scopes"], json!(["questions:write", "account"]));
+        assert!(keys[0]["last_used_at"].is_string());
+        assert_eq!(keys[0].get("key"), None);
+
+        let res = warp::test::request()
+            .method("DELETE")
+            .path("/me/api-keys/1")
+            .header("Authorization", &token)
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = post("/questions", r#"{"title": "Again?", "content": "Please!"}"#).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn sessions_have_the_scopes_asked_for_at_login() {
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        register_and_login(&routes, &mailer, "scoped@bar.com").await;
+        let res = warp::test::request()
+            .method("POST")
+            .path("/login")
+            .json(&json!({
+                "email": "scoped@bar.com",
+                "password": "pw",
+                "scopes": ["questions:write", "admin"]
+            }))
+            .reply(&routes)
+            .await;
+        let pair: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        let post = |token: &str, path: &str, body: Value| {
+            warp::test::request()
+                .method("POST")
+                .path(path)
+                .header("Authorization", token)
+                .json(&body)
+                .reply(&routes)
+        };
+        let question = json!({ "title": "How?", "content": "Please help!" });
+        let res = post(&pair.access_token, "/questions", question.clone()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let comment = json!({ "content": "Me too" });
+        let res = post(&pair.access_token, "/questions/1/comments", comment.clone()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            res.body(),
+            "Forbidden, the session lacks the scope comments:write"
+        );
+
+        // The scopes are kept across refreshes, without the admin scope the
+        // role doesn't allow.
+        let res = warp::test::request()
+            .method("POST")
+            .path("/token/refresh")
+            .json(&json!({ "refresh_token": pair.refresh_token }))
+            .reply(&routes)
+            .await;
+        let pair: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        let res = post(&pair.access_token, "/questions/1/comments", comment).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = post(&pair.access_token, "/questions", question).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = post(
+            &pair.access_token,
+            "/me/api-keys",
+            json!({ "name": "Bot", "scopes": ["admin"] }),
+        )
+        .await;
+        assert_eq!(res.body(), "Forbidden, the session lacks the scope account");
+    }
+
+    #[tokio::test]
+    async fn rotated_keys_keep_the_sessions_of_the_previous_ones() {
+        let mailer = InMemoryMailer::new();
+        let store = InMemoryStore::new();
+        let routes = build_routes(
+            store.clone(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::default(),
+        )
+        .await;
+        let old_token = register_and_login(&routes, &mailer, "keys@bar.com").await;
+
+        let mut file = KeyringFile::from_secret("RANDOM WORDS WINTER MACINTOSH PC");
+        let kid = file.rotate(TokenVersion::V4Public, &[]).unwrap();
+        let routes = build_routes(
+            store,
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            Keyring::from_file(&file).unwrap(),
+            OidcProviders::default(),
+        )
+        .await;
+        let res = warp::test::request()
+            .method("POST")
+            .path("/login")
+            .json(&json!({ "email": "keys@bar.com", "password": "pw" }))
+            .reply(&routes)
+            .await;
+        let pair: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        assert!(pair.access_token.starts_with("v4.public."));
+        for token in [&old_token, &pair.access_token] {
+            let res = warp::test::request()
+                .path("/me")
+                .header("Authorization", token)
+                .reply(&routes)
+                .await;
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        // The public key alone verifies the new tokens.
+        let res = warp::test::request()
+            .path("/auth/keys")
+            .reply(&routes)
+            .await;
+        let keys: Vec<PublicKey> = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].kid, kid);
+        let verifier = KeyringFile {
+            signing_key: "default".to_string(),
+            keys: vec![
+                file.keys[0].clone(),
+                KeyEntry {
+                    kid,
+                    version: TokenVersion::V4Public,
+                    secret: None,
+                    public_key: Some(keys[0].public_key.clone()),
+                },
+            ],
+        };
+        let claims = Keyring::from_file(&verifier)
+            .unwrap()
+            .verify(&pair.access_token)
+            .unwrap();
+        assert_eq!(claims["account_id"], 1);
+    }
+
+    #[tokio::test]
+    async fn oidc_logins_create_or_link_accounts() {
+        let idp = MockIdp::new("127.0.0.1:3032".parse().unwrap());
+        let handler = idp.oneshot();
+        let provider = OidcProvider::discover(OidcProviderConfig {
+            name: "corp".to_string(),
+            issuer: idp.issuer(),
+            client_id: mock_idp::CLIENT_ID.to_string(),
+            client_secret: Some(mock_idp::CLIENT_SECRET.to_string()),
+            redirect_uri: "http://localhost:3030/auth/oidc/callback".to_string(),
+            scopes: DEFAULT_OIDC_SCOPES.to_string(),
+        })
+        .await
+        .unwrap();
+        let mailer = InMemoryMailer::new();
+        let routes = build_routes(
+            InMemoryStore::new(),
+            mailer.clone(),
+            DeletedAccountPosts::default(),
+            keyring(),
+            OidcProviders::new(vec![provider]),
+        )
+        .await;
+        // Follows the redirections of the browser, and returns the callback.
+        let browser = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let sign_in = |user: MockUser| {
+            idp.set_user(user);
+            let routes = &routes;
+            let browser = &browser;
+            async move {
+                let res = warp::test::request()
+                    .path("/auth/oidc/start?provider=corp")
+                    .reply(routes)
+                    .await;
+                assert_eq!(res.status(), StatusCode::FOUND);
+                let location = res.headers()["location"].to_str().unwrap().to_string();
+                assert!(location.contains("code_challenge_method=S256"));
+                let res = browser.get(location).send().await.unwrap();
+                assert_eq!(res.status(), reqwest::StatusCode::FOUND);
+                let location = res.headers()["location"].to_str().unwrap().to_string();
+                location
+                    .strip_prefix("http://localhost:3030")
+                    .unwrap()
+                    .to_string()
+            }
+        };
+        let me = |token: String| {
+            let routes = &routes;
+            async move {
+                let res = warp::test::request()
+                    .path("/me")
+                    .header("Authorization", token)
+                    .reply(routes)
+                    .await;
+                serde_json::from_slice::<Value>(res.body()).unwrap()
+            }
+        };
+
+        // The first login creates the account, and the next ones find it.
+        for _ in 0..2 {
+            let callback = sign_in(MockUser::default()).await;
+            let res = warp::test::request().path(&callback).reply(&routes).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            let pair: TokenPair = serde_json::from_slice(res.body()).unwrap();
+            let profile = me(pair.access_token).await;
+            assert_eq!(profile["id"], 1);
+            assert_eq!(profile["email"], "jane@example.com");
+            assert_eq!(profile["display_name"], "Jane Doe");
+            assert_eq!(profile["verified"], true);
+            // The state is used once.
+            let res = warp::test::request().path(&callback).reply(&routes).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        // Accounts of the same verified email are linked, unless the provider
+        // didn't verify it.
+        register_and_login(&routes, &mailer, "john@example.com").await;
+        let john = MockUser {
+            subject: "john".to_string(),
+            email: "john@example.com".to_string(),
+            email_verified: false,
+            name: None,
+        };
+        let callback = sign_in(john.clone()).await;
+        let res = warp::test::request().path(&callback).reply(&routes).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let callback = sign_in(MockUser {
+            email_verified: true,
+            ..john
+        })
+        .await;
+        let res = warp::test::request().path(&callback).reply(&routes).await;
+        let pair: TokenPair = serde_json::from_slice(res.body()).unwrap();
+        assert_eq!(me(pair.access_token).await["id"], 2);
+
+        let res = warp::test::request()
+            .path("/auth/oidc/start?provider=unknown")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let _ = handler.sender.send(1);
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use tracing::{event, Level};
+
+use crate::mail::{Email, Mailer};
+use handle_errors::Error;
+
+/// Appends the emails to a file as JSON Lines instead of delivering them, to
+/// run the service locally without a mail server.
+#[derive(Debug, Clone)]
+pub struct FileMailer {
+    path: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(path: PathBuf) -> Self {
+        event!(
+            Level::WARN,
+            "Emails are not delivered but written to {}",
+            path.display()
+        );
+        FileMailer { path }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), Error> {
+        let mut line = serde_json::to_string(&email).expect("emails serialize");
+        line.push('\n');
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(Error::IoError)?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(Error::IoError)?;
+        // Tokio finishes the write in the background until the file is
+        // flushed.
+        file.flush().await.map_err(Error::IoError)?;
+        event!(
+            Level::INFO,
+            "Wrote email to {} in {}",
+            email.to,
+            self.path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod file_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn emails_are_appended() {
+        let path = std::env::temp_dir().join(format!("mail-{}.jsonl", uuid::Uuid::new_v4()));
+        let mailer = FileMailer::new(path.clone());
+        for subject in ["First", "Second"] {
+            let email = Email {
+                to: "foo@bar.com".to_string(),
+                subject: subject.to_string(),
+                body: "Hello".to_string(),
+            };
+            mailer.send(email).await.unwrap();
+        }
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let emails: Vec<Email> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(emails.len(), 2);
+        assert_eq!(emails[1].subject, "Second");
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use crate::mail::{Email, Mailer};
+use handle_errors::Error;
+
+/// Keeps the sent emails instead of delivering them, for the tests.
+/// Clones share the same outbox.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryMailer {
+    outbox: Arc<RwLock<Vec<Email>>>,
+}
+
+impl InMemoryMailer {
+    pub fn new() -> Self {
+        InMemoryMailer::default()
+    }
+
+    /// Emails sent so far, oldest first.
+    pub async fn sent(&self) -> Vec<Email> {
+        self.outbox.read().await.clone()
+    }
+}
+
+#[async_trait]
+impl Mailer for InMemoryMailer {
+    async fn send(&self, email: Email) -> Result<(), Error> {
+        self.outbox.write().await.push(email);
+        Ok(())
+    }
+}
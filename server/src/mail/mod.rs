@@ -0,0 +1,29 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use handle_errors::Error;
+
+pub mod file;
+pub mod memory;
+pub mod smtp;
+
+pub use file::FileMailer;
+pub use memory::InMemoryMailer;
+pub use smtp::SmtpMailer;
+
+/// Plain text email sent to an account.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivery of the emails sent by the route handlers, such as the password
+/// reset tokens.
+// `Clone + Send + Sync + 'static` is required by the Warp filter that
+// hands a copy of the mailer to each request.
+#[async_trait]
+pub trait Mailer: Clone + Send + Sync + 'static {
+    async fn send(&self, email: Email) -> Result<(), Error>;
+}
@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use tracing::{event, Level};
+
+use crate::mail::{Email, Mailer};
+use handle_errors::Error;
+
+/// Delivers the emails through an SMTP relay, over STARTTLS.
+#[derive(Clone)]
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// `from` is the sender of the emails, such as `Q&A <noreply@example.com>`.
+    /// The username and password are only sent when given.
+    pub fn new(
+        host: &str,
+        port: u16,
+        from: &str,
+        credentials: Option<(String, String)>,
+    ) -> Result<Self, Error> {
+        let from = from
+            .parse::<Mailbox>()
+            .map_err(|e| Error::MailError(format!("invalid sender {}: {}", from, e)))?;
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| Error::MailError(e.to_string()))?
+            .port(port);
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), Error> {
+        let to = email
+            .to
+            .parse::<Mailbox>()
+            .map_err(|e| Error::MailError(format!("invalid recipient {}: {}", email.to, e)))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(email.subject)
+            .body(email.body)
+            .map_err(|e| Error::MailError(e.to_string()))?;
+        match self.transport.send(message).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                event!(Level::ERROR, "Cannot send email to {}: {}", email.to, e);
+                Err(Error::MailError(e.to_string()))
+            }
+        }
+    }
+}
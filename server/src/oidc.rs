@@ -0,0 +1,150 @@
+//! Client of the OpenID Connect identity providers, for the logins with the
+//! authorization code flow and PKCE.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{event, Level};
+
+use handle_errors::Error;
+
+use crate::types::oidc::{code_challenge, decode_id_token, IdTokenClaims, OidcProviderConfig};
+
+/// Parts of the discovery document used by the login.
+#[derive(Clone, Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Identity provider with its endpoints, found at startup.
+#[derive(Clone, Debug)]
+pub struct OidcProvider {
+    pub config: OidcProviderConfig,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+impl OidcProvider {
+    /// Reads the endpoints of the provider from its discovery document.
+    pub async fn discover(config: OidcProviderConfig) -> Result<OidcProvider, Error> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            config.issuer.trim_end_matches('/')
+        );
+        let res = reqwest::get(url)
+            .await
+            .map_err(Error::ExternalAPIError)?
+            .error_for_status()
+            .map_err(Error::ExternalAPIError)?;
+        let discovery: Discovery = res.json().await.map_err(Error::ReqwestAPIError)?;
+        if discovery.issuer.trim_end_matches('/') != config.issuer.trim_end_matches('/') {
+            return Err(Error::InvalidParameter(format!(
+                "{} is discovered as {}",
+                config.issuer, discovery.issuer
+            )));
+        }
+        Ok(OidcProvider {
+            config,
+            authorization_endpoint: discovery.authorization_endpoint,
+            token_endpoint: discovery.token_endpoint,
+        })
+    }
+
+    /// URL the browser is sent to for the login, coming back to the
+    /// redirect URI with the state and a code.
+    pub fn authorization_url(&self, state: &str, nonce: &str, code_verifier: &str) -> String {
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", self.config.client_id.as_str()),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("scope", self.config.scopes.as_str()),
+            ("state", state),
+            ("nonce", nonce),
+            ("code_challenge", code_challenge(code_verifier).as_str()),
+            ("code_challenge_method", "S256"),
+        ])
+        .expect("query parameters are strings");
+        let separator = if self.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        format!("{}{}{}", self.authorization_endpoint, separator, query)
+    }
+
+    /// Exchanges the code of the callback for the claims of its ID token. A
+    /// code refused by the provider is unauthorized.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<IdTokenClaims, Error> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        let res = reqwest::Client::new()
+            .post(&self.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(Error::ExternalAPIError)?;
+        if !res.status().is_success() {
+            event!(
+                Level::WARN,
+                "{} refused the code: {}",
+                self.config.issuer,
+                res.status()
+            );
+            return Err(Error::Unauthorized);
+        }
+        let token: TokenResponse = res.json().await.map_err(Error::ReqwestAPIError)?;
+        decode_id_token(&token.id_token)
+    }
+}
+
+/// Identity providers by name. There are none unless configured.
+#[derive(Clone, Debug, Default)]
+pub struct OidcProviders(Arc<BTreeMap<String, OidcProvider>>);
+
+impl OidcProviders {
+    pub fn new(providers: Vec<OidcProvider>) -> OidcProviders {
+        OidcProviders(Arc::new(
+            providers
+                .into_iter()
+                .map(|provider| (provider.config.name.clone(), provider))
+                .collect(),
+        ))
+    }
+
+    /// Providers of a JSON file listing their configurations, with their
+    /// endpoints discovered.
+    pub async fn load(path: &Path) -> Result<OidcProviders, Error> {
+        let text = std::fs::read_to_string(path).map_err(Error::IoError)?;
+        let configs: Vec<OidcProviderConfig> = serde_json::from_str(&text)
+            .map_err(|e| Error::InvalidParameter(format!("{}: {}", path.display(), e)))?;
+        let mut providers = Vec::new();
+        for config in configs {
+            providers.push(OidcProvider::discover(config).await?);
+        }
+        Ok(OidcProviders::new(providers))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OidcProvider> {
+        self.0.get(name)
+    }
+}
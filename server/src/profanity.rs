@@ -32,7 +32,8 @@ pub async fn check_profanity(content: String) -> Result<String, handle_errors::E
     // We are already checking if the ENV VARIABLE is set inside main.rs,
     // so safe to unwrap here
     const ENV_VARIABLE: &str = "BAD_WORDS_API_KEY";
-    let api_key = env::var(ENV_VARIABLE).expect(&format!("env variable {} not set", ENV_VARIABLE));
+    let api_key =
+        env::var(ENV_VARIABLE).unwrap_or_else(|_| panic!("env variable {} not set", ENV_VARIABLE));
     let api_layer_url = env::var("API_LAYER_URL").expect("APILAYER URL NOT SET");
     let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
     let client = ClientBuilder::new(reqwest::Client::new())
@@ -46,14 +47,14 @@ pub async fn check_profanity(content: String) -> Result<String, handle_errors::E
         .body(content)
         .send()
         .await
-        .map_err(|e| handle_errors::Error::MiddlewareReqwestAPIError(e))?;
+        .map_err(handle_errors::Error::MiddlewareReqwestAPIError)?;
     if !res.status().is_success() {
         if res.status().is_client_error() {
             let err = transform_error(res).await;
-            return Err(handle_errors::Error::ClientError(err).into());
+            return Err(handle_errors::Error::ClientError(err));
         } else {
             let err = transform_error(res).await;
-            return Err(handle_errors::Error::ServerError(err).into());
+            return Err(handle_errors::Error::ServerError(err));
         }
     }
     match res.json::<BadWordsResponse>().await {
@@ -0,0 +1,54 @@
+use chrono::{Duration, Utc};
+use tracing::{event, Level};
+
+use crate::store::QaStore;
+use crate::types::login::LOGIN_FAILURE_WINDOW_HOURS;
+
+// How often the deleted questions, expired tokens and old login attempts
+// are looked for.
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Removes the questions deleted for longer than `retention`, once at
+/// startup and then every hour. Failed purges are logged and retried at the
+/// next tick.
+pub async fn purge_deleted_questions<S: QaStore>(store: S, retention: Duration) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let deleted_before = Utc::now().naive_utc() - retention;
+        match store.purge_questions(deleted_before).await {
+            Ok(0) => {}
+            Ok(purged) => event!(Level::INFO, "Purged {} deleted questions", purged),
+            Err(e) => event!(Level::ERROR, "Cannot purge deleted questions: {}", e),
+        }
+    }
+}
+
+/// Removes the refresh tokens and revoked access tokens that have expired,
+/// which can't be used anymore, once at startup and then every hour.
+pub async fn purge_expired_tokens<S: QaStore>(store: S) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match store.purge_expired_tokens(Utc::now().naive_utc()).await {
+            Ok(0) => {}
+            Ok(purged) => event!(Level::INFO, "Purged {} expired tokens", purged),
+            Err(e) => event!(Level::ERROR, "Cannot purge expired tokens: {}", e),
+        }
+    }
+}
+
+/// Removes the login attempts whose last failure is out of the failure
+/// window, which don't count anymore, once at startup and then every hour.
+pub async fn purge_login_attempts<S: QaStore>(store: S) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let before = Utc::now().naive_utc() - Duration::hours(LOGIN_FAILURE_WINDOW_HOURS);
+        match store.purge_login_attempts(before).await {
+            Ok(0) => {}
+            Ok(purged) => event!(Level::INFO, "Purged {} login attempts", purged),
+            Err(e) => event!(Level::ERROR, "Cannot purge login attempts: {}", e),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use tracing::{event, Level};
+
+use crate::routes::authentication::verify_password;
+use crate::routes::pagination::page_reply;
+use crate::store::QaStore;
+use crate::types::account::{
+    check_profile, AccountDeletion, AccountId, DeletedAccountPosts, OwnProfile, Profile,
+    ProfileUpdate, Session,
+};
+use crate::types::pagination::{extract_pagination, Page, Pagination};
+use handle_errors::Error;
+
+/// Public profiles, sorted by account id.
+pub async fn get_accounts<S: QaStore>(
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init. params: {:?}", params);
+    let mut pagination = Pagination::default();
+    if !params.is_empty() {
+        pagination = extract_pagination(params.clone())?;
+    }
+    let total = store.count_accounts().await?;
+    let profiles = store
+        .get_profiles(pagination.limit, pagination.offset)
+        .await?;
+    Ok(page_reply(
+        Page::new(profiles, total, pagination),
+        "/accounts",
+        &params,
+    ))
+}
+
+pub async fn get_account<S: QaStore>(
+    id: i32,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init get account {}", id);
+    Ok(warp::reply::json(
+        &find_profile(&store, AccountId(id)).await?,
+    ))
+}
+
+pub async fn get_me<S: QaStore>(
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init get me");
+    Ok(warp::reply::json(
+        &own_profile(&store, session.account_id).await?,
+    ))
+}
+
+pub async fn update_me<S: QaStore>(
+    session: Session,
+    store: S,
+    update: ProfileUpdate,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init update me");
+    check_profile(&update)?;
+    store
+        .update_profile(session.account_id.clone(), update)
+        .await?;
+    Ok(warp::reply::json(
+        &own_profile(&store, session.account_id).await?,
+    ))
+}
+
+/// Deletes the account of the session, which has to give its password
+/// again, and signs out this login. Other access tokens of the account stop
+/// working as they expire, and can't post meanwhile.
+pub async fn delete_me<S: QaStore>(
+    session: Session,
+    store: S,
+    posts: DeletedAccountPosts,
+    deletion: AccountDeletion,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init delete me");
+    let account = store.get_account_by_id(session.account_id.clone()).await?;
+    match verify_password(&account.password, deletion.password.as_bytes()) {
+        Ok(true) => {}
+        Ok(false) => return Err(warp::reject::custom(Error::WrongPassword)),
+        Err(e) => return Err(warp::reject::custom(Error::ArgonLibraryError(e))),
+    }
+    store
+        .delete_account(session.account_id.clone(), posts)
+        .await?;
+    store
+        .revoke_token(session.jti, session.exp.naive_utc())
+        .await?;
+    event!(
+        Level::INFO,
+        account_id = session.account_id.0,
+        "Account deleted, posts: {:?}",
+        posts
+    );
+    Ok(warp::reply::json(&"Account deleted".to_string()))
+}
+
+async fn find_profile<S: QaStore>(store: &S, account_id: AccountId) -> Result<Profile, Error> {
+    match store.get_profile(account_id.clone()).await? {
+        Some(profile) => Ok(profile),
+        None => Err(Error::InvalidParameter(format!(
+            "no account {}",
+            account_id.0
+        ))),
+    }
+}
+
+async fn own_profile<S: QaStore>(store: &S, account_id: AccountId) -> Result<OwnProfile, Error> {
+    let profile = find_profile(store, account_id.clone()).await?;
+    let account = store.get_account_by_id(account_id).await?;
+    Ok(OwnProfile {
+        profile,
+        email: account.email,
+        verified: account.verified,
+    })
+}
@@ -2,15 +2,17 @@ use std::collections::HashMap;
 use warp::http::StatusCode;
 
 // use crate::profanity::check_profanity;
-use crate::store::Store;
-use crate::types::account::Session;
-use crate::types::answer::NewAnswer;
-use crate::types::pagination::{extract_pagination, Pagination};
+use crate::routes::authentication::{is_moderator, log_action};
+use crate::routes::pagination::{keyset_page_reply, page_reply};
+use crate::store::QaStore;
+use crate::types::account::{Role, Session};
+use crate::types::answer::{Answer, NewAnswer, ACCEPTED_FIRST};
+use crate::types::pagination::{extract_keyset, extract_pagination, KeysetOrder, Page, Pagination};
 use tracing::{event, Level};
 
-pub async fn add_answer(
+pub async fn add_answer<S: QaStore>(
     session: Session,
-    store: Store,
+    store: S,
     new_answer: NewAnswer,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     event!(Level::INFO, "init");
@@ -32,30 +34,112 @@ pub async fn add_answer(
 }
 
 //#[instrument]
-pub async fn get_answers(
+pub async fn get_answers<S: QaStore>(
     params: HashMap<String, String>,
-    store: Store,
+    store: S,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     event!(Level::INFO, "params: {:?}", params);
+    let mut query = params.clone();
+    if let Some(keyset) = extract_keyset(&mut query, KeysetOrder::BY_ID)? {
+        return match store.get_answers_page(keyset.clone()).await {
+            Ok(res) => Ok(keyset_page_reply(res, &keyset, "/answers", &params)),
+            Err(e) => Err(warp::reject::custom(e)),
+        };
+    }
     let mut pagination = Pagination::default();
-    if !params.is_empty() {
-        pagination = extract_pagination(params)?;
+    if !query.is_empty() {
+        pagination = extract_pagination(query)?;
         event!(Level::INFO, pagination = true, "{:?}", pagination);
     }
+    let total = store.count_answers().await?;
     match store.get_answers(pagination.limit, pagination.offset).await {
-        Ok(res) => Ok(warp::reply::json(&res)),
-        Err(e) => return Err(warp::reject::custom(e)),
+        Ok(res) => Ok(page_reply(
+            Page::new(res, total, pagination),
+            "/answers",
+            &params,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
-pub async fn get_answers_of_question(
+pub async fn get_answers_of_question<S: QaStore>(
     question_id: i32,
-    store: Store,
+    params: HashMap<String, String>,
+    store: S,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    event!(Level::INFO, "Init");
-    match store.get_answers_of_question(question_id).await {
-        Ok(res) => Ok(warp::reply::json(&res)),
-        Err(e) => return Err(warp::reject::custom(e)),
+    event!(Level::INFO, "Init. params: {:?}", params);
+    let path = format!("/questions/{}/answers", question_id);
+    let mut query = params.clone();
+    if let Some(keyset) = extract_keyset(&mut query, ACCEPTED_FIRST)? {
+        return match store
+            .get_answers_of_question_page(question_id, keyset.clone())
+            .await
+        {
+            Ok(res) => Ok(keyset_page_reply(res, &keyset, &path, &params)),
+            Err(e) => Err(warp::reject::custom(e)),
+        };
+    }
+    let mut pagination = Pagination::default();
+    if !query.is_empty() {
+        pagination = extract_pagination(query)?;
+    }
+    let total = store.count_answers_of_question(question_id).await?;
+    match store
+        .get_answers_of_question(question_id, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(res) => Ok(page_reply(
+            Page::new(res, total, pagination),
+            &path,
+            &params,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
     }
     //None => Err(warp::reject::custom(Error::QuestionNotFound)), // TODO create this error
 }
+
+pub async fn update_answer<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+    answer: Answer,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init update");
+    let is_owner = store.is_answer_owner(id, &session.account_id).await?;
+    if !is_owner && session.role < Role::Moderator {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    match store.update_answer(answer, id).await {
+        Ok(res) => {
+            if is_moderator(&session) {
+                log_action(&session, &format!("updated answer {}", id));
+            }
+            Ok(warp::reply::json(&res))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn delete_answer<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init delete");
+    let is_owner = store.is_answer_owner(id, &session.account_id).await?;
+    if !is_owner && session.role < Role::Moderator {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    match store.delete_answer(id).await {
+        Ok(_) => {
+            if is_moderator(&session) {
+                log_action(&session, &format!("deleted answer {}", id));
+            }
+            Ok(warp::reply::with_status(
+                format!("Answer {} deleted", id),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
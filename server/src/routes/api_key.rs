@@ -0,0 +1,79 @@
+use tracing::{event, Level};
+
+use crate::routes::authentication::{hash_token, new_token};
+use crate::store::QaStore;
+use crate::types::account::Session;
+use crate::types::api_key::{check_api_key, ApiKeyId, CreatedApiKey, NewApiKey, API_KEY_PREFIX};
+use handle_errors::Error;
+
+/// API keys of the session's account, without the keys themselves.
+pub async fn get_api_keys<S: QaStore>(
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init get API keys");
+    Ok(warp::reply::json(
+        &store.get_api_keys(session.account_id).await?,
+    ))
+}
+
+/// Adds an API key, which is only shown in this reply. It can't have scopes
+/// the session doesn't have, so that a key can't create a stronger one.
+pub async fn add_api_key<S: QaStore>(
+    session: Session,
+    store: S,
+    new_key: NewApiKey,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init add API key");
+    check_api_key(&new_key)?;
+    if let Some(scope) = new_key
+        .scopes
+        .iter()
+        .find(|scope| !session.scopes.contains(scope))
+    {
+        return Err(warp::reject::custom(Error::InvalidParameter(format!(
+            "the session doesn't have the scope {}",
+            scope
+        ))));
+    }
+    let mut new_key = new_key;
+    new_key.name = new_key.name.trim().to_string();
+    new_key.scopes.sort();
+    new_key.scopes.dedup();
+    let key = format!("{}{}", API_KEY_PREFIX, new_token());
+    let api_key = store
+        .add_api_key(session.account_id.clone(), new_key, hash_token(&key))
+        .await?;
+    event!(
+        Level::INFO,
+        account_id = session.account_id.0,
+        "API key {} added",
+        api_key.id.0
+    );
+    Ok(warp::reply::json(&CreatedApiKey { api_key, key }))
+}
+
+/// Revokes the API key: it stops working at once.
+pub async fn delete_api_key<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init delete API key {}", id);
+    if !store
+        .delete_api_key(session.account_id.clone(), ApiKeyId(id))
+        .await?
+    {
+        return Err(warp::reject::custom(Error::InvalidParameter(format!(
+            "no API key {}",
+            id
+        ))));
+    }
+    event!(
+        Level::INFO,
+        account_id = session.account_id.0,
+        "API key {} revoked",
+        id
+    );
+    Ok(warp::reply::json(&"API key revoked".to_string()))
+}
@@ -1,26 +1,68 @@
 use argon2::{self, Config};
 use chrono::prelude::Utc;
-use paseto;
+use chrono::{Duration, NaiveDateTime};
 use rand::Rng;
-use std::{env, future};
+use sha2::{Digest, Sha256};
+use std::future;
+use std::net::SocketAddr;
 use tracing::{event, Level};
-use warp::{http::StatusCode, Filter};
+use warp::Filter;
 
-use crate::store::Store;
-use crate::types::account::{Account, AccountId, Session};
+use crate::keyring::Keyring;
+use crate::mail::Mailer;
+use crate::routes::verification::send_verification;
+use crate::store::QaStore;
+use crate::types::account::{
+    check_profile, is_valid_email, Account, AccountId, Login, ProfileUpdate, Role, RoleUpdate,
+    Session,
+};
+use crate::types::api_key::API_KEY_HEADER;
+use crate::types::login::{LoginKey, LOGIN_FAILURE_WINDOW_HOURS};
+use crate::types::scope::{grant_scopes, Scope};
+use crate::types::token::{RefreshRequest, RefreshToken, TokenPair};
+use crate::types::totp::{LoginChallenge, TotpChallenge, TOTP_CHALLENGE_PURPOSE};
 
-pub async fn register(store: Store, account: Account) -> Result<impl warp::Reply, warp::Rejection> {
+// Access tokens are short-lived since they can only be revoked one by one,
+// on logout. Refresh tokens are exchanged for a new pair until they expire.
+const ACCESS_TOKEN_LIFETIME_MINUTES: i64 = 15;
+const REFRESH_TOKEN_LIFETIME_DAYS: i64 = 30;
+// Time to type the code of the app after the password.
+const CHALLENGE_TOKEN_LIFETIME_MINUTES: i64 = 5;
+
+/// Adds an unverified account and emails it a verification token. The
+/// account can log in but can't post until it confirms its email.
+pub async fn register<S: QaStore, M: Mailer>(
+    store: S,
+    mailer: M,
+    account: Account,
+) -> Result<impl warp::Reply, warp::Rejection> {
     event!(Level::INFO, "Init register");
+    if !is_valid_email(&account.email) {
+        return Err(warp::reject::custom(handle_errors::Error::InvalidEmail(
+            account.email,
+        )));
+    }
+    check_profile(&ProfileUpdate {
+        display_name: account.display_name.clone(),
+        bio: account.bio.clone(),
+    })?;
     let hashed_password = hash_password(account.password.as_bytes());
     let account = Account {
         id: account.id,
         email: account.email,
         password: hashed_password,
+        role: Role::User,
+        verified: false,
+        display_name: account.display_name.filter(|name| !name.is_empty()),
+        bio: account.bio.filter(|bio| !bio.is_empty()),
     };
-    match store.add_account(account).await {
-        Ok(_) => Ok(warp::reply::json(&"Account added".to_string())),
-        Err(e) => Err(warp::reject::custom(e)),
+    let email = account.email.clone();
+    if let Err(e) = store.add_account(account).await {
+        return Err(warp::reject::custom(e));
     }
+    let account = store.get_account(email).await?;
+    send_verification(&store, &mailer, account).await?;
+    Ok(warp::reply::json(&"Account added".to_string()))
 }
 
 pub fn hash_password(password: &[u8]) -> String {
@@ -29,95 +71,623 @@ pub fn hash_password(password: &[u8]) -> String {
     argon2::hash_encoded(password, &salt, &config).unwrap()
 }
 
-pub async fn login(store: Store, login: Account) -> Result<impl warp::Reply, warp::Rejection> {
+/// Signs in with the email and password. Failed logins are counted by
+/// account and by IP address, and past a few of them the logins are refused
+/// for a while, whatever the password. Accounts with two-factor
+/// authentication get a challenge token instead of a session, to exchange
+/// with a code at `POST /login/totp`. The session has the scopes asked for
+/// that the role allows.
+pub async fn login<S: QaStore>(
+    store: S,
+    keyring: Keyring,
+    remote: Option<SocketAddr>,
+    login: Login,
+) -> Result<impl warp::Reply, warp::Rejection> {
     event!(Level::INFO, "Init login");
-    match store.get_account(login.email).await {
-        Ok(account) => match verify_password(&account.password, login.password.as_bytes()) {
-            Ok(verified) => {
-                if verified {
-                    Ok(warp::reply::json(&issue_token(
-                        account.id.expect("id not found"),
-                    )))
-                } else {
-                    Err(warp::reject::custom(handle_errors::Error::WrongPassword))
-                }
+    let now = Utc::now().naive_utc();
+    let ip_key = remote.map(|addr| LoginKey::Ip(addr.ip()));
+    if let Some(key) = &ip_key {
+        check_login_lock(&store, key, now).await?;
+    }
+    let account = match store.get_account(login.email).await {
+        Ok(account) => account,
+        Err(e @ handle_errors::Error::DatabaseQueryError(sqlx::Error::RowNotFound)) => {
+            if let Some(key) = &ip_key {
+                add_login_failure(&store, key, now).await?;
+            }
+            return Err(warp::reject::custom(e));
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let account_id = account.id.clone().expect("id not found");
+    let account_key = LoginKey::Account(account_id.clone());
+    check_login_lock(&store, &account_key, now).await?;
+    match verify_password(&account.password, login.password.as_bytes()) {
+        Ok(true) => {}
+        Ok(false) => {
+            add_login_failure(&store, &account_key, now).await?;
+            if let Some(key) = &ip_key {
+                add_login_failure(&store, key, now).await?;
             }
-            Err(e) => Err(warp::reject::custom(
+            return Err(warp::reject::custom(handle_errors::Error::WrongPassword));
+        }
+        Err(e) => {
+            return Err(warp::reject::custom(
                 handle_errors::Error::ArgonLibraryError(e),
-            )),
-        },
-        Err(e) => Err(warp::reject::custom(e)),
+            ))
+        }
+    }
+    // The failures of the address are kept: it may be guessing the
+    // passwords of other accounts.
+    store.clear_login_failures(&account_key).await?;
+    Ok(login_reply(&store, &keyring, &account, login.scopes).await?)
+}
+
+/// Reply of a login whose account was identified: a session, or a challenge
+/// token when the account has two-factor authentication.
+pub async fn login_reply<S: QaStore>(
+    store: &S,
+    keyring: &Keyring,
+    account: &Account,
+    scopes: Option<Vec<Scope>>,
+) -> Result<warp::reply::Json, handle_errors::Error> {
+    let account_id = account.id.clone().expect("id not found");
+    if let Some(factor) = store.get_totp_factor(account_id.clone()).await? {
+        if factor.enabled {
+            return Ok(warp::reply::json(&LoginChallenge {
+                challenge_token: issue_challenge_token(keyring, account_id, scopes),
+                expires_in: Duration::minutes(CHALLENGE_TOKEN_LIFETIME_MINUTES).num_seconds(),
+            }));
+        }
+    }
+    Ok(warp::reply::json(
+        &start_session(store, keyring, account, scopes).await?,
+    ))
+}
+
+/// Starts a login of the account, with a new family of refresh tokens.
+/// `scopes` are the ones asked for, `None` for all the ones of the role.
+pub async fn start_session<S: QaStore>(
+    store: &S,
+    keyring: &Keyring,
+    account: &Account,
+    scopes: Option<Vec<Scope>>,
+) -> Result<TokenPair, handle_errors::Error> {
+    let account_id = account.id.clone().expect("id not found");
+    let sid = uuid::Uuid::new_v4().to_string();
+    let refresh_token = new_token();
+    store
+        .add_refresh_token(RefreshToken {
+            token_hash: hash_token(&refresh_token),
+            account_id: account_id.clone(),
+            sid: sid.clone(),
+            expires_at: refresh_expiration(),
+            used: false,
+            scopes: scopes.clone(),
+        })
+        .await?;
+    let role = account.role;
+    Ok(token_pair(
+        keyring,
+        account_id,
+        role,
+        grant_scopes(scopes.as_deref(), role),
+        sid,
+        refresh_token,
+    ))
+}
+
+pub async fn check_login_lock<S: QaStore>(
+    store: &S,
+    key: &LoginKey,
+    now: NaiveDateTime,
+) -> Result<(), handle_errors::Error> {
+    let locked_until = store
+        .get_login_attempts(key)
+        .await?
+        .and_then(|attempts| attempts.locked_until(key));
+    match locked_until {
+        Some(until) if until > now => {
+            event!(Level::WARN, "Login of {} locked until {}", key, until);
+            // Rounded up, so that retrying after the wait succeeds.
+            let seconds = (until - now).num_milliseconds().saturating_add(999) / 1000;
+            Err(handle_errors::Error::AccountLocked(seconds))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub async fn add_login_failure<S: QaStore>(
+    store: &S,
+    key: &LoginKey,
+    now: NaiveDateTime,
+) -> Result<(), handle_errors::Error> {
+    let attempts = store
+        .add_login_failure(key, now, now - Duration::hours(LOGIN_FAILURE_WINDOW_HOURS))
+        .await?;
+    if attempts.failures >= key.free_failures() {
+        event!(
+            Level::WARN,
+            "{} failed logins of {}",
+            attempts.failures,
+            key
+        );
+    }
+    Ok(())
+}
+
+/// Exchanges a refresh token for a new pair. Each refresh token can be used
+/// once: using it again means it was stolen, so every refresh token of the
+/// login is revoked.
+pub async fn refresh<S: QaStore>(
+    store: S,
+    keyring: Keyring,
+    request: RefreshRequest,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init token refresh");
+    let token_hash = hash_token(&request.refresh_token);
+    let token = match store.get_refresh_token(token_hash.clone()).await? {
+        Some(token) => token,
+        None => return Err(warp::reject::custom(handle_errors::Error::Unauthorized)),
+    };
+    if token.used {
+        event!(
+            Level::WARN,
+            account_id = token.account_id.0,
+            "Refresh token reused, revoking login {}",
+            token.sid
+        );
+        store.revoke_refresh_tokens(token.sid).await?;
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    if token.expires_at < Utc::now().naive_utc() {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
     }
+    // The role is read again so that role changes, and the scopes they
+    // allow, apply from the next refresh.
+    let account = store.get_account_by_id(token.account_id.clone()).await?;
+    let refresh_token = new_token();
+    let next = RefreshToken {
+        token_hash: hash_token(&refresh_token),
+        account_id: token.account_id.clone(),
+        sid: token.sid.clone(),
+        expires_at: refresh_expiration(),
+        used: false,
+        scopes: token.scopes.clone(),
+    };
+    // Lost to a concurrent refresh with the same token.
+    if !store.rotate_refresh_token(token_hash, next).await? {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    let role = account.role;
+    Ok(warp::reply::json(&token_pair(
+        &keyring,
+        token.account_id,
+        role,
+        grant_scopes(token.scopes.as_deref(), role),
+        token.sid,
+        refresh_token,
+    )))
+}
+
+/// Revokes the access token of the request and the refresh tokens of its
+/// login. Other logins of the account are left alone.
+pub async fn logout<S: QaStore>(
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init logout");
+    store
+        .revoke_token(session.jti, session.exp.naive_utc())
+        .await?;
+    store.revoke_refresh_tokens(session.sid).await?;
+    Ok(warp::reply::json(&"Logged out".to_string()))
 }
 
-fn verify_password(hash: &str, password: &[u8]) -> Result<bool, argon2::Error> {
+pub fn verify_password(hash: &str, password: &[u8]) -> Result<bool, argon2::Error> {
     argon2::verify_encoded(hash, password)
 }
 
-pub fn verify_token(token: String) -> Result<Session, handle_errors::Error> {
-    let key = env::var("PASETO_KEY").unwrap();
-    let token = paseto::tokens::validate_local_token(
-        &token,
-        None,
-        key.as_bytes(),
-        &paseto::tokens::TimeBackend::Chrono,
-    )
-    .map_err(|_| handle_errors::Error::CannotDecryptToken)?;
+pub fn verify_token(token: String, keyring: &Keyring) -> Result<Session, handle_errors::Error> {
+    let token = keyring.verify(&token)?;
     serde_json::from_value::<Session>(token).map_err(|_| handle_errors::Error::CannotDecryptToken)
 }
 
-fn issue_token(account_id: AccountId) -> String {
-    let key = env::var("PASETO_KEY").unwrap();
+/// Claims of a challenge token given by `login`, when it hasn't expired.
+pub fn verify_challenge_token(
+    token: String,
+    keyring: &Keyring,
+) -> Result<TotpChallenge, handle_errors::Error> {
+    let token = keyring.verify(&token)?;
+    match serde_json::from_value::<TotpChallenge>(token) {
+        Ok(challenge) if challenge.purpose == TOTP_CHALLENGE_PURPOSE => Ok(challenge),
+        _ => Err(handle_errors::Error::CannotDecryptToken),
+    }
+}
+
+/// Public keys verifying the `v4.public` access tokens, for the services
+/// that check sessions without the shared secret.
+pub async fn get_public_keys(keyring: Keyring) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init get public keys");
+    Ok(warp::reply::json(&keyring.public_keys()))
+}
+
+/// Gives the account the role. Tokens already issued keep the previous role
+/// until they expire.
+pub async fn set_role<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+    update: RoleUpdate,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init set role of account {}", id);
+    match store.set_account_role(AccountId(id), update.role).await {
+        Ok(true) => {
+            log_action(
+                &session,
+                &format!("set role of account {} to {}", id, update.role),
+            );
+            Ok(warp::reply::json(&update))
+        }
+        Ok(false) => Err(warp::reject::custom(
+            handle_errors::Error::InvalidParameter(format!("no account {}", id)),
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Forgets the failed logins of the account, so that it can log in again
+/// before its lockout ends.
+pub async fn unlock_account<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init unlock of account {}", id);
+    match store.get_account_by_id(AccountId(id)).await {
+        Ok(_) => {}
+        Err(handle_errors::Error::DatabaseQueryError(sqlx::Error::RowNotFound)) => {
+            return Err(warp::reject::custom(
+                handle_errors::Error::InvalidParameter(format!("no account {}", id)),
+            ))
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    }
+    store
+        .clear_login_failures(&LoginKey::Account(AccountId(id)))
+        .await?;
+    log_action(&session, &format!("unlocked account {}", id));
+    Ok(warp::reply::json(&"Account unlocked".to_string()))
+}
+
+/// Whether the changes of the session on posts are logged with `log_action`:
+/// all of those of the moderators, on their own posts too, so the log tells
+/// every change a moderator made without looking up the owners.
+pub fn is_moderator(session: &Session) -> bool {
+    session.role >= Role::Moderator
+}
+
+/// Records an action taken with the privileges of the session's role: on
+/// the catalogue and the accounts, and on the posts changed by a moderator.
+pub fn log_action(session: &Session, action: &str) {
+    event!(
+        Level::INFO,
+        account_id = session.account_id.0,
+        role = %session.role,
+        "Moderation: {}",
+        action
+    );
+}
+
+fn issue_token(
+    keyring: &Keyring,
+    account_id: AccountId,
+    role: Role,
+    scopes: &[Scope],
+    sid: &str,
+) -> String {
     let current_date_time = Utc::now();
-    let expiration_date_time = current_date_time + chrono::Duration::days(1);
+    let expiration_date_time = current_date_time + Duration::minutes(ACCESS_TOKEN_LIFETIME_MINUTES);
     // Instead of using the JWT format, we use Paseto, which has a stronger algorithm.
-    paseto::tokens::PasetoBuilder::new()
-        .set_encryption_key(&Vec::from(key.as_bytes()))
-        .set_expiration(&expiration_date_time)
-        .set_claim("account_id", serde_json::json!(account_id))
-        .build()
-        .expect("Failed to construct paseto token w/ builder!")
+    keyring.sign(&serde_json::json!({
+        "exp": expiration_date_time,
+        "account_id": account_id,
+        "jti": uuid::Uuid::new_v4().to_string(),
+        "sid": sid,
+        "role": role,
+        "scopes": scopes,
+    }))
+}
+
+fn issue_challenge_token(
+    keyring: &Keyring,
+    account_id: AccountId,
+    scopes: Option<Vec<Scope>>,
+) -> String {
+    let expiration_date_time = Utc::now() + Duration::minutes(CHALLENGE_TOKEN_LIFETIME_MINUTES);
+    keyring.sign(&serde_json::json!({
+        "exp": expiration_date_time,
+        "account_id": account_id,
+        "purpose": TOTP_CHALLENGE_PURPOSE,
+        "scopes": scopes,
+    }))
+}
+
+fn token_pair(
+    keyring: &Keyring,
+    account_id: AccountId,
+    role: Role,
+    scopes: Vec<Scope>,
+    sid: String,
+    refresh_token: String,
+) -> TokenPair {
+    TokenPair {
+        access_token: issue_token(keyring, account_id, role, &scopes, &sid),
+        refresh_token,
+        expires_in: Duration::minutes(ACCESS_TOKEN_LIFETIME_MINUTES).num_seconds(),
+    }
+}
+
+/// Random token for the refresh and reset tokens, safe in URLs.
+pub fn new_token() -> String {
+    let bytes = rand::thread_rng().gen::<[u8; 32]>();
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn refresh_expiration() -> chrono::NaiveDateTime {
+    Utc::now().naive_utc() + Duration::days(REFRESH_TOKEN_LIFETIME_DAYS)
+}
+
+/// Refresh and reset tokens are random, so a plain SHA-256 is enough to keep
+/// them useless to someone reading the database.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
 }
 
 // We return a type that implements the Filter trait that expects the generic type Session, or an Error that implements Warp’s Rejection trait. With `+ Clone` the returned Filter can be clone.
 // `future::ready` returns a type Ready with the Result inside it.
-pub fn auth() -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
-    warp::header::<String>("Authorization").and_then(|token: String| {
-        let token = match verify_token(token) {
-            Ok(t) => t,
-            Err(_) => {
-                return future::ready(Err(warp::reject::custom(
-                    handle_errors::Error::Unauthorized,
-                )))
+// Tokens revoked on logout are refused until they expire. Requests may give
+// an API key instead of an access token.
+pub fn auth<S: QaStore>(
+    store: S,
+    keyring: Keyring,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("Authorization")
+        .and(warp::header::optional::<String>(API_KEY_HEADER))
+        .and_then(move |token: Option<String>, api_key: Option<String>| {
+            let store = store.clone();
+            let keyring = keyring.clone();
+            async move {
+                let session = match (token, api_key) {
+                    (Some(token), _) => token_session(&store, &keyring, token).await,
+                    (None, Some(api_key)) => api_key_session(&store, &api_key).await,
+                    (None, None) => Err(handle_errors::Error::Unauthorized),
+                };
+                session.map_err(warp::reject::custom)
             }
-        };
+        })
+}
 
-        future::ready(Ok(token))
+async fn token_session<S: QaStore>(
+    store: &S,
+    keyring: &Keyring,
+    token: String,
+) -> Result<Session, handle_errors::Error> {
+    let session = match verify_token(token, keyring) {
+        Ok(t) => t,
+        Err(_) => return Err(handle_errors::Error::Unauthorized),
+    };
+    if store.is_token_revoked(&session.jti).await? {
+        return Err(handle_errors::Error::Unauthorized);
+    }
+    Ok(session)
+}
+
+/// Session of an API key, with the current role of its account and the
+/// scopes of the key that the role allows. It lasts for the request.
+async fn api_key_session<S: QaStore>(
+    store: &S,
+    key: &str,
+) -> Result<Session, handle_errors::Error> {
+    let now = Utc::now();
+    let api_key = match store.use_api_key(hash_token(key), now.naive_utc()).await? {
+        Some(api_key) => api_key,
+        None => return Err(handle_errors::Error::Unauthorized),
+    };
+    let account = store.get_account_by_id(api_key.account_id.clone()).await?;
+    let id = format!("api-key:{}", api_key.id.0);
+    let role = account.role;
+    Ok(Session {
+        exp: now + Duration::minutes(ACCESS_TOKEN_LIFETIME_MINUTES),
+        account_id: api_key.account_id,
+        jti: id.clone(),
+        sid: id,
+        role,
+        scopes: grant_scopes(Some(&api_key.scopes), role),
+    })
+}
+
+/// Narrows a filter giving a session, like `auth` or `require_role`, to the
+/// sessions having the scope. Sessions without it are forbidden rather than
+/// unauthorized: signing in again with the same scopes won't help.
+pub fn require_scope<F>(
+    scope: Scope,
+    filter: F,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (Session,), Error = warp::Rejection> + Clone,
+{
+    filter.and_then(move |session: Session| {
+        if session.scopes.contains(&scope) {
+            future::ready(Ok(session))
+        } else {
+            future::ready(Err(warp::reject::custom(handle_errors::Error::Forbidden(
+                scope.to_string(),
+            ))))
+        }
+    })
+}
+
+/// Same as `auth`, for routes that need at least the given role.
+pub fn require_role<S: QaStore>(
+    role: Role,
+    store: S,
+    keyring: Keyring,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    auth(store, keyring).and_then(move |session: Session| {
+        if session.role >= role {
+            future::ready(Ok(session))
+        } else {
+            future::ready(Err(warp::reject::custom(
+                handle_errors::Error::Unauthorized,
+            )))
+        }
+    })
+}
+
+/// Same as `auth`, for routes that need an account that confirmed its email.
+pub fn require_verified<S: QaStore>(
+    store: S,
+    keyring: Keyring,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    auth(store.clone(), keyring).and_then(move |session: Session| {
+        let store = store.clone();
+        async move {
+            match store.get_account_by_id(session.account_id.clone()).await {
+                Ok(account) if account.verified => Ok(session),
+                Ok(_) => Err(warp::reject::custom(
+                    handle_errors::Error::AccountNotVerified,
+                )),
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        }
     })
 }
 
 #[cfg(test)]
 mod authentication_tests {
-    use super::{auth, env, issue_token, AccountId};
+    use super::{
+        auth, hash_token, is_moderator, issue_token, require_role, require_scope, AccountId, Role,
+        Scope,
+    };
+    use crate::keyring::Keyring;
+    use crate::store::{memory::InMemoryStore, QaStore};
+    use crate::types::{account::Account, api_key::NewApiKey, scope::all_scopes};
+
+    fn keyring() -> Keyring {
+        Keyring::from_secret("RANDOM WORDS WINTER MACINTOSH PC")
+    }
 
     #[tokio::test]
     async fn post_questions_auth() {
-        // Set the PASETO_KEY env variable; otherwise, the issue_token function,
-        // which auth calls in the background, would fail.
-        // It's important to set the same value in all tests to not affect
-        // other tests.
-        env::set_var("PASETO_KEY", "RANDOM WORDS WINTER MACINTOSH PC");
         // Issues a new token that we can pass to your test request in the
         // Authorization header.
-        let token = issue_token(AccountId(3));
-        let filter = auth();
+        let token = issue_token(&keyring(), AccountId(3), Role::User, &all_scopes(), "login");
+        let store = InMemoryStore::new();
+        let filter = auth(store.clone(), keyring());
         // Calls create-a-test request with a header and passes it to the filter,
         // which is our auth function.
         let res = warp::test::request()
-            .header("Authorization", token)
+            .header("Authorization", &token)
             .filter(&filter);
         // Awaits the response and gets a session back, where we compare the account_id
         // from the session with the one we issued the token with.
-        assert_eq!(res.await.unwrap().account_id, AccountId(3));
+        let session = res.await.unwrap();
+        assert_eq!(session.account_id, AccountId(3));
+        store
+            .revoke_token(session.jti, session.exp.naive_utc())
+            .await
+            .unwrap();
+        let res = warp::test::request()
+            .header("Authorization", token)
+            .filter(&filter);
+        assert!(res.await.is_err());
+    }
+    #[tokio::test]
+    async fn roles_are_checked() {
+        let filter = require_role(Role::Moderator, InMemoryStore::new(), keyring());
+        for (role, allowed) in [
+            (Role::User, false),
+            (Role::Moderator, true),
+            (Role::Admin, true),
+        ] {
+            let res = warp::test::request()
+                .header(
+                    "Authorization",
+                    issue_token(&keyring(), AccountId(3), role, &all_scopes(), "login"),
+                )
+                .filter(&filter)
+                .await;
+            assert_eq!(res.is_ok(), allowed);
+        }
+    }
+
+    #[tokio::test]
+    async fn changes_of_moderators_are_logged() {
+        for (role, logged) in [
+            (Role::User, false),
+            (Role::Moderator, true),
+            (Role::Admin, true),
+        ] {
+            let token = issue_token(&keyring(), AccountId(3), role, &all_scopes(), "login");
+            let session = warp::test::request()
+                .header("Authorization", token)
+                .filter(&auth(InMemoryStore::new(), keyring()))
+                .await
+                .unwrap();
+            assert_eq!(is_moderator(&session), logged);
+        }
+    }
+
+    #[tokio::test]
+    async fn api_keys_have_their_scopes() {
+        let store = InMemoryStore::new();
+        let account = Account {
+            id: None,
+            email: "bot@bar.com".to_string(),
+            password: "pw".to_string(),
+            role: Role::User,
+            verified: true,
+            display_name: None,
+            bio: None,
+        };
+        store.add_account(account).await.unwrap();
+        let new_key = NewApiKey {
+            name: "Answer bot".to_string(),
+            scopes: vec![Scope::AnswersWrite],
+        };
+        let api_key = store
+            .add_api_key(AccountId(1), new_key, hash_token("qa_key"))
+            .await
+            .unwrap();
+        let request = |scope: Scope| {
+            let filter = require_scope(scope, auth(store.clone(), keyring()));
+            async move {
+                warp::test::request()
+                    .header("X-API-Key", "qa_key")
+                    .filter(&filter)
+                    .await
+            }
+        };
+        let session = request(Scope::AnswersWrite).await.unwrap();
+        assert_eq!(session.account_id, AccountId(1));
+        assert!(request(Scope::QuestionsWrite).await.is_err());
+        // Access tokens have every scope.
+        let res = warp::test::request()
+            .header(
+                "Authorization",
+                issue_token(&keyring(), AccountId(1), Role::User, &all_scopes(), "login"),
+            )
+            .filter(&require_scope(
+                Scope::QuestionsWrite,
+                auth(store.clone(), keyring()),
+            ))
+            .await;
+        assert!(res.is_ok());
+        store
+            .delete_api_key(AccountId(1), api_key.id)
+            .await
+            .unwrap();
+        assert!(request(Scope::AnswersWrite).await.is_err());
     }
 }
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use tracing::{event, Level};
+use warp::http::StatusCode;
+
+use crate::routes::pagination::page_reply;
+use crate::store::QaStore;
+use crate::types::account::Session;
+use crate::types::answer::AnswerId;
+use crate::types::comment::{check_comment, CommentTarget, NewComment};
+use crate::types::pagination::{extract_pagination, Page, Pagination};
+use crate::types::question::QuestionId;
+
+pub async fn get_question_comments<S: QaStore>(
+    id: i32,
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let path = format!("/questions/{}/comments", id);
+    get_comments(
+        CommentTarget::Question(QuestionId(id)),
+        &path,
+        params,
+        store,
+    )
+    .await
+}
+
+pub async fn add_question_comment<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    add_comment(
+        CommentTarget::Question(QuestionId(id)),
+        session,
+        store,
+        new_comment,
+    )
+    .await
+}
+
+pub async fn update_question_comment<S: QaStore>(
+    id: i32,
+    comment_id: i32,
+    session: Session,
+    store: S,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    update_comment(
+        CommentTarget::Question(QuestionId(id)),
+        comment_id,
+        session,
+        store,
+        new_comment,
+    )
+    .await
+}
+
+pub async fn delete_question_comment<S: QaStore>(
+    id: i32,
+    comment_id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    delete_comment(
+        CommentTarget::Question(QuestionId(id)),
+        comment_id,
+        session,
+        store,
+    )
+    .await
+}
+
+pub async fn get_answer_comments<S: QaStore>(
+    id: i32,
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let path = format!("/answers/{}/comments", id);
+    get_comments(CommentTarget::Answer(AnswerId(id)), &path, params, store).await
+}
+
+pub async fn add_answer_comment<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    add_comment(
+        CommentTarget::Answer(AnswerId(id)),
+        session,
+        store,
+        new_comment,
+    )
+    .await
+}
+
+pub async fn update_answer_comment<S: QaStore>(
+    id: i32,
+    comment_id: i32,
+    session: Session,
+    store: S,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    update_comment(
+        CommentTarget::Answer(AnswerId(id)),
+        comment_id,
+        session,
+        store,
+        new_comment,
+    )
+    .await
+}
+
+pub async fn delete_answer_comment<S: QaStore>(
+    id: i32,
+    comment_id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    delete_comment(
+        CommentTarget::Answer(AnswerId(id)),
+        comment_id,
+        session,
+        store,
+    )
+    .await
+}
+
+async fn get_comments<S: QaStore>(
+    target: CommentTarget,
+    path: &str,
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init. {:?} params: {:?}", target, params);
+    let mut pagination = Pagination::default();
+    if !params.is_empty() {
+        pagination = extract_pagination(params.clone())?;
+    }
+    let total = store.count_comments(&target).await?;
+    match store
+        .get_comments(target, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(res) => Ok(page_reply(Page::new(res, total, pagination), path, &params)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+async fn add_comment<S: QaStore>(
+    target: CommentTarget,
+    session: Session,
+    store: S,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init add comment on {:?}", target);
+    check_comment(&new_comment.content)?;
+    match store
+        .add_comment(target, new_comment, session.account_id)
+        .await
+    {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+async fn update_comment<S: QaStore>(
+    target: CommentTarget,
+    comment_id: i32,
+    session: Session,
+    store: S,
+    new_comment: NewComment,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init update comment {}", comment_id);
+    let account_id = session.account_id;
+    if !store.is_comment_owner(comment_id, &account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    check_comment(&new_comment.content)?;
+    match store
+        .update_comment(target, comment_id, new_comment, account_id)
+        .await
+    {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+async fn delete_comment<S: QaStore>(
+    target: CommentTarget,
+    comment_id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init delete comment {}", comment_id);
+    let account_id = session.account_id;
+    if !store.is_comment_owner(comment_id, &account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    match store.delete_comment(target, comment_id, account_id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Comment {} deleted", comment_id),
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
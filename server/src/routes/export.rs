@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use futures::TryStreamExt;
+use tracing::{event, Level};
+use warp::http::{header, Response};
+use warp::hyper::Body;
+
+use crate::routes::authentication::log_action;
+use crate::store::QaStore;
+use crate::types::account::Session;
+use crate::types::export::{extract_export_options, ExportFormat};
+
+pub async fn export<S: QaStore>(
+    session: Session,
+    store: S,
+    params: HashMap<String, String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init export. params: {:?}", params);
+    let options = extract_export_options(params)?;
+    log_action(&session, &format!("exported {:?}", options));
+    let (content_type, file_name) = match options.format {
+        ExportFormat::JsonLines => ("application/x-ndjson", "export.jsonl"),
+        ExportFormat::Csv => ("text/csv", "export.csv"),
+        ExportFormat::Archive => ("application/x-ndjson", "archive.jsonl"),
+    };
+    let lines = crate::export::export(&store, options)?;
+    // The body is sent as the rows are read. An error in the middle of the
+    // export aborts the response.
+    let body = Body::wrap_stream(lines.map_err(|e| std::io::Error::other(e.to_string())));
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .body(body)
+        .unwrap())
+}
@@ -1,3 +1,16 @@
+pub mod account;
 pub mod answer;
+pub mod api_key;
 pub mod authentication;
+pub mod comment;
+pub mod export;
+pub mod oidc;
+pub mod pagination;
+pub mod password;
 pub mod question;
+pub mod revision;
+pub mod search;
+pub mod tag;
+pub mod totp;
+pub mod verification;
+pub mod vote;
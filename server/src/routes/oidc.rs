@@ -0,0 +1,167 @@
+use chrono::{Duration, Utc};
+use tracing::{event, Level};
+use warp::http::Uri;
+
+use crate::keyring::Keyring;
+use crate::oidc::{OidcProvider, OidcProviders};
+use crate::routes::authentication::{hash_password, hash_token, login_reply, new_token};
+use crate::store::QaStore;
+use crate::types::account::{is_valid_email, Account, Role, MAX_DISPLAY_NAME_LENGTH};
+use crate::types::oidc::{check_id_token, IdTokenClaims, OidcCallback, OidcStart, OidcState};
+use handle_errors::Error;
+
+// Time to sign in at the provider.
+const OIDC_STATE_LIFETIME_MINUTES: i64 = 10;
+
+/// Sends the browser to the provider to sign in, with a PKCE challenge. The
+/// provider sends it back to `GET /auth/oidc/callback`.
+pub async fn start_oidc_login<S: QaStore>(
+    start: OidcStart,
+    store: S,
+    providers: OidcProviders,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(
+        Level::INFO,
+        "Init OpenID Connect login at {}",
+        start.provider
+    );
+    let provider = match providers.get(&start.provider) {
+        Some(provider) => provider,
+        None => {
+            return Err(warp::reject::custom(Error::InvalidParameter(format!(
+                "unknown identity provider {}",
+                start.provider
+            ))))
+        }
+    };
+    let state = new_token();
+    let nonce = new_token();
+    let code_verifier = new_token();
+    let url = provider.authorization_url(&state, &nonce, &code_verifier);
+    store
+        .add_oidc_state(OidcState {
+            state_hash: hash_token(&state),
+            provider: start.provider,
+            code_verifier,
+            nonce,
+            expires_at: Utc::now().naive_utc() + Duration::minutes(OIDC_STATE_LIFETIME_MINUTES),
+        })
+        .await?;
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|_| Error::InvalidParameter(format!("invalid authorization URL {}", url)))?;
+    Ok(warp::redirect::found(uri))
+}
+
+/// Exchanges the code of the provider for its ID token, and signs in the
+/// account linked to it like `POST /login`. The first login of an identity
+/// links it to the account of its email, or creates one.
+pub async fn finish_oidc_login<S: QaStore>(
+    callback: OidcCallback,
+    store: S,
+    keyring: Keyring,
+    providers: OidcProviders,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init OpenID Connect callback");
+    let now = Utc::now();
+    // Taken before anything else, so that each state is used once.
+    let state = match store
+        .take_oidc_state(hash_token(&callback.state), now.naive_utc())
+        .await?
+    {
+        Some(state) => state,
+        None => return Err(warp::reject::custom(Error::Unauthorized)),
+    };
+    let provider = match providers.get(&state.provider) {
+        Some(provider) => provider,
+        None => return Err(warp::reject::custom(Error::Unauthorized)),
+    };
+    let code = match (callback.code, callback.error) {
+        (Some(code), None) => code,
+        (_, error) => {
+            event!(
+                Level::WARN,
+                "{} refused the login: {}",
+                state.provider,
+                error.unwrap_or_default()
+            );
+            return Err(warp::reject::custom(Error::Unauthorized));
+        }
+    };
+    let claims = provider.exchange_code(&code, &state.code_verifier).await?;
+    check_id_token(
+        &claims,
+        &provider.config.issuer,
+        &provider.config.client_id,
+        &state.nonce,
+        now.timestamp(),
+    )?;
+    let account = identity_account(&store, provider, claims).await?;
+    Ok(login_reply(&store, &keyring, &account, None).await?)
+}
+
+/// Account of the identity. A new identity is linked to the account of its
+/// email when both the provider and the account verified it: otherwise
+/// whoever registered the email first, or set it at the provider, could take
+/// the account over. Without account, one is created with a random password,
+/// which a password reset can replace.
+async fn identity_account<S: QaStore>(
+    store: &S,
+    provider: &OidcProvider,
+    claims: IdTokenClaims,
+) -> Result<Account, Error> {
+    let issuer = provider.config.issuer.clone();
+    if let Some(account_id) = store
+        .get_account_identity(issuer.clone(), claims.sub.clone())
+        .await?
+    {
+        return store.get_account_by_id(account_id).await;
+    }
+    let email = match claims.email.filter(|email| is_valid_email(email)) {
+        Some(email) => email,
+        None => {
+            event!(Level::WARN, "{} gave no email", provider.config.name);
+            return Err(Error::Unauthorized);
+        }
+    };
+    let account = match store.get_account(email.clone()).await {
+        Ok(account) if account.verified && claims.email_verified => account,
+        Ok(_) => {
+            event!(
+                Level::WARN,
+                "{} can't be linked to the account of its unverified email",
+                provider.config.name
+            );
+            return Err(Error::Unauthorized);
+        }
+        Err(Error::DatabaseQueryError(sqlx::Error::RowNotFound)) => {
+            store
+                .add_account(Account {
+                    id: None,
+                    email: email.clone(),
+                    password: hash_password(new_token().as_bytes()),
+                    role: Role::User,
+                    verified: claims.email_verified,
+                    display_name: claims
+                        .name
+                        .filter(|name| !name.is_empty())
+                        .filter(|name| name.chars().count() <= MAX_DISPLAY_NAME_LENGTH),
+                    bio: None,
+                })
+                .await?;
+            store.get_account(email).await?
+        }
+        Err(e) => return Err(e),
+    };
+    let account_id = account.id.clone().expect("id not found");
+    store
+        .add_account_identity(issuer, claims.sub, account_id.clone())
+        .await?;
+    event!(
+        Level::INFO,
+        account_id = account_id.0,
+        "Identity of {} linked",
+        provider.config.name
+    );
+    Ok(account)
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use warp::http::header::{HeaderValue, LINK};
+use warp::reply::Response;
+use warp::Reply;
+
+use crate::types::pagination::{Keyset, KeysetPage, Page};
+
+// Parameters replaced in the links to the other pages.
+const PAGE_PARAMETERS: [&str; 4] = ["limit", "offset", "after", "before"];
+
+/// Replies with the page as JSON and a `Link` header (RFC 8288) to the
+/// first, previous, next and last pages of `path`.
+/// `params` are the query parameters of the request: all of them but the
+/// pagination are kept in the links.
+pub fn page_reply<T: Serialize>(
+    page: Page<T>,
+    path: &str,
+    params: &HashMap<String, String>,
+) -> Response {
+    let links = page
+        .links()
+        .into_iter()
+        .map(|(rel, pagination)| {
+            let limit = pagination.limit.unwrap_or_default().to_string();
+            let offset = pagination.offset.to_string();
+            link(path, params, &[("limit", &limit), ("offset", &offset)], rel)
+        })
+        .collect();
+    with_links(warp::reply::json(&page).into_response(), links)
+}
+
+/// Replies with the page as JSON and a `Link` header to the first,
+/// previous and next pages of `path`. Lists paginated with cursors have
+/// no link to their last page.
+pub fn keyset_page_reply<T: Serialize>(
+    page: KeysetPage<T>,
+    keyset: &Keyset,
+    path: &str,
+    params: &HashMap<String, String>,
+) -> Response {
+    let limit = keyset.limit.to_string();
+    let mut links = vec![link(
+        path,
+        params,
+        &[("after", ""), ("limit", &limit)],
+        "first",
+    )];
+    if let Some(prev) = &page.prev {
+        let before = prev.encode(&keyset.order);
+        links.push(link(
+            path,
+            params,
+            &[("before", &before), ("limit", &limit)],
+            "prev",
+        ));
+    }
+    if let Some(next) = &page.next {
+        let after = next.encode(&keyset.order);
+        links.push(link(
+            path,
+            params,
+            &[("after", &after), ("limit", &limit)],
+            "next",
+        ));
+    }
+    with_links(warp::reply::json(&page).into_response(), links)
+}
+
+fn link(
+    path: &str,
+    params: &HashMap<String, String>,
+    pagination: &[(&str, &str)],
+    rel: &str,
+) -> String {
+    let mut query: Vec<(&str, &str)> = params
+        .iter()
+        .filter(|(name, _)| !PAGE_PARAMETERS.contains(&name.as_str()))
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    query.sort();
+    query.extend_from_slice(pagination);
+    let query = serde_urlencoded::to_string(query).expect("query parameters are strings");
+    format!("<{}?{}>; rel=\"{}\"", path, query, rel)
+}
+
+fn with_links(mut response: Response, links: Vec<String>) -> Response {
+    if !links.is_empty() {
+        let links = HeaderValue::from_str(&links.join(", ")).expect("links are URL encoded");
+        response.headers_mut().insert(LINK, links);
+    }
+    response
+}
@@ -0,0 +1,91 @@
+use chrono::{Duration, Utc};
+use tracing::{event, Level};
+
+use crate::mail::{Email, Mailer};
+use crate::routes::authentication::{hash_password, hash_token, new_token, verify_password};
+use crate::store::QaStore;
+use crate::types::account::{PasswordChange, PasswordReset, PasswordResetRequest, Session};
+use crate::types::token::ResetToken;
+use handle_errors::Error;
+
+const RESET_TOKEN_LIFETIME_MINUTES: i64 = 60;
+
+/// Changes the password of the session's account, which has to give its
+/// current password. The other logins of the account are signed out.
+pub async fn change_password<S: QaStore>(
+    session: Session,
+    store: S,
+    change: PasswordChange,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init password change");
+    let account = store.get_account_by_id(session.account_id.clone()).await?;
+    match verify_password(&account.password, change.old_password.as_bytes()) {
+        Ok(true) => {}
+        Ok(false) => return Err(warp::reject::custom(Error::WrongPassword)),
+        Err(e) => return Err(warp::reject::custom(Error::ArgonLibraryError(e))),
+    }
+    let password = hash_password(change.new_password.as_bytes());
+    store
+        .set_account_password(session.account_id, password)
+        .await?;
+    Ok(warp::reply::json(&"Password changed".to_string()))
+}
+
+/// Emails a reset token to the account. The reply is the same whether the
+/// account exists or not, so that it can't be used to find accounts.
+pub async fn request_password_reset<S: QaStore, M: Mailer>(
+    store: S,
+    mailer: M,
+    request: PasswordResetRequest,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init password reset request");
+    let reply = warp::reply::json(&"Password reset email sent".to_string());
+    let account = match store.get_account(request.email.clone()).await {
+        Ok(account) => account,
+        Err(Error::DatabaseQueryError(sqlx::Error::RowNotFound)) => {
+            event!(Level::INFO, "No account to reset for {}", request.email);
+            return Ok(reply);
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let token = new_token();
+    store
+        .add_reset_token(ResetToken {
+            token_hash: hash_token(&token),
+            account_id: account.id.expect("id not found"),
+            expires_at: Utc::now().naive_utc() + Duration::minutes(RESET_TOKEN_LIFETIME_MINUTES),
+        })
+        .await?;
+    mailer
+        .send(Email {
+            to: account.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Someone asked to reset the password of your account. If it was you, send this \
+                 token with your new password to /account/password/reset/confirm within {} \
+                 minutes:\n\n{}\n\nOtherwise, you can ignore this email.",
+                RESET_TOKEN_LIFETIME_MINUTES, token
+            ),
+        })
+        .await?;
+    Ok(reply)
+}
+
+/// Sets the new password of the account the reset token was sent to, and
+/// signs out its logins.
+pub async fn reset_password<S: QaStore>(
+    store: S,
+    reset: PasswordReset,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init password reset");
+    let account_id = match store
+        .use_reset_token(hash_token(&reset.token), Utc::now().naive_utc())
+        .await?
+    {
+        Some(account_id) => account_id,
+        None => return Err(warp::reject::custom(Error::InvalidResetToken)),
+    };
+    let password = hash_password(reset.new_password.as_bytes());
+    store.set_account_password(account_id, password).await?;
+    Ok(warp::reply::json(&"Password changed".to_string()))
+}
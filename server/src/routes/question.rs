@@ -5,14 +5,18 @@ use tracing::{event, Level};
 use warp::http::StatusCode;
 
 // use crate::profanity::check_profanity;
-use crate::store::Store;
-use crate::types::account::Session;
-use crate::types::pagination::{extract_pagination, Pagination};
+use crate::routes::authentication::{is_moderator, log_action};
+use crate::routes::pagination::{keyset_page_reply, page_reply};
+use crate::routes::tag::resolve_tags;
+use crate::store::QaStore;
+use crate::types::account::{Role, Session};
+use crate::types::filter::extract_question_query;
+use crate::types::pagination::Page;
 use crate::types::question::{NewQuestion, Question};
 
-pub async fn add_question(
+pub async fn add_question<S: QaStore>(
     session: Session,
-    store: Store,
+    store: S,
     new_question: NewQuestion,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     //let title = match check_profanity(new_question.title).await {
@@ -28,7 +32,7 @@ pub async fn add_question(
     let question = NewQuestion {
         title: new_question.title,
         content: new_question.content,
-        tags: new_question.tags,
+        tags: resolve_tags(&store, new_question.tags).await?,
     };
     match store.add_question(question, account_id).await {
         Ok(_) => Ok(warp::reply::with_status("Question added", StatusCode::OK)),
@@ -36,32 +40,61 @@ pub async fn add_question(
     }
 }
 
-// TODO check what happen y ID not in db
-pub async fn delete_question(
+pub async fn delete_question<S: QaStore>(
     id: i32,
     session: Session,
-    store: Store,
+    store: S,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let account_id = session.account_id;
-    if store.is_question_owner(id, &account_id).await? {
-        match store.delete_question(id, account_id).await {
-            Ok(_) => Ok(warp::reply::with_status(
+    let is_owner = store.is_question_owner(id, &session.account_id).await?;
+    if !is_owner && session.role < Role::Moderator {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    match store.delete_question(id).await {
+        Ok(_) => {
+            if is_moderator(&session) {
+                log_action(&session, &format!("deleted question {}", id));
+            }
+            Ok(warp::reply::with_status(
                 format!("Question {} deleted", id),
                 StatusCode::OK,
-            )),
-            Err(e) => Err(warp::reject::custom(e)),
+            ))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Undoes the deletion of a question, for its owner or a moderator.
+pub async fn restore_question<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init restore");
+    // Deleted questions still have their owner.
+    let is_owner = store.is_question_owner(id, &session.account_id).await?;
+    if !is_owner && session.role < Role::Moderator {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    match store.restore_question(id).await {
+        Ok(res) => {
+            if is_moderator(&session) {
+                log_action(&session, &format!("restored question {}", id));
+            }
+            Ok(warp::reply::json(&res))
         }
-    } else {
-        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+        Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
 // TODO check what happen y ID not in db
-pub async fn get_question(id: i32, store: Store) -> Result<impl warp::Reply, warp::Rejection> {
+pub async fn get_question<S: QaStore>(
+    id: i32,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
     event!(Level::INFO, "Init");
     match store.get_question(id).await {
         Ok(res) => Ok(warp::reply::json(&res)),
-        Err(e) => return Err(warp::reject::custom(e)),
+        Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
@@ -71,28 +104,44 @@ pub async fn get_question(id: i32, store: Store) -> Result<impl warp::Reply, war
 // assigned to this span.
 // instrument: genereates more logs with more data.
 //#[instrument]
-pub async fn get_questions(
+pub async fn get_questions<S: QaStore>(
     params: HashMap<String, String>,
-    store: Store,
+    store: S,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     event!(Level::INFO, "Init. params: {:?}", params);
-    let mut pagination = Pagination::default();
-    if !params.is_empty() {
-        pagination = extract_pagination(params)?;
+    let query = extract_question_query(params.clone())?;
+    if let Some(keyset) = query.keyset {
+        return match store
+            .get_questions_page(query.filter, query.order, keyset.clone())
+            .await
+        {
+            Ok(res) => Ok(keyset_page_reply(res, &keyset, "/questions", &params)),
+            Err(e) => Err(warp::reject::custom(e)),
+        };
     }
+    let total = store.count_questions(&query.filter).await?;
     match store
-        .get_questions(pagination.limit, pagination.offset)
+        .get_questions(
+            query.filter,
+            query.order,
+            query.pagination.limit,
+            query.pagination.offset,
+        )
         .await
     {
-        Ok(res) => Ok(warp::reply::json(&res)),
-        Err(e) => return Err(warp::reject::custom(e)),
+        Ok(res) => Ok(page_reply(
+            Page::new(res, total, query.pagination),
+            "/questions",
+            &params,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
-pub async fn update_question(
+pub async fn update_question<S: QaStore>(
     id: i32,
     session: Session,
-    store: Store,
+    store: S,
     question: Question,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     //let title = check_profanity(question.title);
@@ -112,19 +161,45 @@ pub async fn update_question(
     //    tags: question.tags,
     //};
     event!(Level::INFO, "Init update");
-    let account_id = session.account_id;
-    if store.is_question_owner(id, &account_id).await? {
-        let question = Question {
-            id: question.id,
-            title: question.title,
-            content: question.content,
-            tags: question.tags,
-        };
-        match store.update_question(question, id, account_id).await {
-            Ok(res) => Ok(warp::reply::json(&res)),
-            Err(e) => Err(warp::reject::custom(e)),
+    let is_owner = store.is_question_owner(id, &session.account_id).await?;
+    if !is_owner && session.role < Role::Moderator {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    let question = Question {
+        id: question.id,
+        title: question.title,
+        content: question.content,
+        tags: resolve_tags(&store, question.tags).await?,
+        accepted_answer_id: question.accepted_answer_id,
+        score: question.score,
+    };
+    match store
+        .update_question(question, id, session.account_id.clone())
+        .await
+    {
+        Ok(res) => {
+            if is_moderator(&session) {
+                log_action(&session, &format!("updated question {}", id));
+            }
+            Ok(warp::reply::json(&res))
         }
-    } else {
-        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn accept_answer<S: QaStore>(
+    id: i32,
+    answer_id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init accept answer");
+    let account_id = session.account_id;
+    if !store.is_question_owner(id, &account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+    match store.accept_answer(id, answer_id, account_id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e)),
     }
 }
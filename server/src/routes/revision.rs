@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use tracing::{event, Level};
+
+use crate::routes::authentication::{is_moderator, log_action};
+use crate::routes::pagination::page_reply;
+use crate::store::QaStore;
+use crate::types::account::{Role, Session};
+use crate::types::pagination::{extract_pagination, Page, Pagination};
+use crate::types::question::Question;
+use crate::types::revision::{extract_diff_range, Revision, RevisionDiff};
+use handle_errors::Error;
+
+pub async fn get_revisions<S: QaStore>(
+    id: i32,
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init. question: {} params: {:?}", id, params);
+    let path = format!("/questions/{}/revisions", id);
+    let mut pagination = Pagination::default();
+    if !params.is_empty() {
+        pagination = extract_pagination(params.clone())?;
+    }
+    // Unknown questions are reported instead of having no revisions.
+    store.get_question(id).await?;
+    let total = store.count_revisions(id).await?;
+    match store
+        .get_revisions(id, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(res) => Ok(page_reply(
+            Page::new(res, total, pagination),
+            &path,
+            &params,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Changes between the revision `from` and the revision `to`, or the
+/// current question when `to` is left out.
+pub async fn get_revision_diff<S: QaStore>(
+    id: i32,
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init. question: {} params: {:?}", id, params);
+    let (from, to) = extract_diff_range(&params)?;
+    let from = find_revision(&store, id, from).await?;
+    let diff = match to {
+        Some(number) => {
+            let to = find_revision(&store, id, number).await?;
+            RevisionDiff::new(&from, Some(number), &to.title, &to.content, &to.tags)
+        }
+        None => {
+            let question = store.get_question(id).await?;
+            RevisionDiff::new(
+                &from,
+                None,
+                &question.title,
+                &question.content,
+                &question.tags,
+            )
+        }
+    };
+    Ok(warp::reply::json(&diff))
+}
+
+/// Puts back the title, content and tags of the revision. The rollback is
+/// an edit of its own, so the replaced version becomes a new revision.
+pub async fn rollback_question<S: QaStore>(
+    id: i32,
+    number: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(
+        Level::INFO,
+        "Init rollback of question {} to {}",
+        id,
+        number
+    );
+    let is_owner = store.is_question_owner(id, &session.account_id).await?;
+    if !is_owner && session.role < Role::Moderator {
+        return Err(warp::reject::custom(Error::Unauthorized));
+    }
+    let revision = find_revision(&store, id, number).await?;
+    let current = store.get_question(id).await?;
+    // Tags merged since the revision are resolved by the store, deleted
+    // ones are left out.
+    let question = Question {
+        title: revision.title,
+        content: revision.content,
+        tags: revision.tags,
+        ..current
+    };
+    match store
+        .update_question(question, id, session.account_id.clone())
+        .await
+    {
+        Ok(res) => {
+            if is_moderator(&session) {
+                log_action(
+                    &session,
+                    &format!("rolled back question {} to revision {}", id, number),
+                );
+            }
+            Ok(warp::reply::json(&res))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+async fn find_revision<S: QaStore>(
+    store: &S,
+    question_id: i32,
+    number: i32,
+) -> Result<Revision, Error> {
+    match store.get_revision(question_id, number).await? {
+        Some(revision) => Ok(revision),
+        None => Err(Error::UnknownRevision(number)),
+    }
+}
@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+
+use tracing::{event, Level};
+
+use crate::routes::pagination::page_reply;
+use crate::store::QaStore;
+use crate::types::pagination::Page;
+use crate::types::search::extract_search;
+
+pub async fn search<S: QaStore>(
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init search. params: {:?}", params);
+    let search = extract_search(params.clone())?;
+    let pagination = search.pagination;
+    let total = store.count_search_results(&search).await?;
+    match store.search(search).await {
+        Ok(res) => Ok(page_reply(
+            Page::new(res, total, pagination),
+            "/search",
+            &params,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
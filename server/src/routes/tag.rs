@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use percent_encoding::percent_decode_str;
+use tracing::{event, Level};
+
+use crate::routes::authentication::log_action;
+use crate::routes::pagination::page_reply;
+use crate::store::QaStore;
+use crate::types::account::Session;
+use crate::types::filter::QuestionFilter;
+use crate::types::pagination::{extract_pagination, Page, Pagination};
+use crate::types::question::QuestionOrder;
+use crate::types::tag::{check_tag_name, NewSynonym, NewTag, Tag, TagMerge, TagUpdate};
+use handle_errors::Error;
+
+pub async fn get_tags<S: QaStore>(
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init. params: {:?}", params);
+    let mut pagination = Pagination::default();
+    if !params.is_empty() {
+        pagination = extract_pagination(params.clone())?;
+    }
+    let total = store.count_tags().await?;
+    match store.get_tags(pagination.limit, pagination.offset).await {
+        Ok(res) => Ok(page_reply(
+            Page::new(res, total, pagination),
+            "/tags",
+            &params,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Questions having the tag, or the tag a synonym stands for.
+pub async fn get_tag_questions<S: QaStore>(
+    name: String,
+    params: HashMap<String, String>,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init. tag: {} params: {:?}", name, params);
+    let path = format!("/tags/{}/questions", name);
+    let tag = find_tag(&store, decode_name(&name)?).await?;
+    let mut pagination = Pagination::default();
+    if !params.is_empty() {
+        pagination = extract_pagination(params.clone())?;
+    }
+    let filter = QuestionFilter {
+        tags: Some(vec![tag.name]),
+        ..QuestionFilter::default()
+    };
+    let total = store.count_questions(&filter).await?;
+    match store
+        .get_questions(
+            filter,
+            QuestionOrder::default(),
+            pagination.limit,
+            pagination.offset,
+        )
+        .await
+    {
+        Ok(res) => Ok(page_reply(
+            Page::new(res, total, pagination),
+            &path,
+            &params,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+// The routes changing the catalogue are behind `require_role(Role::Moderator)`.
+pub async fn add_tag<S: QaStore>(
+    session: Session,
+    store: S,
+    new_tag: NewTag,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init add tag {}", new_tag.name);
+    check_tag_name(&new_tag.name)?;
+    if store.get_tag(new_tag.name.clone()).await?.is_some() {
+        return Err(warp::reject::custom(Error::TagExists(new_tag.name)));
+    }
+    match store.add_tag(new_tag).await {
+        Ok(res) => {
+            log_action(&session, &format!("added tag {}", res.name));
+            Ok(warp::reply::json(&res))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn update_tag<S: QaStore>(
+    name: String,
+    session: Session,
+    store: S,
+    update: TagUpdate,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init update tag {}", name);
+    let tag = find_tag(&store, decode_name(&name)?).await?;
+    match store.update_tag(tag.name, update).await {
+        Ok(res) => {
+            log_action(&session, &format!("updated tag {}", res.name));
+            Ok(warp::reply::json(&res))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn add_tag_synonym<S: QaStore>(
+    name: String,
+    session: Session,
+    store: S,
+    synonym: NewSynonym,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init add synonym {} of {}", synonym.name, name);
+    check_tag_name(&synonym.name)?;
+    let tag = find_tag(&store, decode_name(&name)?).await?;
+    // An existing tag is turned into a synonym by merging it.
+    if store.get_tag(synonym.name.clone()).await?.is_some() {
+        return Err(warp::reject::custom(Error::TagExists(synonym.name)));
+    }
+    match store.add_tag_synonym(tag.name, synonym.name.clone()).await {
+        Ok(res) => {
+            log_action(
+                &session,
+                &format!("added synonym {} of tag {}", synonym.name, res.name),
+            );
+            Ok(warp::reply::json(&res))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn merge_tags<S: QaStore>(
+    name: String,
+    session: Session,
+    store: S,
+    merge: TagMerge,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init merge tag {} into {}", name, merge.into);
+    let tag = find_tag(&store, decode_name(&name)?).await?;
+    let into = find_tag(&store, merge.into).await?;
+    if tag.name == into.name {
+        return Err(warp::reject::custom(Error::InvalidParameter(format!(
+            "can't merge {} into itself",
+            tag.name
+        ))));
+    }
+    match store.merge_tags(tag.name.clone(), into.name).await {
+        Ok(res) => {
+            log_action(
+                &session,
+                &format!("merged tag {} into {}", tag.name, res.name),
+            );
+            Ok(warp::reply::json(&res))
+        }
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Checks the tags of a new or updated question against the catalogue and
+/// replaces the synonyms by their tag.
+pub async fn resolve_tags<S: QaStore>(
+    store: &S,
+    tags: Option<Vec<String>>,
+) -> Result<Option<Vec<String>>, Error> {
+    let tags = match tags {
+        Some(tags) => tags,
+        None => return Ok(None),
+    };
+    let mut resolved = Vec::with_capacity(tags.len());
+    for name in tags {
+        resolved.push(find_tag(store, name).await?.name);
+    }
+    Ok(Some(resolved))
+}
+
+async fn find_tag<S: QaStore>(store: &S, name: String) -> Result<Tag, Error> {
+    match store.get_tag(name.clone()).await? {
+        Some(tag) => Ok(tag),
+        None => Err(Error::UnknownTag(name)),
+    }
+}
+
+// Path parameters are taken as sent, percent-encoded.
+fn decode_name(name: &str) -> Result<String, Error> {
+    match percent_decode_str(name).decode_utf8() {
+        Ok(name) => Ok(name.into_owned()),
+        Err(_) => Err(Error::UnknownTag(name.to_string())),
+    }
+}
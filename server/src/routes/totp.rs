@@ -0,0 +1,183 @@
+use chrono::Utc;
+use tracing::{event, Level};
+
+use crate::keyring::Keyring;
+use crate::routes::authentication::{
+    add_login_failure, check_login_lock, hash_token, start_session, verify_challenge_token,
+};
+use crate::store::QaStore;
+use crate::types::account::Session;
+use crate::types::login::LoginKey;
+use crate::types::totp::{
+    is_totp_code, new_recovery_code, new_secret, normalize_recovery_code, otpauth_uri, verify_code,
+    RecoveryCodes, TotpCode, TotpEnrollment, TotpFactor, TotpLogin, RECOVERY_CODE_COUNT,
+};
+use handle_errors::Error;
+
+/// Starts the enrollment with a new secret, replacing the one of an
+/// enrollment not confirmed yet. Logins ask for codes once it's confirmed.
+pub async fn enroll_totp<S: QaStore>(
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init two-factor enrollment");
+    if let Some(factor) = store.get_totp_factor(session.account_id.clone()).await? {
+        if factor.enabled {
+            return Err(warp::reject::custom(already_enabled()));
+        }
+    }
+    let account = store.get_account_by_id(session.account_id.clone()).await?;
+    let secret = new_secret();
+    store
+        .set_totp_factor(TotpFactor {
+            account_id: session.account_id,
+            secret: secret.clone(),
+            enabled: false,
+            last_used_step: None,
+            recovery_code_hashes: Vec::new(),
+        })
+        .await?;
+    Ok(warp::reply::json(&TotpEnrollment {
+        otpauth_uri: otpauth_uri(&account.email, &secret),
+        secret,
+    }))
+}
+
+/// Enables the factor with a first code of the app, which shows that it has
+/// the secret, and gives the recovery codes.
+pub async fn confirm_totp<S: QaStore>(
+    session: Session,
+    store: S,
+    confirmation: TotpCode,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init two-factor confirmation");
+    let factor = match store.get_totp_factor(session.account_id.clone()).await? {
+        Some(factor) if factor.enabled => return Err(warp::reject::custom(already_enabled())),
+        Some(factor) => factor,
+        None => {
+            return Err(warp::reject::custom(Error::InvalidParameter(
+                "no two-factor enrollment started".to_string(),
+            )))
+        }
+    };
+    let step = match verify_code(&factor.secret, &confirmation.code, Utc::now()) {
+        Some(step) => step,
+        None => return Err(warp::reject::custom(Error::WrongTotpCode)),
+    };
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| new_recovery_code())
+        .collect();
+    store
+        .set_totp_factor(TotpFactor {
+            enabled: true,
+            last_used_step: Some(step),
+            recovery_code_hashes: recovery_codes
+                .iter()
+                .map(|code| hash_token(&normalize_recovery_code(code)))
+                .collect(),
+            ..factor
+        })
+        .await?;
+    event!(
+        Level::INFO,
+        account_id = session.account_id.0,
+        "Two-factor authentication enabled"
+    );
+    Ok(warp::reply::json(&RecoveryCodes { recovery_codes }))
+}
+
+/// Disables the factor. An enabled one needs a code, so that a stolen
+/// access token isn't enough.
+pub async fn disable_totp<S: QaStore>(
+    session: Session,
+    store: S,
+    confirmation: TotpCode,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init two-factor removal");
+    match store.get_totp_factor(session.account_id.clone()).await? {
+        Some(factor) if factor.enabled => {
+            check_second_factor(&store, &factor, &confirmation.code).await?
+        }
+        Some(_) => {}
+        None => {
+            return Err(warp::reject::custom(Error::InvalidParameter(
+                "two-factor authentication is not enabled".to_string(),
+            )))
+        }
+    }
+    store.delete_totp_factor(session.account_id.clone()).await?;
+    event!(
+        Level::INFO,
+        account_id = session.account_id.0,
+        "Two-factor authentication disabled"
+    );
+    Ok(warp::reply::json(
+        &"Two-factor authentication disabled".to_string(),
+    ))
+}
+
+/// Second step of the login of accounts with two-factor authentication:
+/// exchanges the challenge token of `login` and a code for a session.
+pub async fn login_totp<S: QaStore>(
+    store: S,
+    keyring: Keyring,
+    login: TotpLogin,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init two-factor login");
+    let challenge = match verify_challenge_token(login.challenge_token, &keyring) {
+        Ok(challenge) => challenge,
+        Err(_) => return Err(warp::reject::custom(Error::Unauthorized)),
+    };
+    // The factor may have been disabled since the password was checked, in
+    // which case the login starts again.
+    let factor = match store.get_totp_factor(challenge.account_id.clone()).await? {
+        Some(factor) if factor.enabled => factor,
+        _ => return Err(warp::reject::custom(Error::Unauthorized)),
+    };
+    check_second_factor(&store, &factor, &login.code).await?;
+    let account = store.get_account_by_id(challenge.account_id).await?;
+    Ok(warp::reply::json(
+        &start_session(&store, &keyring, &account, challenge.scopes).await?,
+    ))
+}
+
+/// Checks a code of the app, or uses a recovery code. Wrong codes count as
+/// failed logins of the account, so that they can't be guessed.
+async fn check_second_factor<S: QaStore>(
+    store: &S,
+    factor: &TotpFactor,
+    code: &str,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let key = LoginKey::Account(factor.account_id.clone());
+    check_login_lock(store, &key, now.naive_utc()).await?;
+    let accepted = if is_totp_code(code.trim()) {
+        match verify_code(&factor.secret, code, now) {
+            Some(step) => store.use_totp_step(factor.account_id.clone(), step).await?,
+            None => false,
+        }
+    } else {
+        let code_hash = hash_token(&normalize_recovery_code(code));
+        let used = store
+            .use_recovery_code(factor.account_id.clone(), code_hash)
+            .await?;
+        if used {
+            event!(
+                Level::WARN,
+                account_id = factor.account_id.0,
+                "Recovery code used"
+            );
+        }
+        used
+    };
+    if !accepted {
+        add_login_failure(store, &key, now.naive_utc()).await?;
+        return Err(Error::WrongTotpCode);
+    }
+    store.clear_login_failures(&key).await?;
+    Ok(())
+}
+
+fn already_enabled() -> Error {
+    Error::InvalidParameter("two-factor authentication is already enabled".to_string())
+}
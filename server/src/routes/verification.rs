@@ -0,0 +1,77 @@
+use chrono::{Duration, Utc};
+use tracing::{event, Level};
+
+use crate::mail::{Email, Mailer};
+use crate::routes::authentication::{hash_token, new_token};
+use crate::store::QaStore;
+use crate::types::account::{Account, EmailVerification, Session};
+use crate::types::token::VerificationToken;
+use handle_errors::Error;
+
+const VERIFICATION_TOKEN_LIFETIME_HOURS: i64 = 24;
+
+/// Emails a new verification token to the account. Tokens sent before stay
+/// valid until they expire.
+pub async fn send_verification<S: QaStore, M: Mailer>(
+    store: &S,
+    mailer: &M,
+    account: Account,
+) -> Result<(), Error> {
+    let token = new_token();
+    store
+        .add_verification_token(VerificationToken {
+            token_hash: hash_token(&token),
+            account_id: account.id.expect("id not found"),
+            expires_at: Utc::now().naive_utc() + Duration::hours(VERIFICATION_TOKEN_LIFETIME_HOURS),
+        })
+        .await?;
+    mailer
+        .send(Email {
+            to: account.email,
+            subject: "Confirm your email".to_string(),
+            body: format!(
+                "Welcome! Send this token to /account/verify within {} hours to confirm your \
+                 email and start posting:\n\n{}\n\nIf you didn't register, you can ignore this \
+                 email.",
+                VERIFICATION_TOKEN_LIFETIME_HOURS, token
+            ),
+        })
+        .await
+}
+
+pub async fn verify_account<S: QaStore>(
+    store: S,
+    verification: EmailVerification,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init account verification");
+    match store
+        .verify_account(hash_token(&verification.token), Utc::now().naive_utc())
+        .await?
+    {
+        Some(account_id) => {
+            event!(Level::INFO, account_id = account_id.0, "Account verified");
+            Ok(warp::reply::json(&"Account verified".to_string()))
+        }
+        None => Err(warp::reject::custom(Error::InvalidParameter(
+            "invalid or expired verification token".to_string(),
+        ))),
+    }
+}
+
+/// Sends another verification email, for when the first one is lost or
+/// expired.
+pub async fn resend_verification<S: QaStore, M: Mailer>(
+    session: Session,
+    store: S,
+    mailer: M,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init verification resend");
+    let account = store.get_account_by_id(session.account_id).await?;
+    if account.verified {
+        return Err(warp::reject::custom(Error::InvalidParameter(
+            "account already verified".to_string(),
+        )));
+    }
+    send_verification(&store, &mailer, account).await?;
+    Ok(warp::reply::json(&"Verification email sent".to_string()))
+}
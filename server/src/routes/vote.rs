@@ -0,0 +1,85 @@
+use tracing::{event, Level};
+
+use crate::store::QaStore;
+use crate::types::account::Session;
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+use crate::types::vote::{NewVote, Score, VoteTarget};
+
+pub async fn vote_question<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+    new_vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init vote on question {}", id);
+    let account_id = session.account_id;
+    if store.is_question_owner(id, &account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::SelfVote));
+    }
+    match store
+        .vote(
+            VoteTarget::Question(QuestionId(id)),
+            account_id,
+            new_vote.direction,
+        )
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&Score { score })),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn delete_question_vote<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init delete vote on question {}", id);
+    match store
+        .delete_vote(VoteTarget::Question(QuestionId(id)), session.account_id)
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&Score { score })),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn vote_answer<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+    new_vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init vote on answer {}", id);
+    let account_id = session.account_id;
+    if store.is_answer_owner(id, &account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::SelfVote));
+    }
+    match store
+        .vote(
+            VoteTarget::Answer(AnswerId(id)),
+            account_id,
+            new_vote.direction,
+        )
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&Score { score })),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn delete_answer_vote<S: QaStore>(
+    id: i32,
+    session: Session,
+    store: S,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    event!(Level::INFO, "Init delete vote on answer {}", id);
+    match store
+        .delete_vote(VoteTarget::Answer(AnswerId(id)), session.account_id)
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&Score { score })),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
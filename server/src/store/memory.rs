@@ -0,0 +1,2431 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use futures::stream::BoxStream;
+use sqlx::error::DatabaseError;
+use tokio::sync::RwLock;
+
+use crate::store::{not_an_answer_of, not_found, QaStore};
+use crate::types::{
+    account::{
+        Account, AccountId, DeletedAccountPosts, Profile, ProfileUpdate, Role, DELETED_ACCOUNT,
+    },
+    answer::{Answer, AnswerId, NewAnswer, ACCEPTED_FIRST},
+    api_key::{ApiKey, ApiKeyId, NewApiKey},
+    comment::{Comment, CommentId, CommentTarget, NewComment},
+    export::{AnswerRecord, QuestionRecord},
+    filter::{QuestionFilter, TagMatch},
+    import::{ImportOutcome, ImportQuestion, OnDuplicate},
+    login::{LoginAttempts, LoginKey},
+    oidc::OidcState,
+    pagination::{Cursor, Keyset, KeysetOrder, KeysetPage},
+    question::{NewQuestion, Question, QuestionId, QuestionOrder},
+    revision::Revision,
+    search::{Search, SearchResult},
+    tag::{NewTag, Tag, TagUpdate},
+    token::{RefreshToken, ResetToken, VerificationToken},
+    totp::TotpFactor,
+    vote::{VoteDirection, VoteTarget},
+};
+use handle_errors::Error;
+
+// Weights of the matches in the search rank, as given by `ts_rank` to the
+// A (titles), B (question contents) and D (answers) weights.
+const TITLE_WEIGHT: f32 = 1.0;
+const CONTENT_WEIGHT: f32 = 0.4;
+const ANSWER_WEIGHT: f32 = 0.1;
+
+// SQLSTATE codes returned by Postgres for the same situations.
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+const UNIQUE_VIOLATION: &str = "23505";
+
+#[derive(Debug, Clone)]
+struct StoredQuestion {
+    question: Question,
+    account_id: AccountId,
+    // In UTC, like the `NOW()` default of the questions table.
+    created_on: NaiveDateTime,
+    deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredAnswer {
+    answer: Answer,
+    account_id: AccountId,
+}
+
+#[derive(Debug, Clone)]
+struct StoredComment {
+    comment: Comment,
+    account_id: AccountId,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    // BTreeMap keeps the rows sorted by id, which is the insertion order.
+    questions: BTreeMap<i32, StoredQuestion>,
+    answers: BTreeMap<i32, StoredAnswer>,
+    comments: BTreeMap<i32, StoredComment>,
+    // Revisions are keyed by question id and number, the primary key of the
+    // question_revisions table.
+    question_revisions: BTreeMap<(i32, i32), Revision>,
+    // Accounts are keyed by email, the primary key of the accounts table.
+    accounts: BTreeMap<String, Account>,
+    // Votes are keyed by target id and account id, the unique keys of the
+    // votes table.
+    question_votes: BTreeMap<(i32, i32), i16>,
+    answer_votes: BTreeMap<(i32, i32), i16>,
+    // Tags are keyed by name, with their description, and synonyms by name,
+    // with the name of their tag.
+    tags: BTreeMap<String, Option<String>>,
+    tag_synonyms: BTreeMap<String, String>,
+    // Refresh tokens are keyed by hash and revoked access tokens by id, with
+    // the time they expire.
+    refresh_tokens: BTreeMap<String, RefreshToken>,
+    revoked_tokens: BTreeMap<String, NaiveDateTime>,
+    // Reset tokens are keyed by hash, with whether they were used.
+    reset_tokens: BTreeMap<String, (ResetToken, bool)>,
+    verification_tokens: BTreeMap<String, (VerificationToken, bool)>,
+    // Login attempts are keyed by the text of their key.
+    login_attempts: BTreeMap<String, LoginAttempts>,
+    // Two-factor secrets are keyed by account id.
+    totp_factors: BTreeMap<i32, TotpFactor>,
+    // API keys are keyed by id, with the hash of the key.
+    api_keys: BTreeMap<i32, (ApiKey, String)>,
+    // OpenID Connect states are keyed by hash, and identities by issuer and
+    // subject, the primary key of the account_identities table.
+    oidc_states: BTreeMap<String, OidcState>,
+    account_identities: BTreeMap<(String, String), AccountId>,
+    last_question_id: i32,
+    last_answer_id: i32,
+    last_comment_id: i32,
+    last_account_id: i32,
+    last_api_key_id: i32,
+}
+
+impl Tables {
+    /// Refresh, revoked, reset and verification tokens, expired or not.
+    fn token_count(&self) -> usize {
+        self.refresh_tokens.len()
+            + self.revoked_tokens.len()
+            + self.reset_tokens.len()
+            + self.verification_tokens.len()
+            + self.oidc_states.len()
+    }
+
+    /// Removes the question with its answers and the rows referencing it,
+    /// like the cascades of the Postgres tables.
+    fn remove_question(&mut self, question_id: i32) {
+        let answer_ids: Vec<i32> = self
+            .answers
+            .values()
+            .filter(|row| row.answer.question_id.0 == question_id)
+            .map(|row| row.answer.id.0)
+            .collect();
+        for answer_id in answer_ids {
+            self.remove_answer(answer_id);
+        }
+        self.questions.remove(&question_id);
+        self.question_votes
+            .retain(|(voted_id, _), _| *voted_id != question_id);
+        self.question_revisions
+            .retain(|(revised_id, _), _| *revised_id != question_id);
+        let target = CommentTarget::Question(QuestionId(question_id));
+        self.comments
+            .retain(|_, row| !is_comment_of(&row.comment, &target));
+    }
+
+    /// Removes the answer with the rows referencing it.
+    fn remove_answer(&mut self, answer_id: i32) {
+        self.answers.remove(&answer_id);
+        self.answer_votes
+            .retain(|(voted_id, _), _| *voted_id != answer_id);
+        let target = CommentTarget::Answer(AnswerId(answer_id));
+        self.comments
+            .retain(|_, row| !is_comment_of(&row.comment, &target));
+        // ON DELETE SET NULL of questions.accepted_answer_id.
+        for row in self.questions.values_mut() {
+            if row.question.accepted_answer_id == Some(AnswerId(answer_id)) {
+                row.question.accepted_answer_id = None;
+            }
+        }
+    }
+
+    fn profile(&self, account: &Account) -> Profile {
+        let account_id = account.id.clone().expect("id not found");
+        let live_question_ids: Vec<i32> =
+            self.live_questions().map(|row| row.question.id.0).collect();
+        Profile {
+            question_count: self
+                .live_questions()
+                .filter(|row| row.account_id == account_id)
+                .count() as i64,
+            answer_count: self
+                .answers
+                .values()
+                .filter(|row| {
+                    row.account_id == account_id
+                        && live_question_ids.contains(&row.answer.question_id.0)
+                })
+                .count() as i64,
+            id: account_id,
+            display_name: account.display_name.clone(),
+            bio: account.bio.clone(),
+            role: account.role,
+        }
+    }
+
+    /// Whether the question is deleted: its answers and comments are hidden
+    /// with it, and nothing can be added to them.
+    fn is_deleted_question(&self, question_id: i32) -> bool {
+        self.questions
+            .get(&question_id)
+            .is_some_and(|row| row.deleted_at.is_some())
+    }
+
+    /// Whether the question exists and isn't deleted.
+    fn is_live_question(&self, question_id: i32) -> bool {
+        self.questions
+            .get(&question_id)
+            .is_some_and(|row| row.deleted_at.is_none())
+    }
+
+    /// Whether the answer exists and its question isn't deleted.
+    fn is_live_answer(&self, answer_id: i32) -> bool {
+        self.answers
+            .get(&answer_id)
+            .is_some_and(|row| self.is_live_question(row.answer.question_id.0))
+    }
+
+    /// Whether the comment exists and its question isn't deleted.
+    fn is_live_comment(&self, comment_id: i32) -> bool {
+        self.comments
+            .get(&comment_id)
+            .is_some_and(|row| !self.is_deleted_comment(&row.comment))
+    }
+
+    /// Whether the answer is of a deleted question.
+    fn is_deleted_answer(&self, answer_id: i32) -> bool {
+        self.answers
+            .get(&answer_id)
+            .is_some_and(|row| self.is_deleted_question(row.answer.question_id.0))
+    }
+
+    /// Whether the comment is on a deleted question or one of its answers.
+    fn is_deleted_comment(&self, comment: &Comment) -> bool {
+        match (&comment.question_id, &comment.answer_id) {
+            (Some(id), _) => self.is_deleted_question(id.0),
+            (_, Some(id)) => self.is_deleted_answer(id.0),
+            _ => false,
+        }
+    }
+
+    /// Answers of questions that aren't deleted, sorted by id.
+    fn live_answers(&self) -> impl Iterator<Item = &StoredAnswer> {
+        self.answers
+            .values()
+            .filter(|row| !self.is_deleted_question(row.answer.question_id.0))
+    }
+
+    /// Comments of the post, unless its question is deleted, sorted by id.
+    fn live_comments<'a>(
+        &'a self,
+        target: &'a CommentTarget,
+    ) -> impl Iterator<Item = &'a StoredComment> {
+        self.comments
+            .values()
+            .filter(move |row| is_comment_of(&row.comment, target))
+            .filter(|row| !self.is_deleted_comment(&row.comment))
+    }
+
+    /// Questions that aren't deleted, sorted by id.
+    fn live_questions(&self) -> impl Iterator<Item = &StoredQuestion> {
+        self.questions
+            .values()
+            .filter(|row| row.deleted_at.is_none())
+    }
+
+    /// Questions matching the filter, sorted, with their position in the
+    /// order.
+    fn sorted_questions(
+        &self,
+        filter: &QuestionFilter,
+        order: QuestionOrder,
+    ) -> Vec<(Cursor, Question)> {
+        let answer_count = |question_id: i32| {
+            self.answers
+                .values()
+                .filter(|row| row.answer.question_id.0 == question_id)
+                .count() as i64
+        };
+        let mut questions: Vec<(Cursor, Question)> = self
+            .live_questions()
+            .filter(|row| matches_filter(row, filter, answer_count(row.question.id.0) as usize))
+            .map(|row| {
+                let question = self.question(row);
+                let key = match order {
+                    QuestionOrder::Id => None,
+                    QuestionOrder::CreatedOn => Some(row.created_on.timestamp_micros()),
+                    QuestionOrder::Score => Some(question.score),
+                    QuestionOrder::AnswerCount => Some(answer_count(question.id.0)),
+                };
+                let cursor = Cursor {
+                    key,
+                    id: question.id.0,
+                };
+                (cursor, question)
+            })
+            .collect();
+        let keyset_order = order.keyset_order();
+        questions.sort_by(|(a, _), (b, _)| keyset_order.compare(a, b));
+        questions
+    }
+
+    /// Questions and answers matching the search, best matches first.
+    fn search_results(&self, search: &Search) -> Vec<SearchResult> {
+        let terms = SearchTerms::parse(&search.text);
+        let has_tags = |question: &Question| match &search.tags {
+            Some(tags) => tags
+                .iter()
+                .all(|tag| question.tags.iter().flatten().any(|t| t == tag)),
+            None => true,
+        };
+        let mut results = Vec::new();
+        for row in self.live_questions() {
+            let question = &row.question;
+            if !has_tags(question) {
+                continue;
+            }
+            let title = words(&question.title);
+            let content = words(&question.content);
+            if terms.matches(&[&title, &content]) {
+                results.push(SearchResult {
+                    question_id: question.id.clone(),
+                    answer_id: None,
+                    title: terms.highlight(&question.title),
+                    snippet: terms.highlight(&question.content),
+                    rank: terms.rank(&title, TITLE_WEIGHT) + terms.rank(&content, CONTENT_WEIGHT),
+                });
+            }
+            for answer in self.answers.values() {
+                let answer = &answer.answer;
+                if answer.question_id != question.id {
+                    continue;
+                }
+                let content = words(&answer.content);
+                if terms.matches(&[&content]) {
+                    results.push(SearchResult {
+                        question_id: question.id.clone(),
+                        answer_id: Some(answer.id.clone()),
+                        title: terms.highlight(&question.title),
+                        snippet: terms.highlight(&answer.content),
+                        rank: terms.rank(&content, ANSWER_WEIGHT),
+                    });
+                }
+            }
+        }
+        // Stable sort: equal ranks keep the question, then answer, order.
+        results.sort_by(|a, b| b.rank.total_cmp(&a.rank));
+        results
+    }
+
+    /// Tags of a question as stored by `set_question_tags` in Postgres:
+    /// synonyms replaced by their tag, unknown names left out, and `None`
+    /// rather than no tags.
+    fn resolve_tags(&self, tags: Option<Vec<String>>) -> Option<Vec<String>> {
+        let mut resolved: Vec<String> = Vec::new();
+        for name in tags.into_iter().flatten() {
+            let name = match self.tag_synonyms.get(&name) {
+                Some(tag) => tag.clone(),
+                None => name,
+            };
+            if self.tags.contains_key(&name) && !resolved.contains(&name) {
+                resolved.push(name);
+            }
+        }
+        if resolved.is_empty() {
+            None
+        } else {
+            Some(resolved)
+        }
+    }
+
+    fn tag(&self, name: &str) -> Option<Tag> {
+        let description = self.tags.get(name)?;
+        Some(Tag {
+            name: name.to_string(),
+            description: description.clone(),
+            question_count: self
+                .live_questions()
+                .filter(|row| row.question.tags.iter().flatten().any(|tag| tag == name))
+                .count() as i64,
+            synonyms: self
+                .tag_synonyms
+                .iter()
+                .filter(|(_, tag)| *tag == name)
+                .map(|(synonym, _)| synonym.clone())
+                .collect(),
+        })
+    }
+
+    fn question(&self, row: &StoredQuestion) -> Question {
+        Question {
+            score: score(&self.question_votes, row.question.id.0),
+            ..row.question.clone()
+        }
+    }
+
+    fn answer(&self, row: &StoredAnswer) -> Answer {
+        Answer {
+            score: score(&self.answer_votes, row.answer.id.0),
+            ..row.answer.clone()
+        }
+    }
+}
+
+/// Store that keeps every table in memory.
+/// Useful to run the routes without a Postgres instance (tests, demos).
+/// Clones share the same data.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    tables: Arc<RwLock<Tables>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        InMemoryStore::default()
+    }
+}
+
+#[async_trait]
+impl QaStore for InMemoryStore {
+    async fn get_answers(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Answer>, Error> {
+        let tables = self.tables.read().await;
+        let answers = tables.live_answers().map(|row| tables.answer(row));
+        Ok(paginate(answers, limit, offset))
+    }
+
+    async fn count_answers(&self) -> Result<i64, Error> {
+        Ok(self.tables.read().await.live_answers().count() as i64)
+    }
+
+    async fn get_answers_page(&self, keyset: Keyset) -> Result<KeysetPage<Answer>, Error> {
+        let tables = self.tables.read().await;
+        let answers = tables
+            .live_answers()
+            .map(|row| {
+                let cursor = Cursor {
+                    key: None,
+                    id: row.answer.id.0,
+                };
+                (cursor, tables.answer(row))
+            })
+            .collect();
+        Ok(keyset_page(answers, KeysetOrder::BY_ID, &keyset))
+    }
+
+    async fn get_answers_of_question_page(
+        &self,
+        question_id: i32,
+        keyset: Keyset,
+    ) -> Result<KeysetPage<Answer>, Error> {
+        let tables = self.tables.read().await;
+        let accepted_answer_id = tables
+            .questions
+            .get(&question_id)
+            .and_then(|row| row.question.accepted_answer_id.clone());
+        let mut answers: Vec<(Cursor, Answer)> = tables
+            .live_answers()
+            .filter(|row| row.answer.question_id.0 == question_id)
+            .map(|row| {
+                let accepted = Some(&row.answer.id) == accepted_answer_id.as_ref();
+                let cursor = Cursor {
+                    key: Some(i64::from(accepted)),
+                    id: row.answer.id.0,
+                };
+                (cursor, tables.answer(row))
+            })
+            .collect();
+        answers.sort_by(|(a, _), (b, _)| ACCEPTED_FIRST.compare(a, b));
+        Ok(keyset_page(answers, ACCEPTED_FIRST, &keyset))
+    }
+
+    async fn get_answers_of_question(
+        &self,
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Answer>, Error> {
+        let tables = self.tables.read().await;
+        let accepted_answer_id = tables
+            .questions
+            .get(&question_id)
+            .and_then(|row| row.question.accepted_answer_id.clone());
+        let mut answers: Vec<Answer> = tables
+            .live_answers()
+            .filter(|row| row.answer.question_id.0 == question_id)
+            .map(|row| tables.answer(row))
+            .collect();
+        // Stable sort: the other answers keep their insertion order.
+        answers.sort_by_key(|answer| Some(&answer.id) != accepted_answer_id.as_ref());
+        Ok(paginate(answers.into_iter(), limit, offset))
+    }
+
+    async fn count_answers_of_question(&self, question_id: i32) -> Result<i64, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables
+            .live_answers()
+            .filter(|row| row.answer.question_id.0 == question_id)
+            .count() as i64)
+    }
+
+    async fn get_question(&self, question_id: i32) -> Result<Question, Error> {
+        let tables = self.tables.read().await;
+        match tables.questions.get(&question_id) {
+            Some(row) if row.deleted_at.is_none() => Ok(tables.question(row)),
+            _ => Err(not_found("question", question_id)),
+        }
+    }
+
+    async fn get_questions(
+        &self,
+        filter: QuestionFilter,
+        order: QuestionOrder,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Question>, Error> {
+        let tables = self.tables.read().await;
+        let questions = tables
+            .sorted_questions(&filter, order)
+            .into_iter()
+            .map(|(_, question)| question);
+        Ok(paginate(questions, limit, offset))
+    }
+
+    async fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.sorted_questions(filter, QuestionOrder::Id).len() as i64)
+    }
+
+    async fn get_questions_page(
+        &self,
+        filter: QuestionFilter,
+        order: QuestionOrder,
+        keyset: Keyset,
+    ) -> Result<KeysetPage<Question>, Error> {
+        let tables = self.tables.read().await;
+        let questions = tables.sorted_questions(&filter, order);
+        Ok(keyset_page(questions, order.keyset_order(), &keyset))
+    }
+
+    async fn add_question(
+        &self,
+        new_question: NewQuestion,
+        account_id: AccountId,
+    ) -> Result<Question, Error> {
+        let mut tables = self.tables.write().await;
+        tables.last_question_id += 1;
+        let question = Question {
+            id: QuestionId(tables.last_question_id),
+            title: new_question.title,
+            content: new_question.content,
+            tags: tables.resolve_tags(new_question.tags),
+            accepted_answer_id: None,
+            score: 0,
+        };
+        tables.questions.insert(
+            question.id.0,
+            StoredQuestion {
+                question: question.clone(),
+                account_id,
+                created_on: Utc::now().naive_utc(),
+                deleted_at: None,
+            },
+        );
+        Ok(question)
+    }
+
+    // Words are compared in lowercase, without the stemming and stop words
+    // of the Postgres text search.
+    async fn search(&self, search: Search) -> Result<Vec<SearchResult>, Error> {
+        let tables = self.tables.read().await;
+        Ok(paginate(
+            tables.search_results(&search).into_iter(),
+            search.pagination.limit,
+            search.pagination.offset,
+        ))
+    }
+
+    async fn count_search_results(&self, search: &Search) -> Result<i64, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.search_results(search).len() as i64)
+    }
+
+    async fn update_question(
+        &self,
+        question: Question,
+        question_id: i32,
+        account_id: AccountId,
+    ) -> Result<Question, Error> {
+        let mut tables = self.tables.write().await;
+        let tags = tables.resolve_tags(question.tags);
+        let number = tables
+            .question_revisions
+            .range((question_id, 0)..=(question_id, i32::MAX))
+            .count() as i32
+            + 1;
+        match tables.questions.get_mut(&question_id) {
+            Some(row) if row.deleted_at.is_none() => {
+                let revision = Revision {
+                    number,
+                    question_id: QuestionId(question_id),
+                    title: row.question.title.clone(),
+                    content: row.question.content.clone(),
+                    tags: row.question.tags.clone(),
+                    account_id,
+                    created_on: Utc::now().naive_utc(),
+                };
+                row.question.title = question.title;
+                row.question.content = question.content;
+                row.question.tags = tags;
+                let row = row.clone();
+                tables
+                    .question_revisions
+                    .insert((question_id, number), revision);
+                Ok(tables.question(&row))
+            }
+            _ => Err(not_found("question", question_id)),
+        }
+    }
+
+    async fn get_revisions(
+        &self,
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Revision>, Error> {
+        let tables = self.tables.read().await;
+        let revisions = tables
+            .question_revisions
+            .range((question_id, 0)..=(question_id, i32::MAX))
+            .map(|(_, revision)| revision.clone());
+        Ok(paginate(revisions, limit, offset))
+    }
+
+    async fn count_revisions(&self, question_id: i32) -> Result<i64, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables
+            .question_revisions
+            .range((question_id, 0)..=(question_id, i32::MAX))
+            .count() as i64)
+    }
+
+    async fn get_revision(&self, question_id: i32, number: i32) -> Result<Option<Revision>, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables
+            .question_revisions
+            .get(&(question_id, number))
+            .cloned())
+    }
+
+    async fn delete_question(&self, question_id: i32) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        match tables.questions.get_mut(&question_id) {
+            Some(row) if row.deleted_at.is_none() => {
+                row.deleted_at = Some(Utc::now().naive_utc());
+                Ok(true)
+            }
+            _ => Err(not_found("question", question_id)),
+        }
+    }
+
+    async fn restore_question(&self, question_id: i32) -> Result<Question, Error> {
+        let mut tables = self.tables.write().await;
+        match tables.questions.get_mut(&question_id) {
+            Some(row) if row.deleted_at.is_some() => {
+                row.deleted_at = None;
+                let row = row.clone();
+                Ok(tables.question(&row))
+            }
+            _ => Err(not_found("question", question_id)),
+        }
+    }
+
+    async fn purge_questions(&self, deleted_before: NaiveDateTime) -> Result<u64, Error> {
+        let mut tables = self.tables.write().await;
+        let purged: Vec<i32> = tables
+            .questions
+            .values()
+            .filter(|row| row.deleted_at.is_some_and(|at| at < deleted_before))
+            .map(|row| row.question.id.0)
+            .collect();
+        for question_id in &purged {
+            tables.remove_question(*question_id);
+        }
+        Ok(purged.len() as u64)
+    }
+
+    async fn accept_answer(
+        &self,
+        question_id: i32,
+        answer_id: i32,
+        account_id: AccountId,
+    ) -> Result<Question, Error> {
+        let mut tables = self.tables.write().await;
+        if !tables.is_live_answer(answer_id) {
+            return Err(not_found("answer", answer_id));
+        }
+        if tables.answers[&answer_id].answer.question_id.0 != question_id {
+            return Err(not_an_answer_of(answer_id, question_id));
+        }
+        match tables.questions.get_mut(&question_id) {
+            Some(row) if row.account_id == account_id => {
+                row.question.accepted_answer_id = Some(AnswerId(answer_id));
+                let row = row.clone();
+                Ok(tables.question(&row))
+            }
+            _ => Err(not_found("question", question_id)),
+        }
+    }
+
+    async fn add_answer(
+        &self,
+        new_answer: NewAnswer,
+        account_id: AccountId,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if !tables.is_live_question(new_answer.question_id.0) {
+            return Err(not_found("question", new_answer.question_id.0));
+        }
+        tables.last_answer_id += 1;
+        let answer = Answer {
+            id: AnswerId(tables.last_answer_id),
+            content: new_answer.content,
+            question_id: new_answer.question_id,
+            score: 0,
+        };
+        tables
+            .answers
+            .insert(answer.id.0, StoredAnswer { answer, account_id });
+        Ok(true)
+    }
+
+    async fn update_answer(&self, answer: Answer, answer_id: i32) -> Result<Answer, Error> {
+        let mut tables = self.tables.write().await;
+        if !tables.is_live_answer(answer_id) {
+            return Err(not_found("answer", answer_id));
+        }
+        let row = tables.answers.get_mut(&answer_id).unwrap();
+        row.answer.content = answer.content;
+        let row = row.clone();
+        Ok(tables.answer(&row))
+    }
+
+    async fn delete_answer(&self, answer_id: i32) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if !tables.is_live_answer(answer_id) {
+            return Err(not_found("answer", answer_id));
+        }
+        tables.remove_answer(answer_id);
+        Ok(true)
+    }
+
+    async fn vote(
+        &self,
+        target: VoteTarget,
+        account_id: AccountId,
+        direction: VoteDirection,
+    ) -> Result<i64, Error> {
+        let mut tables = self.tables.write().await;
+        let votes = match &target {
+            VoteTarget::Question(id) if tables.is_live_question(id.0) => &mut tables.question_votes,
+            VoteTarget::Answer(id) if tables.is_live_answer(id.0) => &mut tables.answer_votes,
+            VoteTarget::Question(id) => return Err(not_found("question", id.0)),
+            VoteTarget::Answer(id) => return Err(not_found("answer", id.0)),
+        };
+        let target_id = target_id(&target);
+        votes.insert((target_id, account_id.0), direction.value());
+        Ok(score(votes, target_id))
+    }
+
+    async fn delete_vote(&self, target: VoteTarget, account_id: AccountId) -> Result<i64, Error> {
+        let mut tables = self.tables.write().await;
+        let votes = match &target {
+            VoteTarget::Question(id) if tables.is_live_question(id.0) => &mut tables.question_votes,
+            VoteTarget::Answer(id) if tables.is_live_answer(id.0) => &mut tables.answer_votes,
+            VoteTarget::Question(id) => return Err(not_found("question", id.0)),
+            VoteTarget::Answer(id) => return Err(not_found("answer", id.0)),
+        };
+        let target_id = target_id(&target);
+        votes.remove(&(target_id, account_id.0));
+        Ok(score(votes, target_id))
+    }
+
+    async fn get_comments(
+        &self,
+        target: CommentTarget,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Comment>, Error> {
+        let tables = self.tables.read().await;
+        let comments = tables.live_comments(&target).map(|row| row.comment.clone());
+        Ok(paginate(comments, limit, offset))
+    }
+
+    async fn count_comments(&self, target: &CommentTarget) -> Result<i64, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.live_comments(target).count() as i64)
+    }
+
+    async fn add_comment(
+        &self,
+        target: CommentTarget,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error> {
+        let mut tables = self.tables.write().await;
+        let (question_id, answer_id) = match target {
+            CommentTarget::Question(id) => {
+                if !tables.is_live_question(id.0) {
+                    return Err(not_found("question", id.0));
+                }
+                (Some(id), None)
+            }
+            CommentTarget::Answer(id) => {
+                if !tables.is_live_answer(id.0) {
+                    return Err(not_found("answer", id.0));
+                }
+                (None, Some(id))
+            }
+        };
+        tables.last_comment_id += 1;
+        let comment = Comment {
+            id: CommentId(tables.last_comment_id),
+            content: new_comment.content,
+            question_id,
+            answer_id,
+        };
+        tables.comments.insert(
+            comment.id.0,
+            StoredComment {
+                comment: comment.clone(),
+                account_id,
+            },
+        );
+        Ok(comment)
+    }
+
+    async fn update_comment(
+        &self,
+        target: CommentTarget,
+        comment_id: i32,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error> {
+        let mut tables = self.tables.write().await;
+        if !tables.is_live_comment(comment_id) {
+            return Err(not_found("comment", comment_id));
+        }
+        match tables.comments.get_mut(&comment_id) {
+            Some(row) if row.account_id == account_id && is_comment_of(&row.comment, &target) => {
+                row.comment.content = new_comment.content;
+                Ok(row.comment.clone())
+            }
+            _ => Err(not_found("comment", comment_id)),
+        }
+    }
+
+    async fn delete_comment(
+        &self,
+        target: CommentTarget,
+        comment_id: i32,
+        account_id: AccountId,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if !tables.is_live_comment(comment_id) {
+            return Err(not_found("comment", comment_id));
+        }
+        match tables.comments.get(&comment_id) {
+            Some(row) if row.account_id == account_id && is_comment_of(&row.comment, &target) => {
+                tables.comments.remove(&comment_id);
+                Ok(true)
+            }
+            _ => Err(not_found("comment", comment_id)),
+        }
+    }
+
+    async fn get_tags(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Tag>, Error> {
+        let tables = self.tables.read().await;
+        let mut tags: Vec<Tag> = tables
+            .tags
+            .keys()
+            .filter_map(|name| tables.tag(name))
+            .collect();
+        // Stable sort: equal counts keep the order of the names.
+        tags.sort_by_key(|tag| std::cmp::Reverse(tag.question_count));
+        Ok(paginate(tags.into_iter(), limit, offset))
+    }
+
+    async fn count_tags(&self) -> Result<i64, Error> {
+        Ok(self.tables.read().await.tags.len() as i64)
+    }
+
+    async fn get_tag(&self, name: String) -> Result<Option<Tag>, Error> {
+        let tables = self.tables.read().await;
+        let name = tables.tag_synonyms.get(&name).unwrap_or(&name);
+        Ok(tables.tag(name))
+    }
+
+    async fn add_tag(&self, new_tag: NewTag) -> Result<Tag, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.tags.contains_key(&new_tag.name) {
+            return Err(constraint_violation(UNIQUE_VIOLATION, "tags_name_key"));
+        }
+        tables
+            .tags
+            .insert(new_tag.name.clone(), new_tag.description);
+        Ok(tables.tag(&new_tag.name).expect("the tag was just added"))
+    }
+
+    async fn update_tag(&self, name: String, update: TagUpdate) -> Result<Tag, Error> {
+        let mut tables = self.tables.write().await;
+        match tables.tags.get_mut(&name) {
+            Some(description) => *description = update.description,
+            None => return Err(row_not_found()),
+        }
+        Ok(tables.tag(&name).expect("the tag exists"))
+    }
+
+    async fn add_tag_synonym(&self, name: String, synonym: String) -> Result<Tag, Error> {
+        let mut tables = self.tables.write().await;
+        if !tables.tags.contains_key(&name) {
+            return Err(row_not_found());
+        }
+        if tables.tag_synonyms.contains_key(&synonym) {
+            return Err(constraint_violation(UNIQUE_VIOLATION, "tag_synonyms_pkey"));
+        }
+        tables.tag_synonyms.insert(synonym, name.clone());
+        Ok(tables.tag(&name).expect("the tag exists"))
+    }
+
+    async fn merge_tags(&self, name: String, into: String) -> Result<Tag, Error> {
+        let mut tables = self.tables.write().await;
+        if !tables.tags.contains_key(&name) || !tables.tags.contains_key(&into) {
+            return Err(row_not_found());
+        }
+        for row in tables.questions.values_mut() {
+            if let Some(tags) = &mut row.question.tags {
+                if let Some(position) = tags.iter().position(|tag| *tag == name) {
+                    // Questions having both tags keep the one they already have.
+                    if tags.contains(&into) {
+                        tags.remove(position);
+                    } else {
+                        tags[position] = into.clone();
+                    }
+                }
+            }
+        }
+        for tag in tables.tag_synonyms.values_mut() {
+            if *tag == name {
+                *tag = into.clone();
+            }
+        }
+        tables.tags.remove(&name);
+        tables.tag_synonyms.insert(name, into.clone());
+        Ok(tables.tag(&into).expect("the tag exists"))
+    }
+
+    async fn add_account(&self, account: Account) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.accounts.contains_key(&account.email) {
+            return Err(constraint_violation(UNIQUE_VIOLATION, "accounts_pkey"));
+        }
+        tables.last_account_id += 1;
+        let account = Account {
+            id: Some(AccountId(tables.last_account_id)),
+            email: account.email,
+            password: account.password,
+            role: account.role,
+            verified: account.verified,
+            display_name: account.display_name,
+            bio: account.bio,
+        };
+        tables.accounts.insert(account.email.clone(), account);
+        Ok(true)
+    }
+
+    async fn get_account(&self, email: String) -> Result<Account, Error> {
+        let tables = self.tables.read().await;
+        match tables.accounts.get(&email) {
+            Some(account) => Ok(account.clone()),
+            None => Err(row_not_found()),
+        }
+    }
+
+    async fn get_account_by_id(&self, account_id: AccountId) -> Result<Account, Error> {
+        let tables = self.tables.read().await;
+        match tables
+            .accounts
+            .values()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => Ok(account.clone()),
+            None => Err(row_not_found()),
+        }
+    }
+
+    async fn set_account_role(&self, account_id: AccountId, role: Role) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        match tables
+            .accounts
+            .values_mut()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => {
+                account.role = role;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_account_password(
+        &self,
+        account_id: AccountId,
+        password: String,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        tables
+            .refresh_tokens
+            .retain(|_, token| token.account_id != account_id);
+        match tables
+            .accounts
+            .values_mut()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => {
+                account.password = password;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_profile(&self, account_id: AccountId) -> Result<Option<Profile>, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables
+            .accounts
+            .values()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+            .map(|account| tables.profile(account)))
+    }
+
+    async fn get_profiles(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Profile>, Error> {
+        let tables = self.tables.read().await;
+        let mut accounts: Vec<&Account> = tables.accounts.values().collect();
+        accounts.sort_by_key(|account| account.id.as_ref().map(|id| id.0));
+        Ok(accounts
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(|account| tables.profile(account))
+            .collect())
+    }
+
+    async fn count_accounts(&self) -> Result<i64, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.accounts.len() as i64)
+    }
+
+    async fn update_profile(
+        &self,
+        account_id: AccountId,
+        update: ProfileUpdate,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        match tables
+            .accounts
+            .values_mut()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => {
+                if let Some(display_name) = update.display_name {
+                    account.display_name = Some(display_name).filter(|name| !name.is_empty());
+                }
+                if let Some(bio) = update.bio {
+                    account.bio = Some(bio).filter(|bio| !bio.is_empty());
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_account(
+        &self,
+        account_id: AccountId,
+        posts: DeletedAccountPosts,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        let email = match tables
+            .accounts
+            .values()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            Some(account) => account.email.clone(),
+            None => return Ok(false),
+        };
+        tables.accounts.remove(&email);
+        match posts {
+            DeletedAccountPosts::Remove => {
+                let question_ids: Vec<i32> = tables
+                    .questions
+                    .values()
+                    .filter(|row| row.account_id == account_id)
+                    .map(|row| row.question.id.0)
+                    .collect();
+                for question_id in question_ids {
+                    tables.remove_question(question_id);
+                }
+                let answer_ids: Vec<i32> = tables
+                    .answers
+                    .values()
+                    .filter(|row| row.account_id == account_id)
+                    .map(|row| row.answer.id.0)
+                    .collect();
+                for answer_id in answer_ids {
+                    tables.remove_answer(answer_id);
+                }
+                tables
+                    .comments
+                    .retain(|_, row| row.account_id != account_id);
+            }
+            DeletedAccountPosts::Anonymize => {
+                for row in tables.questions.values_mut() {
+                    if row.account_id == account_id {
+                        row.account_id = DELETED_ACCOUNT;
+                    }
+                }
+                for row in tables.answers.values_mut() {
+                    if row.account_id == account_id {
+                        row.account_id = DELETED_ACCOUNT;
+                    }
+                }
+                for row in tables.comments.values_mut() {
+                    if row.account_id == account_id {
+                        row.account_id = DELETED_ACCOUNT;
+                    }
+                }
+            }
+        }
+        for revision in tables.question_revisions.values_mut() {
+            if revision.account_id == account_id {
+                revision.account_id = DELETED_ACCOUNT;
+            }
+        }
+        tables
+            .question_votes
+            .retain(|(_, voter_id), _| *voter_id != account_id.0);
+        tables
+            .answer_votes
+            .retain(|(_, voter_id), _| *voter_id != account_id.0);
+        tables
+            .refresh_tokens
+            .retain(|_, token| token.account_id != account_id);
+        tables
+            .reset_tokens
+            .retain(|_, (token, _)| token.account_id != account_id);
+        tables
+            .verification_tokens
+            .retain(|_, (token, _)| token.account_id != account_id);
+        tables.totp_factors.remove(&account_id.0);
+        tables
+            .api_keys
+            .retain(|_, (api_key, _)| api_key.account_id != account_id);
+        tables
+            .account_identities
+            .retain(|_, linked_id| *linked_id != account_id);
+        tables
+            .login_attempts
+            .remove(&LoginKey::Account(account_id).to_string());
+        Ok(true)
+    }
+
+    async fn add_refresh_token(&self, token: RefreshToken) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.refresh_tokens.contains_key(&token.token_hash) {
+            return Err(constraint_violation(
+                UNIQUE_VIOLATION,
+                "refresh_tokens_pkey",
+            ));
+        }
+        tables
+            .refresh_tokens
+            .insert(token.token_hash.clone(), token);
+        Ok(true)
+    }
+
+    async fn get_refresh_token(&self, token_hash: String) -> Result<Option<RefreshToken>, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.refresh_tokens.get(&token_hash).cloned())
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        token_hash: String,
+        next: RefreshToken,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        match tables.refresh_tokens.get_mut(&token_hash) {
+            Some(token) if !token.used => token.used = true,
+            _ => return Ok(false),
+        }
+        tables.refresh_tokens.insert(next.token_hash.clone(), next);
+        Ok(true)
+    }
+
+    async fn revoke_refresh_tokens(&self, sid: String) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        let count = tables.refresh_tokens.len();
+        tables.refresh_tokens.retain(|_, token| token.sid != sid);
+        Ok(tables.refresh_tokens.len() < count)
+    }
+
+    async fn revoke_token(&self, jti: String, expires_at: NaiveDateTime) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.revoked_tokens.contains_key(&jti) {
+            return Ok(false);
+        }
+        tables.revoked_tokens.insert(jti, expires_at);
+        Ok(true)
+    }
+
+    async fn is_token_revoked(&self, jti: &str) -> Result<bool, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.revoked_tokens.contains_key(jti))
+    }
+
+    async fn add_reset_token(&self, token: ResetToken) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.reset_tokens.contains_key(&token.token_hash) {
+            return Err(constraint_violation(
+                UNIQUE_VIOLATION,
+                "password_reset_tokens_pkey",
+            ));
+        }
+        tables
+            .reset_tokens
+            .insert(token.token_hash.clone(), (token, false));
+        Ok(true)
+    }
+
+    async fn use_reset_token(
+        &self,
+        token_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<AccountId>, Error> {
+        let mut tables = self.tables.write().await;
+        match tables.reset_tokens.get_mut(&token_hash) {
+            Some((token, used)) if !*used && token.expires_at > now => {
+                *used = true;
+                Ok(Some(token.account_id.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn add_verification_token(&self, token: VerificationToken) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.verification_tokens.contains_key(&token.token_hash) {
+            return Err(constraint_violation(
+                UNIQUE_VIOLATION,
+                "email_verification_tokens_pkey",
+            ));
+        }
+        tables
+            .verification_tokens
+            .insert(token.token_hash.clone(), (token, false));
+        Ok(true)
+    }
+
+    async fn verify_account(
+        &self,
+        token_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<AccountId>, Error> {
+        let mut tables = self.tables.write().await;
+        let account_id = match tables.verification_tokens.get_mut(&token_hash) {
+            Some((token, used)) if !*used && token.expires_at > now => {
+                *used = true;
+                token.account_id.clone()
+            }
+            _ => return Ok(None),
+        };
+        if let Some(account) = tables
+            .accounts
+            .values_mut()
+            .find(|account| account.id.as_ref() == Some(&account_id))
+        {
+            account.verified = true;
+        }
+        Ok(Some(account_id))
+    }
+
+    async fn purge_expired_tokens(&self, now: NaiveDateTime) -> Result<u64, Error> {
+        let mut tables = self.tables.write().await;
+        let count = tables.token_count();
+        tables
+            .refresh_tokens
+            .retain(|_, token| token.expires_at >= now);
+        tables
+            .revoked_tokens
+            .retain(|_, expires_at| *expires_at >= now);
+        tables
+            .reset_tokens
+            .retain(|_, (token, _)| token.expires_at >= now);
+        tables
+            .verification_tokens
+            .retain(|_, (token, _)| token.expires_at >= now);
+        tables
+            .oidc_states
+            .retain(|_, state| state.expires_at >= now);
+        Ok((count - tables.token_count()) as u64)
+    }
+
+    async fn add_login_failure(
+        &self,
+        key: &LoginKey,
+        now: NaiveDateTime,
+        reset_before: NaiveDateTime,
+    ) -> Result<LoginAttempts, Error> {
+        let mut tables = self.tables.write().await;
+        let attempts = tables
+            .login_attempts
+            .entry(key.to_string())
+            .or_insert(LoginAttempts {
+                failures: 0,
+                last_failure_at: now,
+            });
+        if attempts.last_failure_at < reset_before {
+            attempts.failures = 0;
+        }
+        attempts.failures += 1;
+        attempts.last_failure_at = now;
+        Ok(attempts.clone())
+    }
+
+    async fn get_login_attempts(&self, key: &LoginKey) -> Result<Option<LoginAttempts>, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.login_attempts.get(&key.to_string()).cloned())
+    }
+
+    async fn clear_login_failures(&self, key: &LoginKey) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        Ok(tables.login_attempts.remove(&key.to_string()).is_some())
+    }
+
+    async fn purge_login_attempts(&self, before: NaiveDateTime) -> Result<u64, Error> {
+        let mut tables = self.tables.write().await;
+        let count = tables.login_attempts.len();
+        tables
+            .login_attempts
+            .retain(|_, attempts| attempts.last_failure_at >= before);
+        Ok((count - tables.login_attempts.len()) as u64)
+    }
+
+    async fn set_totp_factor(&self, factor: TotpFactor) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        tables.totp_factors.insert(factor.account_id.0, factor);
+        Ok(true)
+    }
+
+    async fn get_totp_factor(&self, account_id: AccountId) -> Result<Option<TotpFactor>, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.totp_factors.get(&account_id.0).cloned())
+    }
+
+    async fn use_totp_step(&self, account_id: AccountId, step: i64) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        match tables.totp_factors.get_mut(&account_id.0) {
+            Some(factor) if factor.enabled && factor.last_used_step < Some(step) => {
+                factor.last_used_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn use_recovery_code(
+        &self,
+        account_id: AccountId,
+        code_hash: String,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        match tables.totp_factors.get_mut(&account_id.0) {
+            Some(factor) if factor.enabled => {
+                let count = factor.recovery_code_hashes.len();
+                factor
+                    .recovery_code_hashes
+                    .retain(|hash| *hash != code_hash);
+                Ok(factor.recovery_code_hashes.len() < count)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_totp_factor(&self, account_id: AccountId) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        Ok(tables.totp_factors.remove(&account_id.0).is_some())
+    }
+
+    async fn add_api_key(
+        &self,
+        account_id: AccountId,
+        new_key: NewApiKey,
+        key_hash: String,
+    ) -> Result<ApiKey, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.api_keys.values().any(|(_, hash)| *hash == key_hash) {
+            return Err(constraint_violation(
+                UNIQUE_VIOLATION,
+                "api_keys_key_hash_key",
+            ));
+        }
+        tables.last_api_key_id += 1;
+        let api_key = ApiKey {
+            id: ApiKeyId(tables.last_api_key_id),
+            account_id,
+            name: new_key.name,
+            scopes: new_key.scopes,
+            created_on: Utc::now().naive_utc(),
+            last_used_at: None,
+        };
+        tables
+            .api_keys
+            .insert(api_key.id.0, (api_key.clone(), key_hash));
+        Ok(api_key)
+    }
+
+    async fn get_api_keys(&self, account_id: AccountId) -> Result<Vec<ApiKey>, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables
+            .api_keys
+            .values()
+            .filter(|(api_key, _)| api_key.account_id == account_id)
+            .map(|(api_key, _)| api_key.clone())
+            .collect())
+    }
+
+    async fn use_api_key(
+        &self,
+        key_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<ApiKey>, Error> {
+        let mut tables = self.tables.write().await;
+        Ok(tables
+            .api_keys
+            .values_mut()
+            .find(|(_, hash)| *hash == key_hash)
+            .map(|(api_key, _)| {
+                api_key.last_used_at = Some(now);
+                api_key.clone()
+            }))
+    }
+
+    async fn delete_api_key(&self, account_id: AccountId, id: ApiKeyId) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        match tables.api_keys.get(&id.0) {
+            Some((api_key, _)) if api_key.account_id == account_id => {
+                tables.api_keys.remove(&id.0);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn add_oidc_state(&self, state: OidcState) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        if tables.oidc_states.contains_key(&state.state_hash) {
+            return Err(constraint_violation(UNIQUE_VIOLATION, "oidc_states_pkey"));
+        }
+        tables.oidc_states.insert(state.state_hash.clone(), state);
+        Ok(true)
+    }
+
+    async fn take_oidc_state(
+        &self,
+        state_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<OidcState>, Error> {
+        let mut tables = self.tables.write().await;
+        Ok(tables
+            .oidc_states
+            .remove(&state_hash)
+            .filter(|state| state.expires_at > now))
+    }
+
+    async fn get_account_identity(
+        &self,
+        issuer: String,
+        subject: String,
+    ) -> Result<Option<AccountId>, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables.account_identities.get(&(issuer, subject)).cloned())
+    }
+
+    async fn add_account_identity(
+        &self,
+        issuer: String,
+        subject: String,
+        account_id: AccountId,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables.write().await;
+        let key = (issuer, subject);
+        if tables.account_identities.contains_key(&key) {
+            return Err(constraint_violation(
+                UNIQUE_VIOLATION,
+                "account_identities_pkey",
+            ));
+        }
+        tables.account_identities.insert(key, account_id);
+        Ok(true)
+    }
+
+    async fn is_question_owner(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let tables = self.tables.read().await;
+        Ok(tables
+            .questions
+            .get(&question_id)
+            .is_some_and(|row| &row.account_id == account_id))
+    }
+
+    async fn is_answer_owner(&self, answer_id: i32, account_id: &AccountId) -> Result<bool, Error> {
+        let tables = self.tables.read().await;
+        if !tables.is_live_answer(answer_id) {
+            return Err(not_found("answer", answer_id));
+        }
+        Ok(tables.answers[&answer_id].account_id == *account_id)
+    }
+
+    async fn is_comment_owner(
+        &self,
+        comment_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        let tables = self.tables.read().await;
+        if !tables.is_live_comment(comment_id) {
+            return Err(not_found("comment", comment_id));
+        }
+        Ok(tables.comments[&comment_id].account_id == *account_id)
+    }
+
+    async fn import_questions(
+        &self,
+        questions: Vec<ImportQuestion>,
+        account_id: AccountId,
+        on_duplicate: OnDuplicate,
+    ) -> Result<Vec<Result<ImportOutcome, String>>, Error> {
+        // Holding the write lock for the whole import makes it atomic for
+        // the readers, like the transaction of the Postgres store.
+        let mut tables = self.tables.write().await;
+        let mut outcomes = Vec::with_capacity(questions.len());
+        for question in questions {
+            let exists = question
+                .id
+                .as_ref()
+                .is_some_and(|id| tables.questions.contains_key(&id.0));
+            let outcome = match (exists, on_duplicate) {
+                (true, OnDuplicate::Skip) => {
+                    outcomes.push(Ok(ImportOutcome::Skipped));
+                    continue;
+                }
+                (true, OnDuplicate::Upsert) => ImportOutcome::Updated,
+                (false, _) => ImportOutcome::Inserted,
+            };
+            let question_id = match question.id {
+                Some(id) => id,
+                None => QuestionId(tables.last_question_id + 1),
+            };
+            tables.last_question_id = tables.last_question_id.max(question_id.0);
+            // Updated questions keep their creation time, and their deletion
+            // time unless the record has one.
+            let (created_on, deleted_at) = match tables.questions.get(&question_id.0) {
+                Some(row) => (row.created_on, question.deleted_at.or(row.deleted_at)),
+                None => (Utc::now().naive_utc(), question.deleted_at),
+            };
+            for name in question.tags.iter().flatten() {
+                if !name.is_empty() && !tables.tag_synonyms.contains_key(name) {
+                    tables.tags.entry(name.clone()).or_insert(None);
+                }
+            }
+            let tags = tables.resolve_tags(question.tags);
+            tables.questions.insert(
+                question_id.0,
+                StoredQuestion {
+                    question: Question {
+                        id: question_id.clone(),
+                        title: question.title,
+                        content: question.content,
+                        tags,
+                        accepted_answer_id: None,
+                        score: 0,
+                    },
+                    account_id: account_id.clone(),
+                    created_on,
+                    deleted_at,
+                },
+            );
+            let accepted_answer_id = question.accepted_answer_id;
+            for answer in question.answers {
+                let exists = answer
+                    .id
+                    .as_ref()
+                    .is_some_and(|id| tables.answers.contains_key(&id.0));
+                if exists && on_duplicate == OnDuplicate::Skip {
+                    continue;
+                }
+                let answer_id = match answer.id {
+                    Some(id) => id,
+                    None => AnswerId(tables.last_answer_id + 1),
+                };
+                tables.last_answer_id = tables.last_answer_id.max(answer_id.0);
+                tables.answers.insert(
+                    answer_id.0,
+                    StoredAnswer {
+                        answer: Answer {
+                            id: answer_id,
+                            content: answer.content,
+                            question_id: question_id.clone(),
+                            score: 0,
+                        },
+                        account_id: account_id.clone(),
+                    },
+                );
+            }
+            if let Some(answer_id) = accepted_answer_id {
+                let answer_of_question = tables
+                    .answers
+                    .get(&answer_id.0)
+                    .is_some_and(|row| row.answer.question_id == question_id);
+                if answer_of_question {
+                    if let Some(row) = tables.questions.get_mut(&question_id.0) {
+                        row.question.accepted_answer_id = Some(answer_id);
+                    }
+                }
+            }
+            outcomes.push(Ok(outcome));
+        }
+        Ok(outcomes)
+    }
+
+    // The streams copy the rows when they are first polled, so the lock
+    // isn't held while the consumer writes them out.
+    fn stream_accounts(&self) -> BoxStream<'static, Result<Account, Error>> {
+        let tables = self.tables.clone();
+        Box::pin(async_stream::stream! {
+            let mut accounts: Vec<Account> = tables.read().await.accounts.values().cloned().collect();
+            accounts.sort_by_key(|account| account.id.as_ref().map(|id| id.0));
+            for account in accounts {
+                yield Ok(account);
+            }
+        })
+    }
+
+    fn stream_questions(&self) -> BoxStream<'static, Result<QuestionRecord, Error>> {
+        let tables = self.tables.clone();
+        Box::pin(async_stream::stream! {
+            let questions: Vec<QuestionRecord> = tables
+                .read()
+                .await
+                .questions
+                .values()
+                .map(|row| QuestionRecord {
+                    id: row.question.id.clone(),
+                    title: row.question.title.clone(),
+                    content: row.question.content.clone(),
+                    tags: row.question.tags.clone(),
+                    accepted_answer_id: row.question.accepted_answer_id.clone(),
+                    account_id: row.account_id.clone(),
+                    deleted_at: row.deleted_at,
+                })
+                .collect();
+            for question in questions {
+                yield Ok(question);
+            }
+        })
+    }
+
+    fn stream_answers(&self) -> BoxStream<'static, Result<AnswerRecord, Error>> {
+        let tables = self.tables.clone();
+        Box::pin(async_stream::stream! {
+            let mut answers: Vec<AnswerRecord> = {
+                let tables = tables.read().await;
+                tables
+                    .answers
+                    .values()
+                    .map(|row| AnswerRecord {
+                        id: row.answer.id.clone(),
+                        content: row.answer.content.clone(),
+                        question_id: row.answer.question_id.clone(),
+                        account_id: row.account_id.clone(),
+                    })
+                    .collect()
+            };
+            answers.sort_by_key(|answer| (answer.question_id.0, answer.id.0));
+            for answer in answers {
+                yield Ok(answer);
+            }
+        })
+    }
+}
+
+// Same semantics as `LIMIT $1 OFFSET $2`, where a NULL limit means no limit.
+fn paginate<T>(rows: impl Iterator<Item = T>, limit: Option<u32>, offset: u32) -> Vec<T> {
+    let rows = rows.skip(offset as usize);
+    match limit {
+        Some(limit) => rows.take(limit as usize).collect(),
+        None => rows.collect(),
+    }
+}
+
+/// Words of a `websearch_to_tsquery` search: every word is required, except
+/// the ones starting with `-`, which are excluded.
+struct SearchTerms {
+    required: Vec<String>,
+    excluded: Vec<String>,
+}
+
+impl SearchTerms {
+    fn parse(text: &str) -> Self {
+        let mut terms = SearchTerms {
+            required: Vec::new(),
+            excluded: Vec::new(),
+        };
+        for term in text.split_whitespace() {
+            match term.strip_prefix('-') {
+                Some(term) => terms.excluded.extend(words(term)),
+                None => terms.required.extend(words(term)),
+            }
+        }
+        terms
+    }
+
+    fn matches(&self, texts: &[&Vec<String>]) -> bool {
+        let contains = |term: &String| texts.iter().any(|words| words.contains(term));
+        !self.required.is_empty()
+            && self.required.iter().all(contains)
+            && !self.excluded.iter().any(contains)
+    }
+
+    fn rank(&self, words: &[String], weight: f32) -> f32 {
+        let hits = words
+            .iter()
+            .filter(|word| self.required.contains(word))
+            .count();
+        hits as f32 * weight
+    }
+
+    /// Wraps the required words in `<b>` tags, like `ts_headline`.
+    fn highlight(&self, text: &str) -> String {
+        let mut highlighted = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(char::is_alphanumeric) {
+            highlighted.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            if self.required.contains(&word.to_lowercase()) {
+                highlighted.push_str(&format!("<b>{}</b>", word));
+            } else {
+                highlighted.push_str(word);
+            }
+            rest = &rest[end..];
+        }
+        highlighted.push_str(rest);
+        highlighted
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn matches_filter(row: &StoredQuestion, filter: &QuestionFilter, answer_count: usize) -> bool {
+    let tags = row.question.tags.as_deref().unwrap_or_default();
+    let tags_match = match (&filter.tags, filter.tag_match) {
+        (None, _) => true,
+        // A NULL tags column matches neither `@>` nor `&&`.
+        (Some(_), _) if row.question.tags.is_none() => false,
+        (Some(wanted), TagMatch::All) => wanted.iter().all(|tag| tags.contains(tag)),
+        (Some(wanted), TagMatch::Any) => wanted.iter().any(|tag| tags.contains(tag)),
+    };
+    tags_match
+        && filter
+            .author
+            .as_ref()
+            .is_none_or(|author| author == &row.account_id)
+        && filter
+            .created_after
+            .is_none_or(|after| row.created_on > after)
+        && filter
+            .created_before
+            .is_none_or(|before| row.created_on < before)
+        && filter
+            .has_answers
+            .is_none_or(|has_answers| has_answers == (answer_count > 0))
+}
+
+fn target_id(target: &VoteTarget) -> i32 {
+    match target {
+        VoteTarget::Question(id) => id.0,
+        VoteTarget::Answer(id) => id.0,
+    }
+}
+
+// Same as `COALESCE(SUM(value), 0)` over the votes of a target.
+fn score(votes: &BTreeMap<(i32, i32), i16>, target_id: i32) -> i64 {
+    votes
+        .range((target_id, i32::MIN)..=(target_id, i32::MAX))
+        .map(|(_, value)| i64::from(*value))
+        .sum()
+}
+
+// Same rows as the keyset queries of the Postgres store: up to
+// `limit + 1` rows past the cursor, the closest first.
+fn keyset_page<T>(rows: Vec<(Cursor, T)>, order: KeysetOrder, keyset: &Keyset) -> KeysetPage<T> {
+    let past = if keyset.backward {
+        std::cmp::Ordering::Less
+    } else {
+        std::cmp::Ordering::Greater
+    };
+    let mut rows: Vec<(Cursor, T)> = rows
+        .into_iter()
+        .filter(|(cursor, _)| {
+            keyset
+                .cursor
+                .as_ref()
+                .is_none_or(|position| order.compare(cursor, position) == past)
+        })
+        .collect();
+    if keyset.backward {
+        rows.reverse();
+    }
+    rows.truncate(keyset.limit as usize + 1);
+    KeysetPage::from_rows(rows, keyset)
+}
+
+fn is_comment_of(comment: &Comment, target: &CommentTarget) -> bool {
+    match target {
+        CommentTarget::Question(id) => comment.question_id.as_ref() == Some(id),
+        CommentTarget::Answer(id) => comment.answer_id.as_ref() == Some(id),
+    }
+}
+
+fn row_not_found() -> Error {
+    tracing::event!(tracing::Level::ERROR, "{:?}", sqlx::Error::RowNotFound);
+    Error::DatabaseQueryError(sqlx::Error::RowNotFound)
+}
+
+fn constraint_violation(code: &'static str, constraint: &'static str) -> Error {
+    let error = MemoryDatabaseError { code, constraint };
+    tracing::event!(tracing::Level::ERROR, "{:?}", error);
+    Error::DatabaseQueryError(sqlx::Error::Database(Box::new(error)))
+}
+
+/// Database error raised by the in-memory tables, carrying the SQLSTATE code
+/// that Postgres would have returned.
+#[derive(Debug)]
+struct MemoryDatabaseError {
+    code: &'static str,
+    constraint: &'static str,
+}
+
+impl std::fmt::Display for MemoryDatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for MemoryDatabaseError {}
+
+impl DatabaseError for MemoryDatabaseError {
+    fn message(&self) -> &str {
+        match self.code {
+            UNIQUE_VIOLATION => "duplicate key value violates unique constraint",
+            FOREIGN_KEY_VIOLATION => "violates foreign key constraint",
+            _ => "constraint violation",
+        }
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self.code))
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn constraint(&self) -> Option<&str> {
+        Some(self.constraint)
+    }
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::{
+        Account, AccountId, AnswerId, ApiKeyId, CommentTarget, DeletedAccountPosts, Error,
+        InMemoryStore, LoginAttempts, LoginKey, NaiveDateTime, NewAnswer, NewApiKey, NewComment,
+        NewQuestion, NewTag, OidcState, QaStore, Question, QuestionFilter, QuestionId,
+        QuestionOrder, ResetToken, Role, Search, TotpFactor, VoteDirection, VoteTarget,
+        DELETED_ACCOUNT,
+    };
+    use crate::types::scope::Scope;
+    use chrono::{Duration, Utc};
+
+    async fn add_tags(store: &InMemoryStore, names: &[&str]) {
+        for name in names {
+            let tag = NewTag {
+                name: name.to_string(),
+                description: None,
+            };
+            store.add_tag(tag).await.unwrap();
+        }
+    }
+
+    fn new_question(title: &str) -> NewQuestion {
+        NewQuestion {
+            title: title.to_string(),
+            content: "content".to_string(),
+            tags: None,
+        }
+    }
+
+    fn sqlstate(error: Error) -> String {
+        match error {
+            Error::DatabaseQueryError(sqlx::Error::Database(e)) => e.code().unwrap().to_string(),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn questions_are_paginated_in_insertion_order() {
+        let store = InMemoryStore::new();
+        for title in ["first", "second", "third"] {
+            store
+                .add_question(new_question(title), AccountId(1))
+                .await
+                .unwrap();
+        }
+        let questions = store
+            .get_questions(QuestionFilter::default(), QuestionOrder::Id, Some(1), 1)
+            .await
+            .unwrap();
+        assert_eq!(questions.len(), 1);
+        assert_eq!(questions[0].title, "second");
+        assert_eq!(
+            store
+                .get_questions(QuestionFilter::default(), QuestionOrder::Id, None, 0)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn updates_keep_the_owner_and_record_the_editor() {
+        let store = InMemoryStore::new();
+        let question = store
+            .add_question(new_question("title"), AccountId(1))
+            .await
+            .unwrap();
+        let update = Question {
+            id: question.id.clone(),
+            title: "updated".to_string(),
+            content: "updated".to_string(),
+            tags: None,
+            accepted_answer_id: None,
+            score: 0,
+        };
+        assert!(store
+            .update_question(update.clone(), 2, AccountId(1))
+            .await
+            .is_err());
+        // Ownership is checked by the handlers, which let moderators edit.
+        let updated = store
+            .update_question(update, 1, AccountId(2))
+            .await
+            .unwrap();
+        assert_eq!(updated.title, "updated");
+        assert_eq!(store.get_question(1).await.unwrap().title, "updated");
+        assert!(store.is_question_owner(1, &AccountId(1)).await.unwrap());
+        assert!(!store.is_question_owner(1, &AccountId(2)).await.unwrap());
+        let revision = store.get_revision(1, 1).await.unwrap().unwrap();
+        assert_eq!(revision.title, "title");
+        assert_eq!(revision.account_id, AccountId(2));
+    }
+
+    #[tokio::test]
+    async fn answers_need_an_existing_question() {
+        let store = InMemoryStore::new();
+        let answer = NewAnswer {
+            content: "answer".to_string(),
+            question_id: QuestionId(1),
+        };
+        let error = store.add_answer(answer.clone(), AccountId(1)).await;
+        assert!(matches!(error, Err(Error::NotFound(_))));
+
+        store
+            .add_question(new_question("title"), AccountId(1))
+            .await
+            .unwrap();
+        store.add_answer(answer, AccountId(2)).await.unwrap();
+        assert_eq!(
+            store
+                .get_answers_of_question(1, None, 0)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn answers_comments_and_revisions_are_paginated() {
+        let store = InMemoryStore::new();
+        store
+            .add_question(new_question("title"), AccountId(1))
+            .await
+            .unwrap();
+        let target = CommentTarget::Question(QuestionId(1));
+        for content in ["first", "second", "third"] {
+            let answer = NewAnswer {
+                content: content.to_string(),
+                question_id: QuestionId(1),
+            };
+            store.add_answer(answer, AccountId(2)).await.unwrap();
+            let comment = NewComment {
+                content: content.to_string(),
+            };
+            store
+                .add_comment(target.clone(), comment, AccountId(2))
+                .await
+                .unwrap();
+            let mut question = store.get_question(1).await.unwrap();
+            question.content = content.to_string();
+            store
+                .update_question(question, 1, AccountId(1))
+                .await
+                .unwrap();
+        }
+
+        let answers = store.get_answers_of_question(1, Some(1), 1).await.unwrap();
+        assert_eq!(answers[0].content, "second");
+        assert_eq!(store.count_answers_of_question(1).await.unwrap(), 3);
+        let comments = store.get_comments(target.clone(), None, 2).await.unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].content, "third");
+        assert_eq!(store.count_comments(&target).await.unwrap(), 3);
+        let revisions = store.get_revisions(1, Some(2), 0).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[1].content, "first");
+        assert_eq!(store.count_revisions(1).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn duplicated_account_email() {
+        let store = InMemoryStore::new();
+        let account = Account {
+            id: None,
+            email: "foo@bar.com".to_string(),
+            password: "pw".to_string(),
+            role: Role::User,
+            verified: false,
+            display_name: None,
+            bio: None,
+        };
+        store.add_account(account.clone()).await.unwrap();
+        let error = store.add_account(account).await.unwrap_err();
+        assert_eq!(sqlstate(error), "23505");
+        let stored = store.get_account("foo@bar.com".to_string()).await.unwrap();
+        assert_eq!(stored.id, Some(AccountId(1)));
+    }
+
+    #[tokio::test]
+    async fn one_vote_per_account_and_target() {
+        let store = InMemoryStore::new();
+        for title in ["first", "second"] {
+            store
+                .add_question(new_question(title), AccountId(1))
+                .await
+                .unwrap();
+        }
+        let second = VoteTarget::Question(QuestionId(2));
+        for account in [2, 3] {
+            let score = store
+                .vote(second.clone(), AccountId(account), VoteDirection::Up)
+                .await
+                .unwrap();
+            assert_eq!(score, i64::from(account) - 1);
+        }
+        // A new vote of the same account replaces the previous one.
+        let score = store
+            .vote(second.clone(), AccountId(3), VoteDirection::Down)
+            .await
+            .unwrap();
+        assert_eq!(score, 0);
+        store
+            .vote(second.clone(), AccountId(3), VoteDirection::Up)
+            .await
+            .unwrap();
+
+        let questions = store
+            .get_questions(QuestionFilter::default(), QuestionOrder::Score, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(questions[0].title, "second");
+        assert_eq!(questions[0].score, 2);
+        assert_eq!(store.get_question(1).await.unwrap().score, 0);
+
+        assert_eq!(store.delete_vote(second, AccountId(2)).await.unwrap(), 1);
+        let error = store
+            .vote(
+                VoteTarget::Question(QuestionId(3)),
+                AccountId(2),
+                VoteDirection::Up,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Not found: question 3");
+    }
+
+    #[tokio::test]
+    async fn search_ranks_titles_first() {
+        let store = InMemoryStore::new();
+        add_tags(&store, &["rust", "go"]).await;
+        let questions = [
+            ("Lifetimes", "How do closures borrow?", vec!["rust"]),
+            ("Closures in Rust", "Help!", vec!["rust"]),
+            ("Closures in Go", "Help!", vec!["go"]),
+        ];
+        for (title, content, tags) in questions {
+            let question = NewQuestion {
+                title: title.to_string(),
+                content: content.to_string(),
+                tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            };
+            store.add_question(question, AccountId(1)).await.unwrap();
+        }
+        let answer = NewAnswer {
+            content: "Closures capture their environment.".to_string(),
+            question_id: QuestionId(1),
+        };
+        store.add_answer(answer, AccountId(2)).await.unwrap();
+
+        let search = Search {
+            text: "closures -lifetimes".to_string(),
+            tags: Some(vec!["rust".to_string()]),
+            ..Search::default()
+        };
+        let results = store.search(search).await.unwrap();
+        // The answer doesn't mention lifetimes, so it still matches.
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title, "<b>Closures</b> in Rust");
+        assert_eq!(results[1].answer_id, Some(AnswerId(1)));
+
+        let search = Search {
+            text: "closures".to_string(),
+            tags: Some(vec!["rust".to_string()]),
+            ..Search::default()
+        };
+        let results = store.search(search).await.unwrap();
+        let ids: Vec<(i32, Option<i32>)> = results
+            .iter()
+            .map(|result| {
+                (
+                    result.question_id.0,
+                    result.answer_id.as_ref().map(|id| id.0),
+                )
+            })
+            .collect();
+        assert_eq!(ids, [(2, None), (1, None), (1, Some(1))]);
+        assert_eq!(results[1].snippet, "How do <b>closures</b> borrow?");
+    }
+
+    #[tokio::test]
+    async fn merged_tags_become_synonyms() {
+        let store = InMemoryStore::new();
+        add_tags(&store, &["rust", "rustlang"]).await;
+        store
+            .add_tag_synonym("rustlang".to_string(), "rs".to_string())
+            .await
+            .unwrap();
+        for tags in [vec!["rs", "rust"], vec!["rustlang", "unknown"]] {
+            let question = NewQuestion {
+                tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+                ..new_question("Tagged")
+            };
+            store.add_question(question, AccountId(1)).await.unwrap();
+        }
+        let question = store.get_question(1).await.unwrap();
+        assert_eq!(question.tags.unwrap(), ["rustlang", "rust"]);
+
+        let tag = store
+            .merge_tags("rustlang".to_string(), "rust".to_string())
+            .await
+            .unwrap();
+        assert_eq!(tag.question_count, 2);
+        assert_eq!(tag.synonyms, ["rs", "rustlang"]);
+        for id in [1, 2] {
+            let question = store.get_question(id).await.unwrap();
+            assert_eq!(question.tags.unwrap(), ["rust"]);
+        }
+        let tag = store.get_tag("rs".to_string()).await.unwrap().unwrap();
+        assert_eq!(tag.name, "rust");
+        assert_eq!(store.count_tags().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn reset_tokens_are_used_once_before_they_expire() {
+        let store = InMemoryStore::new();
+        let now = Utc::now().naive_utc();
+        for (hash, expires_at) in [("valid", now + Duration::hours(1)), ("expired", now)] {
+            let token = ResetToken {
+                token_hash: hash.to_string(),
+                account_id: AccountId(1),
+                expires_at,
+            };
+            store.add_reset_token(token).await.unwrap();
+        }
+        let use_token = |hash: &str| store.use_reset_token(hash.to_string(), now);
+        assert_eq!(use_token("expired").await.unwrap(), None);
+        assert_eq!(use_token("valid").await.unwrap(), Some(AccountId(1)));
+        assert_eq!(use_token("valid").await.unwrap(), None);
+        assert_eq!(use_token("unknown").await.unwrap(), None);
+        assert_eq!(
+            store
+                .purge_expired_tokens(now + Duration::hours(2))
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn login_failures_are_counted_within_the_window() {
+        let store = InMemoryStore::new();
+        let now = Utc::now().naive_utc();
+        let key = LoginKey::Account(AccountId(1));
+        let fail = |at: NaiveDateTime| store.add_login_failure(&key, at, at - Duration::hours(1));
+        fail(now).await.unwrap();
+        assert_eq!(fail(now).await.unwrap().failures, 2);
+        // The last failure is out of the window of this one.
+        let later = now + Duration::hours(2);
+        assert_eq!(fail(later).await.unwrap().failures, 1);
+        assert_eq!(
+            store.get_login_attempts(&key).await.unwrap(),
+            Some(LoginAttempts {
+                failures: 1,
+                last_failure_at: later,
+            })
+        );
+        assert_eq!(store.purge_login_attempts(later).await.unwrap(), 0);
+        assert!(store.clear_login_failures(&key).await.unwrap());
+        assert_eq!(store.get_login_attempts(&key).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn totp_codes_and_recovery_codes_are_used_once() {
+        let store = InMemoryStore::new();
+        let account_id = AccountId(1);
+        let mut factor = TotpFactor {
+            account_id: account_id.clone(),
+            secret: "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string(),
+            enabled: false,
+            last_used_step: None,
+            recovery_code_hashes: vec!["first".to_string(), "second".to_string()],
+        };
+        store.set_totp_factor(factor.clone()).await.unwrap();
+        // Nothing is used before the factor is confirmed.
+        assert!(!store.use_totp_step(account_id.clone(), 10).await.unwrap());
+        factor.enabled = true;
+        store.set_totp_factor(factor.clone()).await.unwrap();
+        assert!(store.use_totp_step(account_id.clone(), 10).await.unwrap());
+        assert!(!store.use_totp_step(account_id.clone(), 10).await.unwrap());
+        assert!(!store.use_totp_step(account_id.clone(), 9).await.unwrap());
+        assert!(store.use_totp_step(account_id.clone(), 11).await.unwrap());
+        let use_code = |hash: &str| store.use_recovery_code(account_id.clone(), hash.to_string());
+        assert!(use_code("first").await.unwrap());
+        assert!(!use_code("first").await.unwrap());
+        assert!(!use_code("unknown").await.unwrap());
+        assert_eq!(
+            store.get_totp_factor(account_id.clone()).await.unwrap(),
+            Some(TotpFactor {
+                last_used_step: Some(11),
+                recovery_code_hashes: vec!["second".to_string()],
+                ..factor
+            })
+        );
+        assert!(store.delete_totp_factor(account_id.clone()).await.unwrap());
+        assert_eq!(store.get_totp_factor(account_id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn api_keys_record_their_last_use_and_are_revoked_by_their_owner() {
+        let store = InMemoryStore::new();
+        for (account_id, hash) in [(1, "first"), (1, "second"), (2, "other")] {
+            let new_key = NewApiKey {
+                name: hash.to_string(),
+                scopes: vec![Scope::AnswersWrite],
+            };
+            store
+                .add_api_key(AccountId(account_id), new_key, hash.to_string())
+                .await
+                .unwrap();
+        }
+        let now = Utc::now().naive_utc();
+        let api_key = store
+            .use_api_key("second".to_string(), now)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(api_key.id, ApiKeyId(2));
+        assert_eq!(api_key.last_used_at, Some(now));
+        let api_keys = store.get_api_keys(AccountId(1)).await.unwrap();
+        assert_eq!(api_keys.len(), 2);
+        assert_eq!(api_keys[1], api_key);
+        // The keys of other accounts are left alone.
+        assert!(!store
+            .delete_api_key(AccountId(1), ApiKeyId(3))
+            .await
+            .unwrap());
+        assert!(store
+            .delete_api_key(AccountId(1), ApiKeyId(2))
+            .await
+            .unwrap());
+        let used = store.use_api_key("second".to_string(), now).await.unwrap();
+        assert_eq!(used, None);
+    }
+
+    #[tokio::test]
+    async fn oidc_states_are_taken_once_before_they_expire() {
+        let store = InMemoryStore::new();
+        let now = Utc::now().naive_utc();
+        for (hash, minutes) in [("current", 10), ("expired", -1)] {
+            let state = OidcState {
+                state_hash: hash.to_string(),
+                provider: "corp".to_string(),
+                code_verifier: "verifier".to_string(),
+                nonce: "nonce".to_string(),
+                expires_at: now + Duration::minutes(minutes),
+            };
+            store.add_oidc_state(state).await.unwrap();
+        }
+        let take = |hash: &str| store.take_oidc_state(hash.to_string(), now);
+        assert_eq!(take("current").await.unwrap().unwrap().provider, "corp");
+        assert_eq!(take("current").await.unwrap(), None);
+        assert_eq!(take("expired").await.unwrap(), None);
+
+        let identity = || store.get_account_identity("issuer".to_string(), "sub".to_string());
+        store
+            .add_account_identity("issuer".to_string(), "sub".to_string(), AccountId(1))
+            .await
+            .unwrap();
+        assert_eq!(identity().await.unwrap(), Some(AccountId(1)));
+        assert!(store
+            .add_account_identity("issuer".to_string(), "sub".to_string(), AccountId(2))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn deleted_accounts_leave_or_take_their_posts() {
+        for posts in [DeletedAccountPosts::Anonymize, DeletedAccountPosts::Remove] {
+            let store = InMemoryStore::new();
+            for email in ["leaving@bar.com", "staying@bar.com"] {
+                let account = Account {
+                    id: None,
+                    email: email.to_string(),
+                    password: "pw".to_string(),
+                    role: Role::User,
+                    verified: true,
+                    display_name: None,
+                    bio: None,
+                };
+                store.add_account(account).await.unwrap();
+            }
+            for title in ["Leaving", "Staying"] {
+                let account_id = AccountId(if title == "Leaving" { 1 } else { 2 });
+                store
+                    .add_question(new_question(title), account_id)
+                    .await
+                    .unwrap();
+            }
+            // Each answers the question of the other.
+            for (question_id, account_id) in [(2, 1), (1, 2)] {
+                let answer = NewAnswer {
+                    content: "answer".to_string(),
+                    question_id: QuestionId(question_id),
+                };
+                store
+                    .add_answer(answer, AccountId(account_id))
+                    .await
+                    .unwrap();
+            }
+            store
+                .vote(
+                    VoteTarget::Question(QuestionId(2)),
+                    AccountId(1),
+                    VoteDirection::Up,
+                )
+                .await
+                .unwrap();
+
+            assert!(store.delete_account(AccountId(1), posts).await.unwrap());
+            assert!(!store.delete_account(AccountId(1), posts).await.unwrap());
+            assert_eq!(store.get_profile(AccountId(1)).await.unwrap(), None);
+            assert_eq!(store.get_question(2).await.unwrap().score, 0);
+            let profile = store.get_profile(AccountId(2)).await.unwrap().unwrap();
+            match posts {
+                DeletedAccountPosts::Anonymize => {
+                    assert!(store.is_question_owner(1, &DELETED_ACCOUNT).await.unwrap());
+                    assert!(store.is_answer_owner(1, &DELETED_ACCOUNT).await.unwrap());
+                    assert_eq!((profile.question_count, profile.answer_count), (1, 1));
+                }
+                DeletedAccountPosts::Remove => {
+                    assert!(store.get_question(1).await.is_err());
+                    assert_eq!(
+                        store
+                            .get_answers_of_question(2, None, 0)
+                            .await
+                            .unwrap()
+                            .len(),
+                        0
+                    );
+                    // The answer to the removed question goes with it.
+                    assert_eq!((profile.question_count, profile.answer_count), (1, 0));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use futures::stream::BoxStream;
+
+use crate::types::{
+    account::{Account, AccountId, DeletedAccountPosts, Profile, ProfileUpdate, Role},
+    answer::{Answer, NewAnswer},
+    api_key::{ApiKey, ApiKeyId, NewApiKey},
+    comment::{Comment, CommentTarget, NewComment},
+    export::{AnswerRecord, QuestionRecord},
+    filter::QuestionFilter,
+    import::{ImportOutcome, ImportQuestion, OnDuplicate},
+    login::{LoginAttempts, LoginKey},
+    oidc::OidcState,
+    pagination::{Keyset, KeysetPage},
+    question::{NewQuestion, Question, QuestionOrder},
+    revision::Revision,
+    search::{Search, SearchResult},
+    tag::{NewTag, Tag, TagUpdate},
+    token::{RefreshToken, ResetToken, VerificationToken},
+    totp::TotpFactor,
+    vote::{VoteDirection, VoteTarget},
+};
+use handle_errors::Error;
+
+pub mod memory;
+pub mod postgres;
+
+pub use memory::InMemoryStore;
+pub use postgres::PostgresStore;
+
+/// Error of a question, answer or comment that doesn't exist, or whose
+/// question is deleted, e.g. `not_found("answer", 3)`.
+pub(crate) fn not_found(what: &str, id: i32) -> Error {
+    Error::NotFound(format!("{} {}", what, id))
+}
+
+/// Error of accepting an answer of another question.
+pub(crate) fn not_an_answer_of(answer_id: i32, question_id: i32) -> Error {
+    Error::InvalidParameter(format!(
+        "answer {} is not an answer of question {}",
+        answer_id, question_id
+    ))
+}
+
+/// Storage operations used by the route handlers.
+///
+/// Every backend has to behave like the Postgres one: missing questions,
+/// answers and comments, or those of deleted questions, are reported as
+/// `Error::NotFound`, other missing rows as `sqlx::Error::RowNotFound` and
+/// constraint violations as `sqlx::Error::Database` with the matching
+/// SQLSTATE code, so `handle_errors::return_error` answers in the same way
+/// for all of them.
+// `Clone + Send + Sync + 'static` is required by the Warp filter that
+// hands a copy of the store to each request.
+#[async_trait]
+pub trait QaStore: Clone + Send + Sync + 'static {
+    async fn get_answers(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Answer>, Error>;
+
+    async fn count_answers(&self) -> Result<i64, Error>;
+
+    /// Answers sorted by id, paginated with cursors.
+    async fn get_answers_page(&self, keyset: Keyset) -> Result<KeysetPage<Answer>, Error>;
+
+    /// The accepted answer comes first, followed by the others in insertion order.
+    async fn get_answers_of_question(
+        &self,
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Answer>, Error>;
+
+    async fn count_answers_of_question(&self, question_id: i32) -> Result<i64, Error>;
+
+    /// Answers of the question in the `ACCEPTED_FIRST` order, paginated with
+    /// cursors.
+    async fn get_answers_of_question_page(
+        &self,
+        question_id: i32,
+        keyset: Keyset,
+    ) -> Result<KeysetPage<Answer>, Error>;
+
+    async fn get_question(&self, question_id: i32) -> Result<Question, Error>;
+
+    async fn get_questions(
+        &self,
+        filter: QuestionFilter,
+        order: QuestionOrder,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Question>, Error>;
+
+    /// Number of questions `get_questions` returns without pagination.
+    async fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, Error>;
+
+    /// Same questions as `get_questions`, paginated with cursors.
+    async fn get_questions_page(
+        &self,
+        filter: QuestionFilter,
+        order: QuestionOrder,
+        keyset: Keyset,
+    ) -> Result<KeysetPage<Question>, Error>;
+
+    /// Synonyms in the tags are replaced by their tag, names missing from
+    /// the catalogue are left out. The same goes for `update_question`.
+    async fn add_question(
+        &self,
+        new_question: NewQuestion,
+        account_id: AccountId,
+    ) -> Result<Question, Error>;
+
+    /// Questions and answers matching the search, best matches first.
+    /// Matches in titles rank above matches in question contents, which
+    /// rank above matches in answers.
+    async fn search(&self, search: Search) -> Result<Vec<SearchResult>, Error>;
+
+    /// Number of results of the search, ignoring its pagination.
+    async fn count_search_results(&self, search: &Search) -> Result<i64, Error>;
+
+    /// Writes the previous title, content and tags as a new revision,
+    /// made by `account_id`, before updating the question.
+    ///
+    /// Whether the account may change the question, or any other post, is
+    /// checked by the handlers: moderators can change posts of others.
+    async fn update_question(
+        &self,
+        question: Question,
+        question_id: i32,
+        account_id: AccountId,
+    ) -> Result<Question, Error>;
+
+    /// Revisions of the question, the oldest first.
+    async fn get_revisions(
+        &self,
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Revision>, Error>;
+
+    async fn count_revisions(&self, question_id: i32) -> Result<i64, Error>;
+
+    async fn get_revision(&self, question_id: i32, number: i32) -> Result<Option<Revision>, Error>;
+
+    /// Marks the question as deleted. Deleted questions, with their answers
+    /// and comments, are left out of every read until they are restored or
+    /// purged. Answers, votes and comments added to them aren't found, and
+    /// neither are the questions deleted again.
+    async fn delete_question(&self, question_id: i32) -> Result<bool, Error>;
+
+    /// Brings back a deleted question, whoever owns it.
+    async fn restore_question(&self, question_id: i32) -> Result<Question, Error>;
+
+    /// Removes the questions deleted before `deleted_before`, with their
+    /// answers, and returns how many there were.
+    async fn purge_questions(&self, deleted_before: NaiveDateTime) -> Result<u64, Error>;
+
+    /// Marks the answer as the accepted one. Only questions owned by
+    /// `account_id` are matched, and answers of other questions are an
+    /// `Error::InvalidParameter`.
+    async fn accept_answer(
+        &self,
+        question_id: i32,
+        answer_id: i32,
+        account_id: AccountId,
+    ) -> Result<Question, Error>;
+
+    async fn add_answer(&self, new_answer: NewAnswer, account_id: AccountId)
+        -> Result<bool, Error>;
+
+    async fn update_answer(&self, answer: Answer, answer_id: i32) -> Result<Answer, Error>;
+
+    async fn delete_answer(&self, answer_id: i32) -> Result<bool, Error>;
+
+    /// Casts the vote of `account_id`, replacing its previous vote on the
+    /// same target, and returns the new score of the target.
+    async fn vote(
+        &self,
+        target: VoteTarget,
+        account_id: AccountId,
+        direction: VoteDirection,
+    ) -> Result<i64, Error>;
+
+    /// Withdraws the vote of `account_id`, if any, and returns the new score
+    /// of the target. Like for `vote`, missing targets aren't found.
+    async fn delete_vote(&self, target: VoteTarget, account_id: AccountId) -> Result<i64, Error>;
+
+    /// Comments of the post, oldest first.
+    async fn get_comments(
+        &self,
+        target: CommentTarget,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Comment>, Error>;
+
+    async fn count_comments(&self, target: &CommentTarget) -> Result<i64, Error>;
+
+    async fn add_comment(
+        &self,
+        target: CommentTarget,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error>;
+
+    /// Only a comment of `target` owned by `account_id` is matched, the
+    /// same goes for `delete_comment`.
+    async fn update_comment(
+        &self,
+        target: CommentTarget,
+        comment_id: i32,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error>;
+
+    async fn delete_comment(
+        &self,
+        target: CommentTarget,
+        comment_id: i32,
+        account_id: AccountId,
+    ) -> Result<bool, Error>;
+
+    /// Tags of the catalogue, the most used first, then by name.
+    async fn get_tags(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Tag>, Error>;
+
+    async fn count_tags(&self) -> Result<i64, Error>;
+
+    /// The tag named `name`, or having `name` as a synonym.
+    async fn get_tag(&self, name: String) -> Result<Option<Tag>, Error>;
+
+    async fn add_tag(&self, new_tag: NewTag) -> Result<Tag, Error>;
+
+    async fn update_tag(&self, name: String, update: TagUpdate) -> Result<Tag, Error>;
+
+    async fn add_tag_synonym(&self, name: String, synonym: String) -> Result<Tag, Error>;
+
+    /// Moves the questions of the tag `name` to the tag `into`, then
+    /// deletes `name`, which becomes a synonym of `into` along with its
+    /// own synonyms.
+    async fn merge_tags(&self, name: String, into: String) -> Result<Tag, Error>;
+
+    async fn add_account(&self, account: Account) -> Result<bool, Error>;
+
+    async fn get_account(&self, email: String) -> Result<Account, Error>;
+
+    async fn get_account_by_id(&self, account_id: AccountId) -> Result<Account, Error>;
+
+    /// Returns false when there is no such account.
+    async fn set_account_role(&self, account_id: AccountId, role: Role) -> Result<bool, Error>;
+
+    /// Replaces the password hash and removes the refresh tokens of the
+    /// account, so that every login has to sign in again. Returns false when
+    /// there is no such account.
+    async fn set_account_password(
+        &self,
+        account_id: AccountId,
+        password: String,
+    ) -> Result<bool, Error>;
+
+    /// Profile of the account, or `None` when there is no such account.
+    async fn get_profile(&self, account_id: AccountId) -> Result<Option<Profile>, Error>;
+
+    /// Profiles sorted by account id.
+    async fn get_profiles(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Profile>, Error>;
+
+    async fn count_accounts(&self) -> Result<i64, Error>;
+
+    /// Fields of the update left out are kept and empty ones are cleared.
+    /// Returns false when there is no such account.
+    async fn update_profile(
+        &self,
+        account_id: AccountId,
+        update: ProfileUpdate,
+    ) -> Result<bool, Error>;
+
+    /// Removes the account with its votes, tokens, API keys, login attempts,
+    /// two-factor secret and identities. Its questions, answers and comments are removed or
+    /// handed to `DELETED_ACCOUNT`, and so are its revisions in both cases.
+    /// Returns false when there is no such account.
+    async fn delete_account(
+        &self,
+        account_id: AccountId,
+        posts: DeletedAccountPosts,
+    ) -> Result<bool, Error>;
+
+    async fn add_refresh_token(&self, token: RefreshToken) -> Result<bool, Error>;
+
+    async fn get_refresh_token(&self, token_hash: String) -> Result<Option<RefreshToken>, Error>;
+
+    /// Marks the token as used and adds the next token of the login.
+    /// Returns false, adding nothing, when the token was already used.
+    async fn rotate_refresh_token(
+        &self,
+        token_hash: String,
+        next: RefreshToken,
+    ) -> Result<bool, Error>;
+
+    /// Removes all the refresh tokens of the login.
+    async fn revoke_refresh_tokens(&self, sid: String) -> Result<bool, Error>;
+
+    /// Adds the access token to the revoked ones until it expires.
+    async fn revoke_token(&self, jti: String, expires_at: NaiveDateTime) -> Result<bool, Error>;
+
+    async fn is_token_revoked(&self, jti: &str) -> Result<bool, Error>;
+
+    async fn add_reset_token(&self, token: ResetToken) -> Result<bool, Error>;
+
+    /// Marks the reset token as used and returns its account, or `None` when
+    /// the token is unknown, already used or expired at `now`.
+    async fn use_reset_token(
+        &self,
+        token_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<AccountId>, Error>;
+
+    async fn add_verification_token(&self, token: VerificationToken) -> Result<bool, Error>;
+
+    /// Marks the verification token as used and its account as verified.
+    /// Returns the account, or `None` when the token is unknown, already
+    /// used or expired at `now`.
+    async fn verify_account(
+        &self,
+        token_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<AccountId>, Error>;
+
+    /// Removes the refresh tokens, revoked access tokens, reset tokens,
+    /// verification tokens and OpenID Connect states that expired before
+    /// `now`, and returns how many there were.
+    async fn purge_expired_tokens(&self, now: NaiveDateTime) -> Result<u64, Error>;
+
+    /// Counts a failed login of the key and returns its attempts. The count
+    /// starts again when the last failure was before `reset_before`.
+    async fn add_login_failure(
+        &self,
+        key: &LoginKey,
+        now: NaiveDateTime,
+        reset_before: NaiveDateTime,
+    ) -> Result<LoginAttempts, Error>;
+
+    async fn get_login_attempts(&self, key: &LoginKey) -> Result<Option<LoginAttempts>, Error>;
+
+    /// Forgets the failed logins of the key. Returns false when there were
+    /// none.
+    async fn clear_login_failures(&self, key: &LoginKey) -> Result<bool, Error>;
+
+    /// Removes the attempts whose last failure was before `before`, and
+    /// returns how many there were.
+    async fn purge_login_attempts(&self, before: NaiveDateTime) -> Result<u64, Error>;
+
+    /// Adds the two-factor secret of the account, or replaces it.
+    async fn set_totp_factor(&self, factor: TotpFactor) -> Result<bool, Error>;
+
+    async fn get_totp_factor(&self, account_id: AccountId) -> Result<Option<TotpFactor>, Error>;
+
+    /// Records the time step of a code of the enabled factor, so that each
+    /// code is used once. Returns false when a code of this step, or of a
+    /// later one, was already used.
+    async fn use_totp_step(&self, account_id: AccountId, step: i64) -> Result<bool, Error>;
+
+    /// Removes the recovery code from the ones of the enabled factor.
+    /// Returns false when it isn't one of them.
+    async fn use_recovery_code(
+        &self,
+        account_id: AccountId,
+        code_hash: String,
+    ) -> Result<bool, Error>;
+
+    /// Returns false when the account has no factor.
+    async fn delete_totp_factor(&self, account_id: AccountId) -> Result<bool, Error>;
+
+    /// Adds an API key of the account, by hash, and returns it.
+    async fn add_api_key(
+        &self,
+        account_id: AccountId,
+        new_key: NewApiKey,
+        key_hash: String,
+    ) -> Result<ApiKey, Error>;
+
+    /// API keys of the account, oldest first.
+    async fn get_api_keys(&self, account_id: AccountId) -> Result<Vec<ApiKey>, Error>;
+
+    /// Records that the key was used at `now` and returns it, or `None` when
+    /// the key is unknown or revoked.
+    async fn use_api_key(
+        &self,
+        key_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<ApiKey>, Error>;
+
+    /// Revokes the API key. Returns false when the account has no such key.
+    async fn delete_api_key(&self, account_id: AccountId, id: ApiKeyId) -> Result<bool, Error>;
+
+    async fn add_oidc_state(&self, state: OidcState) -> Result<bool, Error>;
+
+    /// Removes the state and returns it, or `None` when it's unknown or
+    /// expired at `now`.
+    async fn take_oidc_state(
+        &self,
+        state_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<OidcState>, Error>;
+
+    /// Account linked to the subject of the issuer, if any.
+    async fn get_account_identity(
+        &self,
+        issuer: String,
+        subject: String,
+    ) -> Result<Option<AccountId>, Error>;
+
+    /// Links the subject of the issuer to the account.
+    async fn add_account_identity(
+        &self,
+        issuer: String,
+        subject: String,
+        account_id: AccountId,
+    ) -> Result<bool, Error>;
+
+    /// Deleted questions still have their owner, who can restore them.
+    async fn is_question_owner(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error>;
+
+    /// Answers of deleted questions aren't found, like the missing ones. The
+    /// same goes for the comments in `is_comment_owner`.
+    async fn is_answer_owner(&self, answer_id: i32, account_id: &AccountId) -> Result<bool, Error>;
+
+    async fn is_comment_owner(
+        &self,
+        comment_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error>;
+
+    /// Imports the questions, with their answers, in a single transaction
+    /// owned by `account_id`. A failing question doesn't abort the others;
+    /// its error message is returned in the same position as the question.
+    /// Tags missing from the catalogue are added to it.
+    async fn import_questions(
+        &self,
+        questions: Vec<ImportQuestion>,
+        account_id: AccountId,
+        on_duplicate: OnDuplicate,
+    ) -> Result<Vec<Result<ImportOutcome, String>>, Error>;
+
+    /// Streams every account ordered by id, password hashes included.
+    fn stream_accounts(&self) -> BoxStream<'static, Result<Account, Error>>;
+
+    /// Streams every question ordered by id, deleted ones included.
+    fn stream_questions(&self) -> BoxStream<'static, Result<QuestionRecord, Error>>;
+
+    /// Streams every answer ordered by question id and then by id, those of
+    /// deleted questions included.
+    fn stream_answers(&self) -> BoxStream<'static, Result<AnswerRecord, Error>>;
+}
@@ -0,0 +1,2379 @@
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use futures::{stream::BoxStream, TryStreamExt};
+use sqlx::postgres::{PgConnection, PgPool, PgPoolOptions, PgRow};
+use sqlx::{Connection, Row};
+
+use crate::store::{not_an_answer_of, not_found, QaStore};
+use crate::types::{
+    account::{
+        Account, AccountId, DeletedAccountPosts, Profile, ProfileUpdate, Role, DELETED_ACCOUNT,
+    },
+    answer::{Answer, AnswerId, NewAnswer, ACCEPTED_FIRST},
+    api_key::{ApiKey, ApiKeyId, NewApiKey},
+    comment::{Comment, CommentId, CommentTarget, NewComment},
+    export::{AnswerRecord, QuestionRecord},
+    filter::{QuestionFilter, TagMatch},
+    import::{ImportOutcome, ImportQuestion, OnDuplicate},
+    login::{LoginAttempts, LoginKey},
+    oidc::OidcState,
+    pagination::{Cursor, Keyset, KeysetOrder, KeysetPage, SortDirection},
+    question::{NewQuestion, Question, QuestionId, QuestionOrder},
+    revision::Revision,
+    search::{Search, SearchResult},
+    tag::{NewTag, Tag, TagUpdate},
+    token::{RefreshToken, ResetToken, VerificationToken},
+    totp::TotpFactor,
+    vote::{VoteDirection, VoteTarget},
+};
+use handle_errors::Error;
+
+// Scores are computed from the votes when the rows are read.
+const QUESTION_SCORE: &str =
+    "COALESCE((SELECT SUM(value) FROM votes WHERE votes.question_id = questions.id), 0) AS score";
+const ANSWER_SCORE: &str =
+    "COALESCE((SELECT SUM(value) FROM votes WHERE votes.answer_id = answers.id), 0) AS score";
+// Answers of the questions that aren't deleted.
+const LIVE_ANSWERS: &str =
+    "answers JOIN questions ON questions.id = answers.question_id WHERE questions.deleted_at IS NULL";
+// Whether the question of the answer isn't deleted.
+const LIVE_ANSWER: &str = "EXISTS (SELECT 1 FROM questions
+    WHERE questions.id = answers.question_id AND questions.deleted_at IS NULL)";
+// Comments on the questions that aren't deleted, and on their answers.
+const LIVE_COMMENT: &str = "EXISTS (SELECT 1 FROM questions WHERE questions.deleted_at IS NULL
+    AND questions.id = COALESCE(comments.question_id,
+        (SELECT answers.question_id FROM answers WHERE answers.id = comments.answer_id)))";
+// Full-text query of the search text bound to $1.
+const SEARCH_QUERY: &str = "WITH search AS (SELECT websearch_to_tsquery('english', $1) AS query)";
+// Questions, and answers, matching the search in questions having all the
+// tags bound to $2.
+const QUESTION_MATCHES: &str =
+    "questions.deleted_at IS NULL AND questions.search_vector @@ search.query AND ($2::text[] IS NULL OR tag_names(questions.id) @> $2)";
+const ANSWER_MATCHES: &str =
+    "questions.deleted_at IS NULL AND answers.search_vector @@ search.query AND ($2::text[] IS NULL OR tag_names(questions.id) @> $2)";
+// Columns of `Profile`, read from the accounts table. Deleted questions,
+// and their answers, aren't counted.
+const PROFILE_COLUMNS: &str = "accounts.id, accounts.display_name, accounts.bio, accounts.role,
+    (SELECT COUNT(*) FROM questions
+        WHERE questions.account_id = accounts.id AND questions.deleted_at IS NULL) AS question_count,
+    (SELECT COUNT(*) FROM answers JOIN questions ON questions.id = answers.question_id
+        WHERE answers.account_id = accounts.id AND questions.deleted_at IS NULL) AS answer_count";
+// Columns of `ApiKey`, read from the api_keys table.
+const API_KEY_COLUMNS: &str = "id, account_id, name, scopes, created_on, last_used_at";
+// Columns of `Tag`, read from the tags table. Deleted questions aren't
+// counted.
+const TAG_COLUMNS: &str = "tags.name, tags.description,
+    (SELECT COUNT(*) FROM question_tags JOIN questions ON questions.id = question_tags.question_id
+        WHERE question_tags.tag_id = tags.id AND questions.deleted_at IS NULL) AS question_count,
+    ARRAY(SELECT tag_synonyms.name FROM tag_synonyms
+        WHERE tag_synonyms.tag_id = tags.id ORDER BY tag_synonyms.name) AS synonyms";
+
+#[derive(Debug, Clone)]
+pub struct PostgresStore {
+    pub connection: PgPool,
+}
+
+impl PostgresStore {
+    pub async fn new(db_url: &str) -> Result<Self, sqlx::Error> {
+        let db_pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(db_url)
+            .await?;
+        Ok(PostgresStore {
+            connection: db_pool,
+        })
+    }
+}
+
+#[async_trait]
+impl QaStore for PostgresStore {
+    async fn get_answers(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Answer>, Error> {
+        match sqlx::query(&format!(
+            "SELECT answers.*, {} from {} ORDER BY answers.id LIMIT $1 OFFSET $2",
+            ANSWER_SCORE, LIVE_ANSWERS
+        ))
+        .bind(limit)
+        .bind(offset)
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(answers) => Ok(answers),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn count_answers(&self) -> Result<i64, Error> {
+        match sqlx::query(&format!("SELECT COUNT(*) AS total FROM {}", LIVE_ANSWERS))
+            .map(|row: PgRow| row.get("total"))
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(total) => Ok(total),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_answers_page(&self, keyset: Keyset) -> Result<KeysetPage<Answer>, Error> {
+        let (condition, order_by) = keyset_clauses(KeysetOrder::BY_ID, keyset.backward);
+        match sqlx::query(&format!(
+            "SELECT * FROM (
+                SELECT answers.*, {}, NULL::bigint AS sort_key from {}
+            ) AS answers
+            WHERE {} ORDER BY {} LIMIT $3",
+            ANSWER_SCORE, LIVE_ANSWERS, condition, order_by
+        ))
+        .bind(keyset.cursor.as_ref().and_then(|cursor| cursor.key))
+        .bind(keyset.cursor.as_ref().map(|cursor| cursor.id))
+        .bind(i64::from(keyset.limit) + 1)
+        .map(|row: PgRow| {
+            (
+                Cursor {
+                    key: row.get("sort_key"),
+                    id: row.get("id"),
+                },
+                Answer {
+                    id: AnswerId(row.get("id")),
+                    content: row.get("content"),
+                    question_id: QuestionId(row.get("question_id")),
+                    score: row.get("score"),
+                },
+            )
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(rows) => Ok(KeysetPage::from_rows(rows, &keyset)),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_answers_of_question(
+        &self,
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Answer>, Error> {
+        match sqlx::query(&format!(
+            "SELECT answers.*, {} from answers
+            JOIN questions ON questions.id = answers.question_id
+            WHERE answers.question_id = $1 AND questions.deleted_at IS NULL
+            ORDER BY (answers.id = questions.accepted_answer_id) IS TRUE DESC, answers.id
+            LIMIT $2 OFFSET $3",
+            ANSWER_SCORE
+        ))
+        .bind(question_id)
+        .bind(limit)
+        .bind(offset)
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(answers) => Ok(answers),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn count_answers_of_question(&self, question_id: i32) -> Result<i64, Error> {
+        sqlx::query(&format!(
+            "SELECT COUNT(*) AS total FROM {} AND answers.question_id = $1",
+            LIVE_ANSWERS
+        ))
+        .bind(question_id)
+        .map(|row: PgRow| row.get("total"))
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn get_answers_of_question_page(
+        &self,
+        question_id: i32,
+        keyset: Keyset,
+    ) -> Result<KeysetPage<Answer>, Error> {
+        let (condition, order_by) = keyset_clauses(ACCEPTED_FIRST, keyset.backward);
+        match sqlx::query(&format!(
+            "SELECT * FROM (
+                SELECT answers.*, {},
+                ((answers.id = questions.accepted_answer_id) IS TRUE)::integer::bigint AS sort_key
+                from answers
+                JOIN questions ON questions.id = answers.question_id
+                WHERE answers.question_id = $3 AND questions.deleted_at IS NULL
+            ) AS answers
+            WHERE {} ORDER BY {} LIMIT $4",
+            ANSWER_SCORE, condition, order_by
+        ))
+        .bind(keyset.cursor.as_ref().and_then(|cursor| cursor.key))
+        .bind(keyset.cursor.as_ref().map(|cursor| cursor.id))
+        .bind(question_id)
+        .bind(i64::from(keyset.limit) + 1)
+        .map(|row: PgRow| {
+            (
+                Cursor {
+                    key: row.get("sort_key"),
+                    id: row.get("id"),
+                },
+                Answer {
+                    id: AnswerId(row.get("id")),
+                    content: row.get("content"),
+                    question_id: QuestionId(row.get("question_id")),
+                    score: row.get("score"),
+                },
+            )
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(rows) => Ok(KeysetPage::from_rows(rows, &keyset)),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_question(&self, question_id: i32) -> Result<Question, Error> {
+        sqlx::query(&format!(
+            "SELECT *, {}, tag_names(id) AS tags from questions
+            WHERE id = $1 AND deleted_at IS NULL",
+            QUESTION_SCORE
+        ))
+        .bind(question_id)
+        .map(|row: PgRow| Question {
+            id: QuestionId(row.get("id")),
+            title: row.get("title"),
+            content: row.get("content"),
+            tags: row.get("tags"),
+            accepted_answer_id: row
+                .get::<Option<i32>, _>("accepted_answer_id")
+                .map(AnswerId),
+            score: row.get("score"),
+        })
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_not_found("question", question_id))
+    }
+
+    async fn get_questions(
+        &self,
+        filter: QuestionFilter,
+        order: QuestionOrder,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Question>, Error> {
+        let (_, order_by) = keyset_clauses(order.keyset_order(), false);
+        match sqlx::query(&format!(
+            "SELECT * FROM (
+                SELECT *, {}, tag_names(id) AS tags, {} AS sort_key from questions WHERE {}
+            ) AS questions
+            ORDER BY {} LIMIT $1 OFFSET $2",
+            QUESTION_SCORE,
+            question_sort_key(order),
+            question_conditions(&filter, 3),
+            order_by
+        ))
+        .bind(limit)
+        .bind(offset)
+        .bind(filter.tags)
+        .bind(filter.author.map(|author| author.0))
+        .bind(filter.created_after)
+        .bind(filter.created_before)
+        .bind(filter.has_answers)
+        .map(|row: PgRow| Question {
+            id: QuestionId(row.get("id")),
+            title: row.get("title"),
+            content: row.get("content"),
+            tags: row.get("tags"),
+            accepted_answer_id: row
+                .get::<Option<i32>, _>("accepted_answer_id")
+                .map(AnswerId),
+            score: row.get("score"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(questions) => Ok(questions),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn count_questions(&self, filter: &QuestionFilter) -> Result<i64, Error> {
+        match sqlx::query(&format!(
+            "SELECT COUNT(*) AS total FROM questions WHERE {}",
+            question_conditions(filter, 1)
+        ))
+        .bind(filter.tags.as_ref())
+        .bind(filter.author.as_ref().map(|author| author.0))
+        .bind(filter.created_after)
+        .bind(filter.created_before)
+        .bind(filter.has_answers)
+        .map(|row: PgRow| row.get("total"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(total) => Ok(total),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_questions_page(
+        &self,
+        filter: QuestionFilter,
+        order: QuestionOrder,
+        keyset: Keyset,
+    ) -> Result<KeysetPage<Question>, Error> {
+        let (condition, order_by) = keyset_clauses(order.keyset_order(), keyset.backward);
+        match sqlx::query(&format!(
+            "SELECT * FROM (
+                SELECT *, {}, tag_names(id) AS tags, {} AS sort_key from questions WHERE {}
+            ) AS questions
+            WHERE {} ORDER BY {} LIMIT $8",
+            QUESTION_SCORE,
+            question_sort_key(order),
+            question_conditions(&filter, 3),
+            condition,
+            order_by
+        ))
+        .bind(keyset.cursor.as_ref().and_then(|cursor| cursor.key))
+        .bind(keyset.cursor.as_ref().map(|cursor| cursor.id))
+        .bind(filter.tags)
+        .bind(filter.author.map(|author| author.0))
+        .bind(filter.created_after)
+        .bind(filter.created_before)
+        .bind(filter.has_answers)
+        .bind(i64::from(keyset.limit) + 1)
+        .map(|row: PgRow| {
+            (
+                Cursor {
+                    key: row.get("sort_key"),
+                    id: row.get("id"),
+                },
+                Question {
+                    id: QuestionId(row.get("id")),
+                    title: row.get("title"),
+                    content: row.get("content"),
+                    tags: row.get("tags"),
+                    accepted_answer_id: row
+                        .get::<Option<i32>, _>("accepted_answer_id")
+                        .map(AnswerId),
+                    score: row.get("score"),
+                },
+            )
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(rows) => Ok(KeysetPage::from_rows(rows, &keyset)),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn add_question(
+        &self,
+        new_question: NewQuestion,
+        account_id: AccountId,
+    ) -> Result<Question, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let question_id = sqlx::query(
+            "INSERT INTO questions (title, content, account_id)
+           VALUES ($1, $2, $3)
+           RETURNING id",
+        )
+        .bind(new_question.title)
+        .bind(new_question.content)
+        .bind(account_id.0)
+        .map(|row: PgRow| row.get::<i32, _>("id"))
+        .fetch_one(&mut tx)
+        .await
+        .map_err(log_error)?;
+        set_question_tags(&mut tx, question_id, new_question.tags)
+            .await
+            .map_err(log_error)?;
+        let question = read_question(&mut tx, question_id)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(question)
+    }
+
+    async fn search(&self, search: Search) -> Result<Vec<SearchResult>, Error> {
+        // Question contents and answers are cut to a few words around the
+        // matches, titles are highlighted as a whole.
+        match sqlx::query(&format!(
+            "{}
+            SELECT * FROM (
+                SELECT questions.id AS question_id, NULL::integer AS answer_id,
+                ts_headline('english', questions.title, search.query, 'HighlightAll=true') AS title,
+                ts_headline('english', questions.content, search.query) AS snippet,
+                ts_rank(questions.search_vector, search.query) AS rank
+                FROM questions, search WHERE {}
+                UNION ALL
+                SELECT questions.id, answers.id,
+                ts_headline('english', questions.title, search.query, 'HighlightAll=true'),
+                ts_headline('english', answers.content, search.query),
+                ts_rank(answers.search_vector, search.query)
+                FROM answers JOIN questions ON questions.id = answers.question_id, search
+                WHERE {}
+            ) AS results
+            ORDER BY rank DESC, question_id, answer_id NULLS FIRST
+            LIMIT $3 OFFSET $4",
+            SEARCH_QUERY, QUESTION_MATCHES, ANSWER_MATCHES
+        ))
+        .bind(search.text)
+        .bind(search.tags)
+        .bind(search.pagination.limit)
+        .bind(search.pagination.offset)
+        .map(|row: PgRow| SearchResult {
+            question_id: QuestionId(row.get("question_id")),
+            answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+            title: row.get("title"),
+            snippet: row.get("snippet"),
+            rank: row.get("rank"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(results) => Ok(results),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn count_search_results(&self, search: &Search) -> Result<i64, Error> {
+        match sqlx::query(&format!(
+            "{}
+            SELECT (SELECT COUNT(*) FROM questions, search WHERE {})
+                + (SELECT COUNT(*)
+                    FROM answers JOIN questions ON questions.id = answers.question_id, search
+                    WHERE {}) AS total",
+            SEARCH_QUERY, QUESTION_MATCHES, ANSWER_MATCHES
+        ))
+        .bind(&search.text)
+        .bind(search.tags.as_ref())
+        .map(|row: PgRow| row.get("total"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(total) => Ok(total),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn update_question(
+        &self,
+        question: Question,
+        question_id: i32,
+        account_id: AccountId,
+    ) -> Result<Question, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        sqlx::query(
+            "INSERT INTO question_revisions
+                (question_id, number, title, content, tags, account_id)
+            SELECT id,
+                COALESCE((SELECT MAX(number) FROM question_revisions WHERE question_id = $1), 0) + 1,
+                title, content, tag_names(id), $2
+            FROM questions
+            WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await
+        .map_err(log_error)?;
+        sqlx::query(
+            "UPDATE questions
+            SET title = $1, content = $2
+            WHERE id = $3 AND deleted_at IS NULL
+            RETURNING id",
+        )
+        .bind(question.title)
+        .bind(question.content)
+        .bind(question_id)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(log_not_found("question", question_id))?;
+        set_question_tags(&mut tx, question_id, question.tags)
+            .await
+            .map_err(log_error)?;
+        let question = read_question(&mut tx, question_id)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(question)
+    }
+
+    async fn get_revisions(
+        &self,
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Revision>, Error> {
+        match sqlx::query(
+            "SELECT * FROM question_revisions WHERE question_id = $1
+            ORDER BY number LIMIT $2 OFFSET $3",
+        )
+        .bind(question_id)
+        .bind(limit)
+        .bind(offset)
+        .map(revision)
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(revisions) => Ok(revisions),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn count_revisions(&self, question_id: i32) -> Result<i64, Error> {
+        sqlx::query("SELECT COUNT(*) AS total FROM question_revisions WHERE question_id = $1")
+            .bind(question_id)
+            .map(|row: PgRow| row.get("total"))
+            .fetch_one(&self.connection)
+            .await
+            .map_err(log_error)
+    }
+
+    async fn get_revision(&self, question_id: i32, number: i32) -> Result<Option<Revision>, Error> {
+        match sqlx::query("SELECT * FROM question_revisions WHERE question_id = $1 AND number = $2")
+            .bind(question_id)
+            .bind(number)
+            .map(revision)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(revision) => Ok(revision),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn delete_question(&self, question_id: i32) -> Result<bool, Error> {
+        let deleted = sqlx::query(
+            "UPDATE questions
+            SET deleted_at = NOW()
+            WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .execute(&self.connection)
+        .await
+        .map_err(log_error)?
+        .rows_affected();
+        if deleted == 0 {
+            return Err(not_found("question", question_id));
+        }
+        Ok(true)
+    }
+
+    async fn restore_question(&self, question_id: i32) -> Result<Question, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        sqlx::query(
+            "UPDATE questions
+            SET deleted_at = NULL
+            WHERE id = $1 AND deleted_at IS NOT NULL
+            RETURNING id",
+        )
+        .bind(question_id)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(log_not_found("question", question_id))?;
+        let question = read_question(&mut tx, question_id)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(question)
+    }
+
+    async fn purge_questions(&self, deleted_before: NaiveDateTime) -> Result<u64, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        // Answers don't cascade with their question, unlike the other rows
+        // referencing it.
+        sqlx::query(
+            "DELETE FROM answers
+            WHERE question_id IN (SELECT id FROM questions WHERE deleted_at < $1)",
+        )
+        .bind(deleted_before)
+        .execute(&mut tx)
+        .await
+        .map_err(log_error)?;
+        let purged = sqlx::query("DELETE FROM questions WHERE deleted_at < $1")
+            .bind(deleted_before)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?
+            .rows_affected();
+        tx.commit().await.map_err(log_error)?;
+        Ok(purged)
+    }
+
+    async fn accept_answer(
+        &self,
+        question_id: i32,
+        answer_id: i32,
+        account_id: AccountId,
+    ) -> Result<Question, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let answer_of: Option<i32> = sqlx::query(&format!(
+            "SELECT question_id FROM answers WHERE id = $1 AND {}",
+            LIVE_ANSWER
+        ))
+        .bind(answer_id)
+        .map(|row: PgRow| row.get("question_id"))
+        .fetch_optional(&mut tx)
+        .await
+        .map_err(log_error)?;
+        match answer_of {
+            None => return Err(not_found("answer", answer_id)),
+            Some(id) if id != question_id => return Err(not_an_answer_of(answer_id, question_id)),
+            Some(_) => (),
+        }
+        let question = sqlx::query(&format!(
+            "UPDATE questions
+            SET accepted_answer_id = $1
+            WHERE id = $2 AND account_id = $3
+            RETURNING id, title, content, tag_names(id) AS tags, accepted_answer_id, {}",
+            QUESTION_SCORE
+        ))
+        .bind(answer_id)
+        .bind(question_id)
+        .bind(account_id.0)
+        .map(|row: PgRow| Question {
+            id: QuestionId(row.get("id")),
+            title: row.get("title"),
+            content: row.get("content"),
+            tags: row.get("tags"),
+            accepted_answer_id: row
+                .get::<Option<i32>, _>("accepted_answer_id")
+                .map(AnswerId),
+            score: row.get("score"),
+        })
+        .fetch_one(&mut tx)
+        .await
+        .map_err(log_not_found("question", question_id))?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(question)
+    }
+
+    async fn add_answer(
+        &self,
+        new_answer: NewAnswer,
+        account_id: AccountId,
+    ) -> Result<bool, Error> {
+        match sqlx::query(&format!(
+            "INSERT INTO answers (content, question_id, account_id)
+            SELECT $1, $2, $3 WHERE {}
+            RETURNING id, content, question_id, {}",
+            live_target("question_id", "$2"),
+            ANSWER_SCORE
+        ))
+        .bind(new_answer.content)
+        .bind(new_answer.question_id.0)
+        .bind(account_id.0)
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+        })
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(sqlx::Error::RowNotFound) => Err(not_found("question", new_answer.question_id.0)),
+            Err(error) => {
+                tracing::event!(
+                    tracing::Level::ERROR,
+                    code = error
+                        .as_database_error()
+                        .unwrap()
+                        .code()
+                        .unwrap()
+                        .parse::<i32>()
+                        .unwrap(),
+                    db_message = error.as_database_error().unwrap().message(),
+                    constraint = error.as_database_error().unwrap().constraint().unwrap()
+                );
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn update_answer(&self, answer: Answer, answer_id: i32) -> Result<Answer, Error> {
+        sqlx::query(&format!(
+            "UPDATE answers
+            SET content = $1
+            WHERE id = $2 AND {}
+            RETURNING id, content, question_id, {}",
+            LIVE_ANSWER, ANSWER_SCORE
+        ))
+        .bind(answer.content)
+        .bind(answer_id)
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+        })
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_not_found("answer", answer_id))
+    }
+
+    async fn delete_answer(&self, answer_id: i32) -> Result<bool, Error> {
+        let deleted = sqlx::query(&format!(
+            "DELETE FROM answers WHERE id = $1 AND {}",
+            LIVE_ANSWER
+        ))
+        .bind(answer_id)
+        .execute(&self.connection)
+        .await
+        .map_err(log_error)?
+        .rows_affected();
+        if deleted == 0 {
+            return Err(not_found("answer", answer_id));
+        }
+        Ok(true)
+    }
+
+    async fn vote(
+        &self,
+        target: VoteTarget,
+        account_id: AccountId,
+        direction: VoteDirection,
+    ) -> Result<i64, Error> {
+        let (column, target_id) = vote_column(&target);
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let voted = sqlx::query(&format!(
+            "INSERT INTO votes (account_id, {0}, value)
+            SELECT $1, $2, $3 WHERE {1}
+            ON CONFLICT (account_id, {0}) DO UPDATE SET value = EXCLUDED.value",
+            column,
+            live_target(column, "$2")
+        ))
+        .bind(account_id.0)
+        .bind(target_id)
+        .bind(direction.value())
+        .execute(&mut tx)
+        .await
+        .map_err(log_error)?
+        .rows_affected();
+        if voted == 0 {
+            return Err(not_found(target_name(column), target_id));
+        }
+        let score = vote_score(&mut tx, column, target_id)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(score)
+    }
+
+    async fn delete_vote(&self, target: VoteTarget, account_id: AccountId) -> Result<i64, Error> {
+        let (column, target_id) = vote_column(&target);
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let live: bool = sqlx::query(&format!("SELECT {} AS live", live_target(column, "$1")))
+            .bind(target_id)
+            .map(|row: PgRow| row.get("live"))
+            .fetch_one(&mut tx)
+            .await
+            .map_err(log_error)?;
+        if !live {
+            return Err(not_found(target_name(column), target_id));
+        }
+        sqlx::query(&format!(
+            "DELETE FROM votes WHERE account_id = $1 AND {} = $2",
+            column
+        ))
+        .bind(account_id.0)
+        .bind(target_id)
+        .execute(&mut tx)
+        .await
+        .map_err(log_error)?;
+        let score = vote_score(&mut tx, column, target_id)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(score)
+    }
+
+    async fn get_comments(
+        &self,
+        target: CommentTarget,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Comment>, Error> {
+        let (column, target_id) = comment_column(&target);
+        match sqlx::query(&format!(
+            "SELECT * FROM comments WHERE {} = $1 AND {} ORDER BY id LIMIT $2 OFFSET $3",
+            column, LIVE_COMMENT
+        ))
+        .bind(target_id)
+        .bind(limit)
+        .bind(offset)
+        .map(comment)
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(comments) => Ok(comments),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn count_comments(&self, target: &CommentTarget) -> Result<i64, Error> {
+        let (column, target_id) = comment_column(target);
+        sqlx::query(&format!(
+            "SELECT COUNT(*) AS total FROM comments WHERE {} = $1 AND {}",
+            column, LIVE_COMMENT
+        ))
+        .bind(target_id)
+        .map(|row: PgRow| row.get("total"))
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn add_comment(
+        &self,
+        target: CommentTarget,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error> {
+        let (column, target_id) = comment_column(&target);
+        sqlx::query(&format!(
+            "INSERT INTO comments (content, {}, account_id)
+            SELECT $1, $2, $3 WHERE {}
+            RETURNING *",
+            column,
+            live_target(column, "$2")
+        ))
+        .bind(new_comment.content)
+        .bind(target_id)
+        .bind(account_id.0)
+        .map(comment)
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_not_found(target_name(column), target_id))
+    }
+
+    async fn update_comment(
+        &self,
+        target: CommentTarget,
+        comment_id: i32,
+        new_comment: NewComment,
+        account_id: AccountId,
+    ) -> Result<Comment, Error> {
+        let (column, target_id) = comment_column(&target);
+        sqlx::query(&format!(
+            "UPDATE comments SET content = $1
+            WHERE id = $2 AND {} = $3 AND account_id = $4 AND {}
+            RETURNING *",
+            column, LIVE_COMMENT
+        ))
+        .bind(new_comment.content)
+        .bind(comment_id)
+        .bind(target_id)
+        .bind(account_id.0)
+        .map(comment)
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_not_found("comment", comment_id))
+    }
+
+    async fn delete_comment(
+        &self,
+        target: CommentTarget,
+        comment_id: i32,
+        account_id: AccountId,
+    ) -> Result<bool, Error> {
+        let (column, target_id) = comment_column(&target);
+        sqlx::query(&format!(
+            "DELETE FROM comments
+            WHERE id = $1 AND {} = $2 AND account_id = $3 AND {}
+            RETURNING id",
+            column, LIVE_COMMENT
+        ))
+        .bind(comment_id)
+        .bind(target_id)
+        .bind(account_id.0)
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_not_found("comment", comment_id))?;
+        Ok(true)
+    }
+
+    async fn get_tags(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Tag>, Error> {
+        match sqlx::query(&format!(
+            "SELECT {} FROM tags ORDER BY question_count DESC, name LIMIT $1 OFFSET $2",
+            TAG_COLUMNS
+        ))
+        .bind(limit)
+        .bind(offset)
+        .map(tag)
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(tags) => Ok(tags),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn count_tags(&self) -> Result<i64, Error> {
+        match sqlx::query("SELECT COUNT(*) AS total FROM tags")
+            .map(|row: PgRow| row.get("total"))
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(total) => Ok(total),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_tag(&self, name: String) -> Result<Option<Tag>, Error> {
+        match sqlx::query(&format!(
+            "SELECT {} FROM tags
+            WHERE tags.name = $1
+            OR tags.id = (SELECT tag_id FROM tag_synonyms WHERE tag_synonyms.name = $1)",
+            TAG_COLUMNS
+        ))
+        .bind(name)
+        .map(tag)
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(tag) => Ok(tag),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn add_tag(&self, new_tag: NewTag) -> Result<Tag, Error> {
+        match sqlx::query(&format!(
+            "INSERT INTO tags (name, description) VALUES ($1, $2) RETURNING {}",
+            TAG_COLUMNS
+        ))
+        .bind(new_tag.name)
+        .bind(new_tag.description)
+        .map(tag)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(tag) => Ok(tag),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn update_tag(&self, name: String, update: TagUpdate) -> Result<Tag, Error> {
+        match sqlx::query(&format!(
+            "UPDATE tags SET description = $1 WHERE name = $2 RETURNING {}",
+            TAG_COLUMNS
+        ))
+        .bind(update.description)
+        .bind(name)
+        .map(tag)
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(tag) => Ok(tag),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn add_tag_synonym(&self, name: String, synonym: String) -> Result<Tag, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        sqlx::query(
+            "INSERT INTO tag_synonyms (name, tag_id)
+            SELECT $1, id FROM tags WHERE name = $2
+            RETURNING tag_id",
+        )
+        .bind(synonym)
+        .bind(&name)
+        .fetch_one(&mut tx)
+        .await
+        .map_err(log_error)?;
+        let tag = read_tag(&mut tx, &name).await.map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(tag)
+    }
+
+    async fn merge_tags(&self, name: String, into: String) -> Result<Tag, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let mut ids = Vec::with_capacity(2);
+        for name in [&name, &into] {
+            let id = sqlx::query("SELECT id FROM tags WHERE name = $1")
+                .bind(name)
+                .map(|row: PgRow| row.get::<i32, _>("id"))
+                .fetch_one(&mut tx)
+                .await
+                .map_err(log_error)?;
+            ids.push(id);
+        }
+        let (from_id, into_id) = (ids[0], ids[1]);
+        // Questions having both tags keep the one they already have.
+        for statement in [
+            "INSERT INTO question_tags (question_id, tag_id, position)
+            SELECT question_id, $2, position FROM question_tags WHERE tag_id = $1
+            ON CONFLICT (question_id, tag_id) DO NOTHING",
+            "UPDATE tag_synonyms SET tag_id = $2 WHERE tag_id = $1",
+            "DELETE FROM tags WHERE id = $1",
+        ] {
+            sqlx::query(statement)
+                .bind(from_id)
+                .bind(into_id)
+                .execute(&mut tx)
+                .await
+                .map_err(log_error)?;
+        }
+        sqlx::query("INSERT INTO tag_synonyms (name, tag_id) VALUES ($1, $2)")
+            .bind(name)
+            .bind(into_id)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        let tag = read_tag(&mut tx, &into).await.map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(tag)
+    }
+
+    async fn add_account(&self, account: Account) -> Result<bool, Error> {
+        match sqlx::query(
+            "INSERT INTO accounts (email, password, role, verified, display_name, bio)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(account.email)
+        .bind(account.password)
+        .bind(account.role.as_str())
+        .bind(account.verified)
+        .bind(account.display_name)
+        .bind(account.bio)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(error) => {
+                tracing::event!(
+                    tracing::Level::ERROR,
+                    code = error
+                        .as_database_error()
+                        .unwrap()
+                        .code()
+                        .unwrap()
+                        .parse::<i32>()
+                        .unwrap(),
+                    db_message = error.as_database_error().unwrap().message(),
+                    constraint = error.as_database_error().unwrap().constraint().unwrap()
+                );
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_account(&self, email: String) -> Result<Account, Error> {
+        match sqlx::query("SELECT *  from accounts where email = $1")
+            .bind(email)
+            .map(account)
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(account) => Ok(account),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn get_account_by_id(&self, account_id: AccountId) -> Result<Account, Error> {
+        match sqlx::query("SELECT * from accounts where id = $1")
+            .bind(account_id.0)
+            .map(account)
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(account) => Ok(account),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn set_account_role(&self, account_id: AccountId, role: Role) -> Result<bool, Error> {
+        match sqlx::query("UPDATE accounts SET role = $1 WHERE id = $2")
+            .bind(role.as_str())
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn set_account_password(
+        &self,
+        account_id: AccountId,
+        password: String,
+    ) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let updated = sqlx::query("UPDATE accounts SET password = $1 WHERE id = $2")
+            .bind(password)
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        sqlx::query("DELETE FROM refresh_tokens WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    async fn get_profile(&self, account_id: AccountId) -> Result<Option<Profile>, Error> {
+        sqlx::query(&format!(
+            "SELECT {} FROM accounts WHERE id = $1",
+            PROFILE_COLUMNS
+        ))
+        .bind(account_id.0)
+        .map(profile)
+        .fetch_optional(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn get_profiles(&self, limit: Option<u32>, offset: u32) -> Result<Vec<Profile>, Error> {
+        sqlx::query(&format!(
+            "SELECT {} FROM accounts ORDER BY id LIMIT $1 OFFSET $2",
+            PROFILE_COLUMNS
+        ))
+        .bind(limit)
+        .bind(offset)
+        .map(profile)
+        .fetch_all(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn count_accounts(&self) -> Result<i64, Error> {
+        sqlx::query("SELECT COUNT(*) AS total FROM accounts")
+            .map(|row: PgRow| row.get("total"))
+            .fetch_one(&self.connection)
+            .await
+            .map_err(log_error)
+    }
+
+    async fn update_profile(
+        &self,
+        account_id: AccountId,
+        update: ProfileUpdate,
+    ) -> Result<bool, Error> {
+        let result = sqlx::query(
+            "UPDATE accounts SET
+                display_name = CASE WHEN $2::text IS NULL THEN display_name
+                    ELSE NULLIF($2, '') END,
+                bio = CASE WHEN $3::text IS NULL THEN bio ELSE NULLIF($3, '') END
+            WHERE id = $1",
+        )
+        .bind(account_id.0)
+        .bind(update.display_name)
+        .bind(update.bio)
+        .execute(&self.connection)
+        .await
+        .map_err(log_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_account(
+        &self,
+        account_id: AccountId,
+        posts: DeletedAccountPosts,
+    ) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let deleted = sqlx::query("DELETE FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?
+            .rows_affected();
+        if deleted == 0 {
+            return Ok(false);
+        }
+        let mut statements = match posts {
+            // Answers don't cascade with their question, unlike the other
+            // rows referencing it.
+            DeletedAccountPosts::Remove => vec![
+                "DELETE FROM answers WHERE account_id = $1
+                OR question_id IN (SELECT id FROM questions WHERE account_id = $1)"
+                    .to_string(),
+                "DELETE FROM questions WHERE account_id = $1".to_string(),
+                "DELETE FROM comments WHERE account_id = $1".to_string(),
+            ],
+            DeletedAccountPosts::Anonymize => ["questions", "answers", "comments"]
+                .iter()
+                .map(|table| {
+                    format!(
+                        "UPDATE {} SET account_id = {} WHERE account_id = $1",
+                        table, DELETED_ACCOUNT.0
+                    )
+                })
+                .collect(),
+        };
+        statements.push(format!(
+            "UPDATE question_revisions SET account_id = {} WHERE account_id = $1",
+            DELETED_ACCOUNT.0
+        ));
+        for table in [
+            "votes",
+            "refresh_tokens",
+            "password_reset_tokens",
+            "email_verification_tokens",
+            "account_totp",
+            "api_keys",
+            "account_identities",
+        ] {
+            statements.push(format!("DELETE FROM {} WHERE account_id = $1", table));
+        }
+        for statement in statements {
+            sqlx::query(&statement)
+                .bind(account_id.0)
+                .execute(&mut tx)
+                .await
+                .map_err(log_error)?;
+        }
+        sqlx::query("DELETE FROM login_attempts WHERE key = $1")
+            .bind(LoginKey::Account(account_id).to_string())
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(true)
+    }
+
+    async fn add_refresh_token(&self, token: RefreshToken) -> Result<bool, Error> {
+        let mut connection = self.connection.acquire().await.map_err(log_error)?;
+        insert_refresh_token(&mut connection, &token)
+            .await
+            .map_err(log_error)?;
+        Ok(true)
+    }
+
+    async fn get_refresh_token(&self, token_hash: String) -> Result<Option<RefreshToken>, Error> {
+        match sqlx::query(
+            "SELECT token_hash, account_id, sid, expires_at, used_at, scopes
+            FROM refresh_tokens WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .map(refresh_token)
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(token) => Ok(token),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        token_hash: String,
+        next: RefreshToken,
+    ) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        // Only one of two concurrent refreshes with the same token marks it.
+        let marked = sqlx::query(
+            "UPDATE refresh_tokens SET used_at = NOW()
+            WHERE token_hash = $1 AND used_at IS NULL",
+        )
+        .bind(token_hash)
+        .execute(&mut tx)
+        .await
+        .map_err(log_error)?;
+        if marked.rows_affected() == 0 {
+            return Ok(false);
+        }
+        insert_refresh_token(&mut tx, &next)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(true)
+    }
+
+    async fn revoke_refresh_tokens(&self, sid: String) -> Result<bool, Error> {
+        match sqlx::query("DELETE FROM refresh_tokens WHERE sid = $1")
+            .bind(sid)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn revoke_token(&self, jti: String, expires_at: NaiveDateTime) -> Result<bool, Error> {
+        match sqlx::query(
+            "INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2)
+            ON CONFLICT (jti) DO NOTHING",
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(result) => Ok(result.rows_affected() > 0),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn is_token_revoked(&self, jti: &str) -> Result<bool, Error> {
+        match sqlx::query("SELECT jti FROM revoked_tokens WHERE jti = $1")
+            .bind(jti)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(revoked) => Ok(revoked.is_some()),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn add_reset_token(&self, token: ResetToken) -> Result<bool, Error> {
+        match sqlx::query(
+            "INSERT INTO password_reset_tokens (token_hash, account_id, expires_at)
+            VALUES ($1, $2, $3)",
+        )
+        .bind(token.token_hash)
+        .bind(token.account_id.0)
+        .bind(token.expires_at)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn use_reset_token(
+        &self,
+        token_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<AccountId>, Error> {
+        match sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = $2
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > $2
+            RETURNING account_id",
+        )
+        .bind(token_hash)
+        .bind(now)
+        .map(|row: PgRow| AccountId(row.get("account_id")))
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(account_id) => Ok(account_id),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn add_verification_token(&self, token: VerificationToken) -> Result<bool, Error> {
+        match sqlx::query(
+            "INSERT INTO email_verification_tokens (token_hash, account_id, expires_at)
+            VALUES ($1, $2, $3)",
+        )
+        .bind(token.token_hash)
+        .bind(token.account_id.0)
+        .bind(token.expires_at)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(true),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    async fn verify_account(
+        &self,
+        token_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<AccountId>, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let account_id = sqlx::query(
+            "UPDATE email_verification_tokens SET used_at = $2
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > $2
+            RETURNING account_id",
+        )
+        .bind(token_hash)
+        .bind(now)
+        .map(|row: PgRow| AccountId(row.get("account_id")))
+        .fetch_optional(&mut tx)
+        .await
+        .map_err(log_error)?;
+        if let Some(account_id) = &account_id {
+            sqlx::query("UPDATE accounts SET verified = TRUE WHERE id = $1")
+                .bind(account_id.0)
+                .execute(&mut tx)
+                .await
+                .map_err(log_error)?;
+        }
+        tx.commit().await.map_err(log_error)?;
+        Ok(account_id)
+    }
+
+    async fn purge_expired_tokens(&self, now: NaiveDateTime) -> Result<u64, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let refresh = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < $1")
+            .bind(now)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        let revoked = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < $1")
+            .bind(now)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        let reset = sqlx::query("DELETE FROM password_reset_tokens WHERE expires_at < $1")
+            .bind(now)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        let verification =
+            sqlx::query("DELETE FROM email_verification_tokens WHERE expires_at < $1")
+                .bind(now)
+                .execute(&mut tx)
+                .await
+                .map_err(log_error)?;
+        let oidc = sqlx::query("DELETE FROM oidc_states WHERE expires_at < $1")
+            .bind(now)
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        tx.commit().await.map_err(log_error)?;
+        Ok(refresh.rows_affected()
+            + revoked.rows_affected()
+            + reset.rows_affected()
+            + verification.rows_affected()
+            + oidc.rows_affected())
+    }
+
+    async fn add_login_failure(
+        &self,
+        key: &LoginKey,
+        now: NaiveDateTime,
+        reset_before: NaiveDateTime,
+    ) -> Result<LoginAttempts, Error> {
+        sqlx::query(
+            "INSERT INTO login_attempts (key, failures, last_failure_at)
+            VALUES ($1, 1, $2)
+            ON CONFLICT (key) DO UPDATE SET
+                failures = CASE WHEN login_attempts.last_failure_at < $3 THEN 1
+                    ELSE login_attempts.failures + 1 END,
+                last_failure_at = $2
+            RETURNING failures, last_failure_at",
+        )
+        .bind(key.to_string())
+        .bind(now)
+        .bind(reset_before)
+        .map(login_attempts)
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn get_login_attempts(&self, key: &LoginKey) -> Result<Option<LoginAttempts>, Error> {
+        sqlx::query("SELECT failures, last_failure_at FROM login_attempts WHERE key = $1")
+            .bind(key.to_string())
+            .map(login_attempts)
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(log_error)
+    }
+
+    async fn clear_login_failures(&self, key: &LoginKey) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE key = $1")
+            .bind(key.to_string())
+            .execute(&self.connection)
+            .await
+            .map_err(log_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn purge_login_attempts(&self, before: NaiveDateTime) -> Result<u64, Error> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE last_failure_at < $1")
+            .bind(before)
+            .execute(&self.connection)
+            .await
+            .map_err(log_error)?;
+        Ok(result.rows_affected())
+    }
+
+    async fn set_totp_factor(&self, factor: TotpFactor) -> Result<bool, Error> {
+        sqlx::query(
+            "INSERT INTO account_totp
+            (account_id, secret, enabled, last_used_step, recovery_code_hashes)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (account_id) DO UPDATE SET
+                secret = $2, enabled = $3, last_used_step = $4, recovery_code_hashes = $5",
+        )
+        .bind(factor.account_id.0)
+        .bind(factor.secret)
+        .bind(factor.enabled)
+        .bind(factor.last_used_step)
+        .bind(factor.recovery_code_hashes)
+        .execute(&self.connection)
+        .await
+        .map_err(log_error)?;
+        Ok(true)
+    }
+
+    async fn get_totp_factor(&self, account_id: AccountId) -> Result<Option<TotpFactor>, Error> {
+        sqlx::query(
+            "SELECT account_id, secret, enabled, last_used_step, recovery_code_hashes
+            FROM account_totp WHERE account_id = $1",
+        )
+        .bind(account_id.0)
+        .map(totp_factor)
+        .fetch_optional(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn use_totp_step(&self, account_id: AccountId, step: i64) -> Result<bool, Error> {
+        let result = sqlx::query(
+            "UPDATE account_totp SET last_used_step = $2
+            WHERE account_id = $1 AND enabled
+            AND (last_used_step IS NULL OR last_used_step < $2)",
+        )
+        .bind(account_id.0)
+        .bind(step)
+        .execute(&self.connection)
+        .await
+        .map_err(log_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn use_recovery_code(
+        &self,
+        account_id: AccountId,
+        code_hash: String,
+    ) -> Result<bool, Error> {
+        let result = sqlx::query(
+            "UPDATE account_totp
+            SET recovery_code_hashes = array_remove(recovery_code_hashes, $2)
+            WHERE account_id = $1 AND enabled AND $2 = ANY(recovery_code_hashes)",
+        )
+        .bind(account_id.0)
+        .bind(code_hash)
+        .execute(&self.connection)
+        .await
+        .map_err(log_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_totp_factor(&self, account_id: AccountId) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM account_totp WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(log_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn add_api_key(
+        &self,
+        account_id: AccountId,
+        new_key: NewApiKey,
+        key_hash: String,
+    ) -> Result<ApiKey, Error> {
+        let scopes: Vec<String> = new_key
+            .scopes
+            .iter()
+            .map(|scope| scope.to_string())
+            .collect();
+        sqlx::query(&format!(
+            "INSERT INTO api_keys (account_id, name, key_hash, scopes)
+            VALUES ($1, $2, $3, $4)
+            RETURNING {}",
+            API_KEY_COLUMNS
+        ))
+        .bind(account_id.0)
+        .bind(new_key.name)
+        .bind(key_hash)
+        .bind(scopes)
+        .map(api_key)
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn get_api_keys(&self, account_id: AccountId) -> Result<Vec<ApiKey>, Error> {
+        sqlx::query(&format!(
+            "SELECT {} FROM api_keys WHERE account_id = $1 ORDER BY id",
+            API_KEY_COLUMNS
+        ))
+        .bind(account_id.0)
+        .map(api_key)
+        .fetch_all(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn use_api_key(
+        &self,
+        key_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<ApiKey>, Error> {
+        sqlx::query(&format!(
+            "UPDATE api_keys SET last_used_at = $2 WHERE key_hash = $1 RETURNING {}",
+            API_KEY_COLUMNS
+        ))
+        .bind(key_hash)
+        .bind(now)
+        .map(api_key)
+        .fetch_optional(&self.connection)
+        .await
+        .map_err(log_error)
+    }
+
+    async fn delete_api_key(&self, account_id: AccountId, id: ApiKeyId) -> Result<bool, Error> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE id = $1 AND account_id = $2")
+            .bind(id.0)
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+            .map_err(log_error)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn add_oidc_state(&self, state: OidcState) -> Result<bool, Error> {
+        sqlx::query(
+            "INSERT INTO oidc_states (state_hash, provider, code_verifier, nonce, expires_at)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(state.state_hash)
+        .bind(state.provider)
+        .bind(state.code_verifier)
+        .bind(state.nonce)
+        .bind(state.expires_at)
+        .execute(&self.connection)
+        .await
+        .map_err(log_error)?;
+        Ok(true)
+    }
+
+    async fn take_oidc_state(
+        &self,
+        state_hash: String,
+        now: NaiveDateTime,
+    ) -> Result<Option<OidcState>, Error> {
+        // Deleted whether expired or not, since it can't be used anymore.
+        let state = sqlx::query(
+            "DELETE FROM oidc_states WHERE state_hash = $1
+            RETURNING state_hash, provider, code_verifier, nonce, expires_at",
+        )
+        .bind(state_hash)
+        .map(|row: PgRow| OidcState {
+            state_hash: row.get("state_hash"),
+            provider: row.get("provider"),
+            code_verifier: row.get("code_verifier"),
+            nonce: row.get("nonce"),
+            expires_at: row.get("expires_at"),
+        })
+        .fetch_optional(&self.connection)
+        .await
+        .map_err(log_error)?;
+        Ok(state.filter(|state| state.expires_at > now))
+    }
+
+    async fn get_account_identity(
+        &self,
+        issuer: String,
+        subject: String,
+    ) -> Result<Option<AccountId>, Error> {
+        sqlx::query("SELECT account_id FROM account_identities WHERE issuer = $1 AND subject = $2")
+            .bind(issuer)
+            .bind(subject)
+            .map(|row: PgRow| AccountId(row.get("account_id")))
+            .fetch_optional(&self.connection)
+            .await
+            .map_err(log_error)
+    }
+
+    async fn add_account_identity(
+        &self,
+        issuer: String,
+        subject: String,
+        account_id: AccountId,
+    ) -> Result<bool, Error> {
+        sqlx::query(
+            "INSERT INTO account_identities (issuer, subject, account_id) VALUES ($1, $2, $3)",
+        )
+        .bind(issuer)
+        .bind(subject)
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        .map_err(log_error)?;
+        Ok(true)
+    }
+
+    async fn is_question_owner(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        // fetch_optional: returns None or one answer back.
+        match sqlx::query("SELECT * from questions where id = $1 and account_id = $2")
+            .bind(question_id)
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(question) => Ok(question.is_some()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    async fn is_answer_owner(&self, answer_id: i32, account_id: &AccountId) -> Result<bool, Error> {
+        sqlx::query(&format!(
+            "SELECT account_id = $2 AS owned FROM answers WHERE id = $1 AND {}",
+            LIVE_ANSWER
+        ))
+        .bind(answer_id)
+        .bind(account_id.0)
+        .map(|row: PgRow| row.get("owned"))
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_not_found("answer", answer_id))
+    }
+
+    async fn is_comment_owner(
+        &self,
+        comment_id: i32,
+        account_id: &AccountId,
+    ) -> Result<bool, Error> {
+        sqlx::query(&format!(
+            "SELECT account_id = $2 AS owned FROM comments WHERE id = $1 AND {}",
+            LIVE_COMMENT
+        ))
+        .bind(comment_id)
+        .bind(account_id.0)
+        .map(|row: PgRow| row.get("owned"))
+        .fetch_one(&self.connection)
+        .await
+        .map_err(log_not_found("comment", comment_id))
+    }
+
+    async fn import_questions(
+        &self,
+        questions: Vec<ImportQuestion>,
+        account_id: AccountId,
+        on_duplicate: OnDuplicate,
+    ) -> Result<Vec<Result<ImportOutcome, String>>, Error> {
+        let mut tx = self.connection.begin().await.map_err(log_error)?;
+        let mut outcomes = Vec::with_capacity(questions.len());
+        for question in questions {
+            // Each question runs in a savepoint, so a failing one is rolled
+            // back without aborting the whole transaction.
+            let mut savepoint = tx.begin().await.map_err(log_error)?;
+            match import_question(&mut savepoint, question, &account_id, on_duplicate).await {
+                Ok(outcome) => {
+                    savepoint.commit().await.map_err(log_error)?;
+                    outcomes.push(Ok(outcome));
+                }
+                Err(error) => {
+                    savepoint.rollback().await.map_err(log_error)?;
+                    tracing::event!(tracing::Level::WARN, "{:?}", error);
+                    outcomes.push(Err(error.to_string()));
+                }
+            }
+        }
+        // Rows inserted with an explicit id don't advance the serial
+        // sequences, so they are moved past the highest id.
+        for table in ["questions", "answers"] {
+            sqlx::query(&format!(
+                "SELECT setval(pg_get_serial_sequence('{0}', 'id'), COALESCE(MAX(id), 0) + 1, false)
+                FROM {0}",
+                table
+            ))
+            .execute(&mut tx)
+            .await
+            .map_err(log_error)?;
+        }
+        tx.commit().await.map_err(log_error)?;
+        Ok(outcomes)
+    }
+
+    // The streams own a handle to the pool, so rows are sent as they are
+    // read instead of being collected first.
+    fn stream_accounts(&self) -> BoxStream<'static, Result<Account, Error>> {
+        let connection = self.connection.clone();
+        Box::pin(async_stream::try_stream! {
+            let mut rows = sqlx::query("SELECT * from accounts ORDER BY id")
+                .map(account)
+                .fetch(&connection);
+            while let Some(account) = rows.try_next().await.map_err(log_error)? {
+                yield account;
+            }
+        })
+    }
+
+    fn stream_questions(&self) -> BoxStream<'static, Result<QuestionRecord, Error>> {
+        let connection = self.connection.clone();
+        Box::pin(async_stream::try_stream! {
+            let mut rows = sqlx::query(
+                "SELECT *, tag_names(id) AS tags from questions ORDER BY id",
+            )
+                .map(|row: PgRow| QuestionRecord {
+                    id: QuestionId(row.get("id")),
+                    title: row.get("title"),
+                    content: row.get("content"),
+                    tags: row.get("tags"),
+                    accepted_answer_id: row
+                        .get::<Option<i32>, _>("accepted_answer_id")
+                        .map(AnswerId),
+                    account_id: AccountId(row.get("account_id")),
+                    deleted_at: row.get("deleted_at"),
+                })
+                .fetch(&connection);
+            while let Some(question) = rows.try_next().await.map_err(log_error)? {
+                yield question;
+            }
+        })
+    }
+
+    fn stream_answers(&self) -> BoxStream<'static, Result<AnswerRecord, Error>> {
+        let connection = self.connection.clone();
+        Box::pin(async_stream::try_stream! {
+            let mut rows = sqlx::query(
+                "SELECT * from answers ORDER BY question_id, id",
+            )
+                .map(|row: PgRow| AnswerRecord {
+                    id: AnswerId(row.get("id")),
+                    content: row.get("content"),
+                    question_id: QuestionId(row.get("question_id")),
+                    account_id: AccountId(row.get("account_id")),
+                })
+                .fetch(&connection);
+            while let Some(answer) = rows.try_next().await.map_err(log_error)? {
+                yield answer;
+            }
+        })
+    }
+}
+
+fn log_error(error: sqlx::Error) -> Error {
+    tracing::event!(tracing::Level::ERROR, "{:?}", error);
+    Error::DatabaseQueryError(error)
+}
+
+// Like `log_error`, but a missing row is the `what` with this id not being
+// found.
+fn log_not_found(what: &'static str, id: i32) -> impl FnOnce(sqlx::Error) -> Error {
+    move |error| match error {
+        sqlx::Error::RowNotFound => not_found(what, id),
+        error => log_error(error),
+    }
+}
+
+fn login_attempts(row: PgRow) -> LoginAttempts {
+    LoginAttempts {
+        failures: row.get("failures"),
+        last_failure_at: row.get("last_failure_at"),
+    }
+}
+
+fn api_key(row: PgRow) -> ApiKey {
+    ApiKey {
+        id: ApiKeyId(row.get("id")),
+        account_id: AccountId(row.get("account_id")),
+        name: row.get("name"),
+        // Only known scopes are stored.
+        scopes: row
+            .get::<Vec<String>, _>("scopes")
+            .iter()
+            .filter_map(|scope| scope.parse().ok())
+            .collect(),
+        created_on: row.get("created_on"),
+        last_used_at: row.get("last_used_at"),
+    }
+}
+
+fn totp_factor(row: PgRow) -> TotpFactor {
+    TotpFactor {
+        account_id: AccountId(row.get("account_id")),
+        secret: row.get("secret"),
+        enabled: row.get("enabled"),
+        last_used_step: row.get("last_used_step"),
+        recovery_code_hashes: row.get("recovery_code_hashes"),
+    }
+}
+
+fn account(row: PgRow) -> Account {
+    Account {
+        id: Some(AccountId(row.get("id"))),
+        email: row.get("email"),
+        password: row.get("password"),
+        // The column is checked against the names of the roles.
+        role: row.get::<String, _>("role").parse().unwrap_or_default(),
+        verified: row.get("verified"),
+        display_name: row.get("display_name"),
+        bio: row.get("bio"),
+    }
+}
+
+fn profile(row: PgRow) -> Profile {
+    Profile {
+        id: AccountId(row.get("id")),
+        display_name: row.get("display_name"),
+        bio: row.get("bio"),
+        role: row.get::<String, _>("role").parse().unwrap_or_default(),
+        question_count: row.get("question_count"),
+        answer_count: row.get("answer_count"),
+    }
+}
+
+fn refresh_token(row: PgRow) -> RefreshToken {
+    RefreshToken {
+        token_hash: row.get("token_hash"),
+        account_id: AccountId(row.get("account_id")),
+        sid: row.get("sid"),
+        expires_at: row.get("expires_at"),
+        used: row.get::<Option<NaiveDateTime>, _>("used_at").is_some(),
+        scopes: row.get::<Option<Vec<String>>, _>("scopes").map(|scopes| {
+            scopes
+                .iter()
+                .filter_map(|scope| scope.parse().ok())
+                .collect()
+        }),
+    }
+}
+
+async fn insert_refresh_token(
+    connection: &mut PgConnection,
+    token: &RefreshToken,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO refresh_tokens (token_hash, account_id, sid, expires_at, scopes)
+        VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(&token.token_hash)
+    .bind(token.account_id.0)
+    .bind(&token.sid)
+    .bind(token.expires_at)
+    .bind(token.scopes.as_ref().map(|scopes| {
+        scopes
+            .iter()
+            .map(|scope| scope.to_string())
+            .collect::<Vec<String>>()
+    }))
+    .execute(connection)
+    .await
+    .map(|_| ())
+}
+
+fn comment(row: PgRow) -> Comment {
+    Comment {
+        id: CommentId(row.get("id")),
+        content: row.get("content"),
+        question_id: row.get::<Option<i32>, _>("question_id").map(QuestionId),
+        answer_id: row.get::<Option<i32>, _>("answer_id").map(AnswerId),
+    }
+}
+
+fn comment_column(target: &CommentTarget) -> (&'static str, i32) {
+    match target {
+        CommentTarget::Question(id) => ("question_id", id.0),
+        CommentTarget::Answer(id) => ("answer_id", id.0),
+    }
+}
+
+// Whether the question, or the answer of a question, bound to `id` exists and
+// isn't deleted: only then are votes and comments changed.
+fn live_target(column: &str, id: &str) -> String {
+    if column == "question_id" {
+        format!(
+            "EXISTS (SELECT 1 FROM questions WHERE id = {} AND deleted_at IS NULL)",
+            id
+        )
+    } else {
+        format!(
+            "EXISTS (SELECT 1 FROM answers JOIN questions ON questions.id = answers.question_id
+            WHERE answers.id = {} AND questions.deleted_at IS NULL)",
+            id
+        )
+    }
+}
+
+// "question" or "answer", from the column of a vote or comment target.
+fn target_name(column: &str) -> &'static str {
+    if column == "question_id" {
+        "question"
+    } else {
+        "answer"
+    }
+}
+
+fn revision(row: PgRow) -> Revision {
+    Revision {
+        number: row.get("number"),
+        question_id: QuestionId(row.get("question_id")),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        account_id: AccountId(row.get("account_id")),
+        created_on: row.get("created_on"),
+    }
+}
+
+fn tag(row: PgRow) -> Tag {
+    Tag {
+        name: row.get("name"),
+        description: row.get("description"),
+        question_count: row.get("question_count"),
+        synonyms: row.get("synonyms"),
+    }
+}
+
+async fn read_tag(connection: &mut PgConnection, name: &str) -> Result<Tag, sqlx::Error> {
+    sqlx::query(&format!("SELECT {} FROM tags WHERE name = $1", TAG_COLUMNS))
+        .bind(name)
+        .map(tag)
+        .fetch_one(connection)
+        .await
+}
+
+async fn read_question(
+    connection: &mut PgConnection,
+    question_id: i32,
+) -> Result<Question, sqlx::Error> {
+    sqlx::query(&format!(
+        "SELECT *, {}, tag_names(id) AS tags from questions WHERE id = $1",
+        QUESTION_SCORE
+    ))
+    .bind(question_id)
+    .map(|row: PgRow| Question {
+        id: QuestionId(row.get("id")),
+        title: row.get("title"),
+        content: row.get("content"),
+        tags: row.get("tags"),
+        accepted_answer_id: row
+            .get::<Option<i32>, _>("accepted_answer_id")
+            .map(AnswerId),
+        score: row.get("score"),
+    })
+    .fetch_one(connection)
+    .await
+}
+
+// Replaces the tags of the question. Synonyms are resolved to their tag and
+// names missing from the catalogue are left out.
+async fn set_question_tags(
+    connection: &mut PgConnection,
+    question_id: i32,
+    tags: Option<Vec<String>>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM question_tags WHERE question_id = $1")
+        .bind(question_id)
+        .execute(&mut *connection)
+        .await?;
+    sqlx::query(
+        "INSERT INTO question_tags (question_id, tag_id, position)
+        SELECT $1, tags.id, MIN(tag.position)
+        FROM unnest($2::text[]) WITH ORDINALITY AS tag(name, position)
+        JOIN tags ON tags.name = tag.name
+        OR tags.id = (SELECT tag_id FROM tag_synonyms WHERE tag_synonyms.name = tag.name)
+        GROUP BY tags.id",
+    )
+    .bind(question_id)
+    .bind(tags)
+    .execute(connection)
+    .await?;
+    Ok(())
+}
+
+// Conditions of `get_questions`, reading the filter from five parameters
+// starting at `$first`. Conditions whose parameter is NULL are left out and
+// deleted questions are never matched.
+fn question_conditions(filter: &QuestionFilter, first: usize) -> String {
+    let tag_operator = match filter.tag_match {
+        TagMatch::All => "@>",
+        TagMatch::Any => "&&",
+    };
+    let [tags, author, after, before, has_answers] =
+        [0, 1, 2, 3, 4].map(|n| format!("${}", first + n));
+    format!(
+        "questions.deleted_at IS NULL
+        AND ({tags}::text[] IS NULL OR tag_names(questions.id) {tag_operator} {tags})
+        AND ({author}::integer IS NULL OR account_id = {author})
+        AND ({after}::timestamp IS NULL OR created_on > {after})
+        AND ({before}::timestamp IS NULL OR created_on < {before})
+        AND ({has_answers}::boolean IS NULL OR
+            EXISTS (SELECT 1 FROM answers WHERE answers.question_id = questions.id) = {has_answers})"
+    )
+}
+
+// Sort keys of `QuestionOrder::keyset_order`.
+fn question_sort_key(order: QuestionOrder) -> &'static str {
+    match order {
+        QuestionOrder::Id => "NULL::bigint",
+        QuestionOrder::CreatedOn => "(EXTRACT(EPOCH FROM created_on) * 1000000)::bigint",
+        QuestionOrder::Score => {
+            "COALESCE((SELECT SUM(value) FROM votes WHERE votes.question_id = questions.id), 0)"
+        }
+        QuestionOrder::AnswerCount => {
+            "(SELECT COUNT(*) FROM answers WHERE answers.question_id = questions.id)"
+        }
+    }
+}
+
+// Condition keeping the rows past the cursor, bound to $1 (sort key) and
+// $2 (id), and the ORDER BY walking away from it, over rows with `sort_key`
+// and `id` columns. Without a cursor, every row is kept.
+fn keyset_clauses(order: KeysetOrder, backward: bool) -> (String, String) {
+    let direction = |direction: SortDirection| match (direction, backward) {
+        (SortDirection::Ascending, false) | (SortDirection::Descending, true) => (">", "ASC"),
+        (SortDirection::Descending, false) | (SortDirection::Ascending, true) => ("<", "DESC"),
+    };
+    let (id_comparison, id_order) = direction(order.id);
+    match order.key {
+        Some(key) => {
+            let (key_comparison, key_order) = direction(key);
+            (
+                format!(
+                    "($2::integer IS NULL OR sort_key {0} $1::bigint
+                    OR (sort_key = $1::bigint AND id {1} $2))",
+                    key_comparison, id_comparison
+                ),
+                format!("sort_key {}, id {}", key_order, id_order),
+            )
+        }
+        None => (
+            format!(
+                "($2::integer IS NULL OR ($1::bigint IS NULL AND id {} $2))",
+                id_comparison
+            ),
+            format!("id {}", id_order),
+        ),
+    }
+}
+
+// Column of the votes table referencing the target, and the target id.
+fn vote_column(target: &VoteTarget) -> (&'static str, i32) {
+    match target {
+        VoteTarget::Question(id) => ("question_id", id.0),
+        VoteTarget::Answer(id) => ("answer_id", id.0),
+    }
+}
+
+async fn vote_score(
+    connection: &mut PgConnection,
+    column: &str,
+    target_id: i32,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query(&format!(
+        "SELECT COALESCE(SUM(value), 0) AS score FROM votes WHERE {} = $1",
+        column
+    ))
+    .bind(target_id)
+    .map(|row: PgRow| row.get::<i64, _>("score"))
+    .fetch_one(connection)
+    .await
+}
+
+async fn import_question(
+    connection: &mut PgConnection,
+    question: ImportQuestion,
+    account_id: &AccountId,
+    on_duplicate: OnDuplicate,
+) -> Result<ImportOutcome, sqlx::Error> {
+    let exists = match &question.id {
+        Some(id) => sqlx::query("SELECT id from questions WHERE id = $1")
+            .bind(id.0)
+            .fetch_optional(&mut *connection)
+            .await?
+            .is_some(),
+        None => false,
+    };
+    let (question_id, outcome) = match (exists, on_duplicate) {
+        (true, OnDuplicate::Skip) => return Ok(ImportOutcome::Skipped),
+        (true, OnDuplicate::Upsert) => {
+            let id = sqlx::query(
+                "UPDATE questions
+                SET title = $1, content = $2, account_id = $3,
+                accepted_answer_id = NULL, deleted_at = COALESCE($5, deleted_at)
+                WHERE id = $4
+                RETURNING id",
+            )
+            .bind(question.title)
+            .bind(question.content)
+            .bind(account_id.0)
+            .bind(question.id.map(|id| id.0))
+            .bind(question.deleted_at)
+            .map(|row: PgRow| row.get::<i32, _>("id"))
+            .fetch_one(&mut *connection)
+            .await?;
+            (id, ImportOutcome::Updated)
+        }
+        (false, _) => {
+            let id = sqlx::query(
+                "INSERT INTO questions (id, title, content, account_id, deleted_at)
+                VALUES (COALESCE($1, nextval(pg_get_serial_sequence('questions', 'id'))), $2, $3, $4, $5)
+                RETURNING id",
+            )
+            .bind(question.id.map(|id| id.0))
+            .bind(question.title)
+            .bind(question.content)
+            .bind(account_id.0)
+            .bind(question.deleted_at)
+            .map(|row: PgRow| row.get::<i32, _>("id"))
+            .fetch_one(&mut *connection)
+            .await?;
+            (id, ImportOutcome::Inserted)
+        }
+    };
+    if let Some(tags) = &question.tags {
+        sqlx::query(
+            "INSERT INTO tags (name)
+            SELECT tag.name FROM unnest($1::text[]) AS tag(name)
+            WHERE tag.name <> ''
+            AND NOT EXISTS (SELECT 1 FROM tag_synonyms WHERE tag_synonyms.name = tag.name)
+            ON CONFLICT (name) DO NOTHING",
+        )
+        .bind(tags)
+        .execute(&mut *connection)
+        .await?;
+    }
+    set_question_tags(&mut *connection, question_id, question.tags).await?;
+    let accepted_answer_id = question.accepted_answer_id;
+    for answer in question.answers {
+        let exists = match &answer.id {
+            Some(id) => sqlx::query("SELECT id from answers WHERE id = $1")
+                .bind(id.0)
+                .fetch_optional(&mut *connection)
+                .await?
+                .is_some(),
+            None => false,
+        };
+        match (exists, on_duplicate) {
+            (true, OnDuplicate::Skip) => continue,
+            (true, OnDuplicate::Upsert) => {
+                sqlx::query(
+                    "UPDATE answers
+                    SET content = $1, question_id = $2, account_id = $3
+                    WHERE id = $4",
+                )
+                .bind(answer.content)
+                .bind(question_id)
+                .bind(account_id.0)
+                .bind(answer.id.map(|id| id.0))
+                .execute(&mut *connection)
+                .await?;
+            }
+            (false, _) => {
+                sqlx::query(
+                    "INSERT INTO answers (id, content, question_id, account_id)
+                    VALUES (COALESCE($1, nextval(pg_get_serial_sequence('answers', 'id'))), $2, $3, $4)",
+                )
+                .bind(answer.id.map(|id| id.0))
+                .bind(answer.content)
+                .bind(question_id)
+                .bind(account_id.0)
+                .execute(&mut *connection)
+                .await?;
+            }
+        }
+    }
+    if let Some(answer_id) = accepted_answer_id {
+        sqlx::query(
+            "UPDATE questions
+            SET accepted_answer_id = $1
+            WHERE id = $2
+            AND EXISTS (SELECT 1 FROM answers WHERE id = $1 AND question_id = $2)",
+        )
+        .bind(answer_id.0)
+        .bind(question_id)
+        .execute(&mut *connection)
+        .await?;
+    }
+    Ok(outcome)
+}
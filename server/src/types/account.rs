@@ -1,10 +1,30 @@
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize}; // TODO not import all
+use std::fmt;
+use std::str::FromStr;
 
+use handle_errors::Error;
+
+use crate::types::scope::{all_scopes, Scope};
+
+/// Claims of an access token, or the session of an API key.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Session {
     pub exp: DateTime<Utc>,
     pub account_id: AccountId,
+    /// Id of the token, looked up in the revoked tokens.
+    pub jti: String,
+    /// Id of the login the token comes from, shared with the refresh tokens
+    /// rotated from it.
+    pub sid: String,
+    /// Role of the account when the token was issued. Tokens issued before
+    /// roles existed carry none and are read as `Role::User`.
+    #[serde(default)]
+    pub role: Role,
+    /// Tokens issued before scopes existed carry none and have all of them,
+    /// within the role.
+    #[serde(default = "all_scopes")]
+    pub scopes: Vec<Scope>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -12,7 +32,272 @@ pub struct Account {
     pub id: Option<AccountId>,
     pub email: String,
     pub password: String,
+    /// Ignored on registration: new accounts are users.
+    #[serde(default)]
+    pub role: Role,
+    /// Set once the account confirms its email. Ignored on registration.
+    #[serde(default)]
+    pub verified: bool,
+    /// Name shown instead of the email, which is kept private.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub bio: Option<String>,
+}
+
+/// Owner of the posts of deleted accounts that were kept.
+pub const DELETED_ACCOUNT: AccountId = AccountId(0);
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 50;
+pub const MAX_BIO_LENGTH: usize = 500;
+
+/// Public view of an account. Deleted questions, and their answers, aren't
+/// counted.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Profile {
+    pub id: AccountId,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub role: Role,
+    pub question_count: i64,
+    pub answer_count: i64,
+}
+
+/// Reply of `GET /me` and `PATCH /me`: the profile with the private fields.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct OwnProfile {
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub email: String,
+    pub verified: bool,
+}
+
+/// Body of `PATCH /me`. Fields left out are kept and empty ones are
+/// cleared.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ProfileUpdate {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+}
+
+/// Checks the lengths of the profile fields, in characters.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::account::{check_profile, ProfileUpdate};
+///
+/// let update = ProfileUpdate {
+///     display_name: Some("Ferris".to_string()),
+///     bio: None,
+/// };
+/// assert!(check_profile(&update).is_ok());
+/// let update = ProfileUpdate {
+///     display_name: Some("F".repeat(51)),
+///     bio: None,
+/// };
+/// assert!(check_profile(&update).is_err());
+/// ```
+pub fn check_profile(update: &ProfileUpdate) -> Result<(), Error> {
+    for (field, value, max_length) in [
+        (
+            "display_name",
+            &update.display_name,
+            MAX_DISPLAY_NAME_LENGTH,
+        ),
+        ("bio", &update.bio, MAX_BIO_LENGTH),
+    ] {
+        if value
+            .as_ref()
+            .is_some_and(|value| value.chars().count() > max_length)
+        {
+            return Err(Error::InvalidParameter(format!(
+                "{} has up to {} characters",
+                field, max_length
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Body of `DELETE /me`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccountDeletion {
+    /// Asked again so that a stolen token isn't enough.
+    pub password: String,
+}
+
+/// What happens to the questions, answers and comments of a deleted
+/// account. Its votes are withdrawn either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeletedAccountPosts {
+    /// The posts are kept and handed to `DELETED_ACCOUNT`.
+    #[default]
+    Anonymize,
+    /// The posts are removed, with the answers and comments of others on
+    /// them.
+    Remove,
+}
+
+impl FromStr for DeletedAccountPosts {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "anonymize" => Ok(DeletedAccountPosts::Anonymize),
+            "remove" => Ok(DeletedAccountPosts::Remove),
+            _ => Err(format!("expected anonymize or remove, got {}", value)),
+        }
+    }
+}
+
+/// Whether the text looks like an email address: a local part and a domain
+/// of at least two labels, without spaces. Whether it exists is only known
+/// once the account confirms it.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::account::is_valid_email;
+///
+/// assert!(is_valid_email("foo@bar.com"));
+/// assert!(!is_valid_email("foo@bar"));
+/// ```
+pub fn is_valid_email(email: &str) -> bool {
+    let (local, domain) = match email.split_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let labels: Vec<&str> = domain.split('.').collect();
+    email.len() <= 254
+        && !local.is_empty()
+        && local.len() <= 64
+        && !local.starts_with('.')
+        && !local.ends_with('.')
+        && !local.contains("..")
+        && local
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-/=?^_`{|}~.".contains(c))
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AccountId(pub i32);
+
+/// What an account may do besides changing its own posts. Each role can do
+/// everything the roles before it can.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    /// Edits and deletes any question or answer and moderates the tags.
+    Moderator,
+    /// Manages the roles of the other accounts and exports the data.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "user" => Ok(Role::User),
+            "moderator" => Ok(Role::Moderator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!(
+                "expected user, moderator or admin for role, got {}",
+                value
+            )),
+        }
+    }
+}
+
+/// Body of `POST /login`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Login {
+    pub email: String,
+    pub password: String,
+    /// Scopes of the login, among the ones of the role. Left out, it has
+    /// all of them.
+    #[serde(default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+/// Body of `PUT /accounts/{id}/role`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RoleUpdate {
+    pub role: Role,
+}
+
+/// Body of `POST /account/password`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PasswordChange {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+/// Body of `POST /account/password/reset`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+/// Body of `POST /account/verify`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EmailVerification {
+    /// Token received by email.
+    pub token: String,
+}
+
+/// Body of `POST /account/password/reset/confirm`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PasswordReset {
+    /// Token received by email.
+    pub token: String,
+    pub new_password: String,
+}
+
+#[cfg(test)]
+mod account_tests {
+    use super::is_valid_email;
+
+    #[test]
+    fn email_syntax() {
+        for email in ["foo@bar.com", "first.last+tag@mail.example.org", "a@b.io"] {
+            assert!(is_valid_email(email), "{}", email);
+        }
+        for email in [
+            "",
+            "foo",
+            "@bar.com",
+            "foo@",
+            "foo@bar",
+            "foo@@bar.com",
+            "foo bar@baz.com",
+            "foo@bar..com",
+            ".foo@bar.com",
+            "foo@-bar.com",
+        ] {
+            assert!(!is_valid_email(email), "{}", email);
+        }
+    }
+}
@@ -1,12 +1,24 @@
 use serde::{Deserialize, Serialize};
 
+use crate::types::pagination::{KeysetOrder, SortDirection};
 use crate::types::question::QuestionId;
 
+/// Order of the answers of a question: the accepted answer first, with 1 as
+/// sort key, followed by the others, with 0.
+pub const ACCEPTED_FIRST: KeysetOrder = KeysetOrder {
+    name: "accepted_first",
+    key: Some(SortDirection::Descending),
+    id: SortDirection::Ascending,
+};
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Answer {
     pub id: AnswerId,
     pub content: String,
     pub question_id: QuestionId,
+    /// Sum of the votes. Computed by the store, ignored on updates.
+    #[serde(default)]
+    pub score: i64,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -0,0 +1,80 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use handle_errors::Error;
+
+use crate::types::account::AccountId;
+use crate::types::scope::Scope;
+
+/// Header carrying an API key, instead of an access token in
+/// `Authorization`.
+pub const API_KEY_HEADER: &str = "X-API-Key";
+/// Start of every key, so that leaked keys are easy to search for.
+pub const API_KEY_PREFIX: &str = "qa_";
+pub const MAX_API_KEY_NAME_LENGTH: usize = 100;
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ApiKeyId(pub i32);
+
+/// API key of an account, as listed. Only the hash of the key is stored, so
+/// the key itself is shown once, when it's created.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    pub account_id: AccountId,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// In UTC.
+    pub created_on: NaiveDateTime,
+    /// In UTC, `None` until the key is used.
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+/// Body of `POST /me/api-keys`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewApiKey {
+    /// Reminds what the key is for, like the name of a bot.
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// Reply of `POST /me/api-keys`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// Checks that the key has a name, in characters, and some scopes.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::api_key::{check_api_key, NewApiKey};
+/// use rust_web_dev::types::scope::Scope;
+///
+/// let key = NewApiKey {
+///     name: "Answer bot".to_string(),
+///     scopes: vec![Scope::AnswersWrite],
+/// };
+/// assert!(check_api_key(&key).is_ok());
+/// let key = NewApiKey {
+///     name: "Answer bot".to_string(),
+///     scopes: vec![],
+/// };
+/// assert!(check_api_key(&key).is_err());
+/// ```
+pub fn check_api_key(new_key: &NewApiKey) -> Result<(), Error> {
+    let length = new_key.name.trim().chars().count();
+    if length == 0 || length > MAX_API_KEY_NAME_LENGTH {
+        return Err(Error::InvalidParameter(format!(
+            "name has 1 to {} characters",
+            MAX_API_KEY_NAME_LENGTH
+        )));
+    }
+    if new_key.scopes.is_empty() {
+        return Err(Error::InvalidParameter(
+            "an API key needs at least one scope".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use handle_errors::Error;
+
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+const MAX_CONTENT_LENGTH: usize = 600;
+
+/// Short remark on a question or an answer.
+/// Exactly one of `question_id` and `answer_id` is set.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Comment {
+    pub id: CommentId,
+    pub content: String,
+    pub question_id: Option<QuestionId>,
+    pub answer_id: Option<AnswerId>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct CommentId(pub i32);
+
+/// Post a comment is written on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommentTarget {
+    Question(QuestionId),
+    Answer(AnswerId),
+}
+
+/// Body of `POST` and `PUT` on `/questions/{id}/comments` and
+/// `/answers/{id}/comments`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewComment {
+    pub content: String,
+}
+
+/// Checks the length of the content of a new or edited comment.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::comment::check_comment;
+///
+/// assert!(check_comment("Which version of warp?").is_ok());
+/// assert!(check_comment(" ").is_err());
+/// ```
+pub fn check_comment(content: &str) -> Result<(), Error> {
+    let length = content.trim().chars().count();
+    if length == 0 || length > MAX_CONTENT_LENGTH {
+        return Err(Error::InvalidParameter(format!(
+            "comments have 1 to {} characters, got {}",
+            MAX_CONTENT_LENGTH, length
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use handle_errors::Error;
+
+use crate::types::account::AccountId;
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+/// Layout of an export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One row per line, tagged with the table it comes from.
+    JsonLines,
+    /// Rows of a single table, with a header line.
+    Csv,
+    /// Questions with their answers, one per line, in the layout read by
+    /// the import.
+    Archive,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "jsonl" => Ok(ExportFormat::JsonLines),
+            "csv" => Ok(ExportFormat::Csv),
+            "archive" => Ok(ExportFormat::Archive),
+            _ => Err(format!("expected jsonl, csv or archive, got {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportTable {
+    Accounts,
+    Questions,
+    Answers,
+}
+
+impl FromStr for ExportTable {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "accounts" => Ok(ExportTable::Accounts),
+            "questions" => Ok(ExportTable::Questions),
+            "answers" => Ok(ExportTable::Answers),
+            _ => Err(format!(
+                "expected accounts, questions or answers, got {}",
+                value
+            )),
+        }
+    }
+}
+
+/// What to export.
+#[derive(Debug, PartialEq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Only export this table; every table when `None`.
+    /// Ignored by the archive, which always holds questions and answers.
+    pub table: Option<ExportTable>,
+    /// Password hashes are left out of the accounts unless this is set.
+    pub include_passwords: bool,
+}
+
+/// Extract the export options from the `/admin/export` query parameters
+/// # Example query
+/// `/admin/export?format=csv&table=questions`
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rust_web_dev::types::export::{extract_export_options, ExportFormat, ExportTable};
+///
+/// let mut query = HashMap::new();
+/// query.insert("format".to_string(), "csv".to_string());
+/// query.insert("table".to_string(), "questions".to_string());
+/// let options = extract_export_options(query).unwrap();
+/// assert_eq!(options.format, ExportFormat::Csv);
+/// assert_eq!(options.table, Some(ExportTable::Questions));
+/// assert!(!options.include_passwords);
+/// ```
+pub fn extract_export_options(params: HashMap<String, String>) -> Result<ExportOptions, Error> {
+    let format = match params.get("format") {
+        Some(format) => format.parse().map_err(Error::InvalidParameter)?,
+        None => ExportFormat::JsonLines,
+    };
+    let table = match params.get("table") {
+        Some(table) => Some(table.parse().map_err(Error::InvalidParameter)?),
+        None => None,
+    };
+    let include_passwords = match params.get("include_passwords").map(String::as_str) {
+        Some("true") => true,
+        Some("false") | None => false,
+        Some(value) => {
+            return Err(Error::InvalidParameter(format!(
+                "expected true or false for include_passwords, got {}",
+                value
+            )))
+        }
+    };
+    if format == ExportFormat::Csv && table.is_none() {
+        return Err(Error::InvalidParameter(
+            "csv exports need a table".to_string(),
+        ));
+    }
+    Ok(ExportOptions {
+        format,
+        table,
+        include_passwords,
+    })
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AccountRecord {
+    pub id: AccountId,
+    pub email: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct QuestionRecord {
+    pub id: QuestionId,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    pub accepted_answer_id: Option<AnswerId>,
+    pub account_id: AccountId,
+    /// Set for deleted questions, which are exported with their answers.
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AnswerRecord {
+    pub id: AnswerId,
+    pub content: String,
+    pub question_id: QuestionId,
+    pub account_id: AccountId,
+}
+
+/// Line of a JSON Lines export.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "table", rename_all = "lowercase")]
+pub enum ExportRecord {
+    Accounts(AccountRecord),
+    Questions(QuestionRecord),
+    Answers(AnswerRecord),
+}
+
+#[cfg(test)]
+mod export_options_tests {
+    use super::{extract_export_options, Error, HashMap};
+
+    #[test]
+    fn csv_needs_a_table() {
+        let mut params = HashMap::new();
+        params.insert(String::from("format"), String::from("csv"));
+        let result = format!("{}", extract_export_options(params).unwrap_err());
+        let expected = format!(
+            "{}",
+            Error::InvalidParameter("csv exports need a table".to_string())
+        );
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn unknown_format() {
+        let mut params = HashMap::new();
+        params.insert(String::from("format"), String::from("xml"));
+        let result = format!("{}", extract_export_options(params).unwrap_err());
+        assert_eq!(
+            result,
+            "Invalid parameter: expected jsonl, csv or archive, got xml"
+        );
+    }
+}
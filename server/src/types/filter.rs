@@ -0,0 +1,237 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+
+use handle_errors::Error;
+
+use crate::types::account::AccountId;
+use crate::types::pagination::{extract_keyset, extract_pagination, Keyset, Pagination};
+use crate::types::question::QuestionOrder;
+
+// Parameters understood by `GET /questions`, besides `limit` and `offset`.
+const QUESTION_PARAMETERS: [&str; 9] = [
+    "tags",
+    "tag_match",
+    "author",
+    "created_after",
+    "created_before",
+    "has_answers",
+    "sort",
+    "after",
+    "before",
+];
+
+/// How the tags of a `QuestionFilter` are matched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TagMatch {
+    /// The question has every tag.
+    #[default]
+    All,
+    /// The question has at least one of the tags.
+    Any,
+}
+
+/// Conditions the questions returned by `GET /questions` have to meet.
+/// Conditions left to `None` aren't checked.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QuestionFilter {
+    pub tags: Option<Vec<String>>,
+    pub tag_match: TagMatch,
+    /// Account that asked the question.
+    pub author: Option<AccountId>,
+    /// Creation times are in UTC and both bounds are excluded.
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+    pub has_answers: Option<bool>,
+}
+
+/// Query of `GET /questions`.
+#[derive(Debug, Default, PartialEq)]
+pub struct QuestionQuery {
+    pub filter: QuestionFilter,
+    pub order: QuestionOrder,
+    pub pagination: Pagination,
+    /// Set when the questions are paginated with cursors rather than
+    /// `pagination`.
+    pub keyset: Option<Keyset>,
+}
+
+/// Extract the filter, the order and the pagination from the `/questions`
+/// query parameters
+/// # Example query
+/// `/questions?tags=rust,warp&tag_match=any&has_answers=false&sort=score`
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rust_web_dev::types::filter::{extract_question_query, TagMatch};
+/// use rust_web_dev::types::question::QuestionOrder;
+///
+/// let mut query = HashMap::new();
+/// query.insert("tags".to_string(), "rust,warp".to_string());
+/// query.insert("tag_match".to_string(), "any".to_string());
+/// query.insert("created_after".to_string(), "2023-06-01".to_string());
+/// query.insert("sort".to_string(), "answers".to_string());
+/// let query = extract_question_query(query).unwrap();
+/// assert_eq!(query.filter.tags, Some(vec!["rust".to_string(), "warp".to_string()]));
+/// assert_eq!(query.filter.tag_match, TagMatch::Any);
+/// assert_eq!(query.filter.created_after.unwrap().to_string(), "2023-06-01 00:00:00");
+/// assert_eq!(query.order, QuestionOrder::AnswerCount);
+/// assert_eq!(query.pagination.limit, None);
+/// ```
+pub fn extract_question_query(mut params: HashMap<String, String>) -> Result<QuestionQuery, Error> {
+    let mut unknown: Vec<&String> = params
+        .keys()
+        .filter(|key| {
+            !QUESTION_PARAMETERS.contains(&key.as_str()) && *key != "limit" && *key != "offset"
+        })
+        .collect();
+    if !unknown.is_empty() {
+        unknown.sort();
+        return Err(Error::InvalidParameter(format!(
+            "unknown parameter {}, expected limit, offset or one of {}",
+            unknown[0],
+            QUESTION_PARAMETERS.join(", ")
+        )));
+    }
+    let tags = params.remove("tags").map(|tags| {
+        tags.split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    });
+    let tag_match = match params.remove("tag_match").as_deref() {
+        Some("all") | None => TagMatch::All,
+        Some("any") => TagMatch::Any,
+        Some(value) => {
+            return Err(Error::InvalidParameter(format!(
+                "expected all or any for tag_match, got {}",
+                value
+            )))
+        }
+    };
+    let author = match params.remove("author") {
+        Some(author) => Some(AccountId(author.parse::<i32>().map_err(Error::ParseError)?)),
+        None => None,
+    };
+    let created_after = match params.remove("created_after") {
+        Some(value) => Some(parse_time("created_after", &value)?),
+        None => None,
+    };
+    let created_before = match params.remove("created_before") {
+        Some(value) => Some(parse_time("created_before", &value)?),
+        None => None,
+    };
+    let has_answers = match params.remove("has_answers").as_deref() {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        None => None,
+        Some(value) => {
+            return Err(Error::InvalidParameter(format!(
+                "expected true or false for has_answers, got {}",
+                value
+            )))
+        }
+    };
+    let order = match params.remove("sort") {
+        Some(sort) => sort.parse().map_err(Error::InvalidParameter)?,
+        None => QuestionOrder::default(),
+    };
+    let keyset = extract_keyset(&mut params, order.keyset_order())?;
+    let mut pagination = Pagination::default();
+    if !params.is_empty() {
+        pagination = extract_pagination(params)?;
+    }
+    Ok(QuestionQuery {
+        filter: QuestionFilter {
+            tags,
+            tag_match,
+            author,
+            created_after,
+            created_before,
+            has_answers,
+        },
+        order,
+        pagination,
+        keyset,
+    })
+}
+
+/// Reads an RFC 3339 time, or a date standing for its midnight, in UTC.
+fn parse_time(name: &str, value: &str) -> Result<NaiveDateTime, Error> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.naive_utc());
+    }
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_hms_opt(0, 0, 0).expect("midnight is a valid time")),
+        Err(_) => Err(Error::InvalidParameter(format!(
+            "expected a date or an RFC 3339 time for {}, got {}",
+            name, value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use super::{extract_question_query, Error, HashMap};
+
+    #[test]
+    fn unknown_parameter() {
+        let mut params = HashMap::new();
+        params.insert(String::from("limit"), String::from("1"));
+        params.insert(String::from("page"), String::from("2"));
+        let result = format!("{}", extract_question_query(params).unwrap_err());
+        assert_eq!(
+            result,
+            "Invalid parameter: unknown parameter page, expected limit, offset or one of \
+             tags, tag_match, author, created_after, created_before, has_answers, sort, after, before"
+        );
+    }
+
+    #[test]
+    fn created_range_in_utc() {
+        let mut params = HashMap::new();
+        params.insert(
+            String::from("created_after"),
+            String::from("2023-06-01T12:00:00+02:00"),
+        );
+        params.insert(String::from("created_before"), String::from("2023-07-01"));
+        let query = extract_question_query(params).unwrap();
+        assert_eq!(
+            query.filter.created_after.unwrap().to_string(),
+            "2023-06-01 10:00:00"
+        );
+        assert_eq!(
+            query.filter.created_before.unwrap().to_string(),
+            "2023-07-01 00:00:00"
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        for (name, value, expected) in [
+            (
+                "created_after",
+                "yesterday",
+                "Invalid parameter: expected a date or an RFC 3339 time for created_after, got yesterday",
+            ),
+            (
+                "has_answers",
+                "yes",
+                "Invalid parameter: expected true or false for has_answers, got yes",
+            ),
+            (
+                "author",
+                "me",
+                "Cannot parse parameter: invalid digit found in string",
+            ),
+        ] {
+            let mut params = HashMap::new();
+            params.insert(name.to_string(), value.to_string());
+            let result = format!("{}", extract_question_query(params).unwrap_err());
+            assert_eq!(result, expected);
+        }
+        let mut params = HashMap::new();
+        params.insert(String::from("limit"), String::from("1"));
+        let result = format!("{}", extract_question_query(params).unwrap_err());
+        assert_eq!(result, format!("{}", Error::MissingParameters));
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+/// Question read from an import file, together with its answers.
+/// When `id` is set, the question keeps that id and it is used to find
+/// duplicates; otherwise the database assigns a new one.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ImportQuestion {
+    pub id: Option<QuestionId>,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    /// Id of one of `answers`.
+    pub accepted_answer_id: Option<AnswerId>,
+    /// Deletion time of a question exported deleted, in UTC. Without it, a
+    /// question is inserted live and an updated one keeps its deletion time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub answers: Vec<ImportAnswer>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ImportAnswer {
+    pub id: Option<AnswerId>,
+    pub content: String,
+}
+
+/// What to do with a record whose id already exists in the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnDuplicate {
+    Skip,
+    Upsert,
+}
+
+impl FromStr for OnDuplicate {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "skip" => Ok(OnDuplicate::Skip),
+            "upsert" => Ok(OnDuplicate::Upsert),
+            _ => Err(format!("expected skip or upsert, got {}", value)),
+        }
+    }
+}
+
+/// Result of importing one question.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Inserted,
+    Updated,
+    Skipped,
+}
+
+/// Summary of an import run.
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct ImportReport {
+    pub inserted: u32,
+    pub updated: u32,
+    pub skipped: u32,
+    pub failures: Vec<RecordFailure>,
+}
+
+/// A record that couldn't be imported.
+/// `record` locates it in the input, e.g. `line 3` or `key "0"`.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RecordFailure {
+    pub record: String,
+    pub message: String,
+}
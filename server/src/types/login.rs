@@ -0,0 +1,111 @@
+use chrono::{Duration, NaiveDateTime};
+use std::net::IpAddr;
+
+use crate::types::account::AccountId;
+
+/// Failures older than this are forgotten.
+pub const LOGIN_FAILURE_WINDOW_HOURS: i64 = 24;
+// The first delay after the free failures, doubled at each failure up to
+// the lockout.
+const FIRST_DELAY_SECONDS: i64 = 15;
+const LOCKOUT_MINUTES: i64 = 15;
+
+/// What failed logins are counted for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoginKey {
+    Account(AccountId),
+    /// Several accounts can log in from the same address, so it's allowed
+    /// more failures.
+    Ip(IpAddr),
+}
+
+impl LoginKey {
+    /// Failures allowed before logins are delayed.
+    pub fn free_failures(&self) -> i32 {
+        match self {
+            LoginKey::Account(_) => 5,
+            LoginKey::Ip(_) => 20,
+        }
+    }
+}
+
+impl std::fmt::Display for LoginKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoginKey::Account(account_id) => write!(f, "account:{}", account_id.0),
+            LoginKey::Ip(ip) => write!(f, "ip:{}", ip),
+        }
+    }
+}
+
+/// Failed logins of a key since its last successful login, or within the
+/// failure window.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoginAttempts {
+    pub failures: i32,
+    /// In UTC.
+    pub last_failure_at: NaiveDateTime,
+}
+
+impl LoginAttempts {
+    /// Until when logins of the key are refused: past the free failures, the
+    /// delay doubles at each failure up to a 15 minutes lockout.
+    /// # Example usage
+    /// ```rust
+    /// use chrono::{Duration, Utc};
+    /// use rust_web_dev::types::account::AccountId;
+    /// use rust_web_dev::types::login::{LoginAttempts, LoginKey};
+    ///
+    /// let key = LoginKey::Account(AccountId(1));
+    /// let now = Utc::now().naive_utc();
+    /// let attempts = LoginAttempts { failures: 6, last_failure_at: now };
+    /// assert_eq!(attempts.locked_until(&key), Some(now + Duration::seconds(30)));
+    /// ```
+    pub fn locked_until(&self, key: &LoginKey) -> Option<NaiveDateTime> {
+        let extra_failures = self.failures - key.free_failures();
+        if extra_failures < 0 {
+            return None;
+        }
+        let lockout = Duration::minutes(LOCKOUT_MINUTES);
+        // Past 2^6 the delay is longer than the lockout anyway.
+        let delay = match extra_failures {
+            0..=6 => Duration::seconds(FIRST_DELAY_SECONDS << extra_failures).min(lockout),
+            _ => lockout,
+        };
+        Some(self.last_failure_at + delay)
+    }
+}
+
+#[cfg(test)]
+mod login_tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn delays_double_up_to_the_lockout() {
+        let now = Utc::now().naive_utc();
+        let key = LoginKey::Account(AccountId(1));
+        let delay = |failures: i32| {
+            let attempts = LoginAttempts {
+                failures,
+                last_failure_at: now,
+            };
+            attempts
+                .locked_until(&key)
+                .map(|until| (until - now).num_seconds())
+        };
+        assert_eq!(delay(4), None);
+        assert_eq!(delay(5), Some(15));
+        assert_eq!(delay(6), Some(30));
+        assert_eq!(delay(9), Some(240));
+        assert_eq!(delay(11), Some(900));
+        assert_eq!(delay(100), Some(900));
+        let ip = LoginKey::Ip("127.0.0.1".parse().unwrap());
+        let attempts = LoginAttempts {
+            failures: 5,
+            last_failure_at: now,
+        };
+        assert_eq!(attempts.locked_until(&ip), None);
+        assert_eq!(ip.to_string(), "ip:127.0.0.1");
+    }
+}
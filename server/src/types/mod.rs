@@ -1,4 +1,18 @@
 pub mod account;
 pub mod answer;
+pub mod api_key;
+pub mod comment;
+pub mod export;
+pub mod filter;
+pub mod import;
+pub mod login;
+pub mod oidc;
 pub mod pagination;
 pub mod question;
+pub mod revision;
+pub mod scope;
+pub mod search;
+pub mod tag;
+pub mod token;
+pub mod totp;
+pub mod vote;
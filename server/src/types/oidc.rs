@@ -0,0 +1,166 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use handle_errors::Error;
+
+/// Scopes asked to the identity providers that don't configure theirs.
+pub const DEFAULT_OIDC_SCOPES: &str = "openid email profile";
+
+/// Identity provider as configured, one per issuer.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OidcProviderConfig {
+    /// Name in `GET /auth/oidc/start?provider=`.
+    pub name: String,
+    /// Issuer URL, where the discovery document is found.
+    pub issuer: String,
+    pub client_id: String,
+    /// Sent in the body of the token requests. Public clients have none and
+    /// rely on PKCE alone.
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// URL of `GET /auth/oidc/callback`, as registered at the provider.
+    pub redirect_uri: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: String,
+}
+
+fn default_scopes() -> String {
+    DEFAULT_OIDC_SCOPES.to_string()
+}
+
+/// Query of `GET /auth/oidc/start`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OidcStart {
+    pub provider: String,
+}
+
+/// Query of `GET /auth/oidc/callback`, where the provider sends back the
+/// browser with a code, or an error.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OidcCallback {
+    pub state: String,
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Login started at a provider, as stored by hash of its state like the
+/// tokens. It's used once, when the provider calls back.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OidcState {
+    pub state_hash: String,
+    /// Name of the provider.
+    pub provider: String,
+    /// PKCE verifier of the code challenge sent to the provider.
+    pub code_verifier: String,
+    /// Expected in the ID token, so that it can't be replayed.
+    pub nonce: String,
+    /// In UTC.
+    pub expires_at: NaiveDateTime,
+}
+
+/// `aud` claim, a client id or a list of them.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    pub fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Audience::One(aud) => aud == client_id,
+            Audience::Many(auds) => auds.iter().any(|aud| aud == client_id),
+        }
+    }
+}
+
+/// Claims of an ID token used to sign in.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    /// Id of the account at the provider, which never changes.
+    pub sub: String,
+    pub aud: Audience,
+    /// Seconds since the epoch.
+    pub exp: i64,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// PKCE challenge of a verifier, with the `S256` method.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::oidc::code_challenge;
+///
+/// // From RFC 7636, appendix B.
+/// assert_eq!(
+///     code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+///     "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+/// );
+/// ```
+pub fn code_challenge(code_verifier: &str) -> String {
+    base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+/// Claims of an ID token, without checking its signature: it comes from
+/// the token endpoint of the provider, over TLS, which OpenID Connect Core
+/// (3.1.3.7) accepts in place of the signature.
+pub fn decode_id_token(id_token: &str) -> Result<IdTokenClaims, Error> {
+    let payload = id_token.split('.').nth(1).ok_or(Error::Unauthorized)?;
+    let payload =
+        base64::decode_config(payload, base64::URL_SAFE_NO_PAD).map_err(|_| Error::Unauthorized)?;
+    serde_json::from_slice(&payload).map_err(|_| Error::Unauthorized)
+}
+
+/// Checks that the ID token was issued by the provider to this client, for
+/// this login, and hasn't expired at `now` (in seconds).
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::oidc::{check_id_token, Audience, IdTokenClaims};
+///
+/// let claims = IdTokenClaims {
+///     iss: "https://sso.example.com".to_string(),
+///     sub: "248289761001".to_string(),
+///     aud: Audience::One("qa".to_string()),
+///     exp: 1_700_000_300,
+///     nonce: Some("n-0S6_WzA2Mj".to_string()),
+///     email: None,
+///     email_verified: false,
+///     name: None,
+/// };
+/// let check = |nonce, now| {
+///     check_id_token(&claims, "https://sso.example.com", "qa", nonce, now)
+/// };
+/// assert!(check("n-0S6_WzA2Mj", 1_700_000_000).is_ok());
+/// assert!(check("another nonce", 1_700_000_000).is_err());
+/// assert!(check("n-0S6_WzA2Mj", 1_700_000_301).is_err());
+/// ```
+pub fn check_id_token(
+    claims: &IdTokenClaims,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<(), Error> {
+    if claims.iss.trim_end_matches('/') != issuer.trim_end_matches('/')
+        || !claims.aud.contains(client_id)
+        || claims.nonce.as_deref() != Some(nonce)
+        || claims.exp < now
+    {
+        return Err(Error::Unauthorized);
+    }
+    Ok(())
+}
@@ -1,12 +1,15 @@
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 use handle_errors::Error;
 
 /// Pagination struct which is getting extract
 /// from query params
-#[derive(Default, Debug, PartialEq)]
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
 pub struct Pagination {
-    /// The index of the last item which has to be returned
+    /// The maximum number of items which have to be returned
     // Default limit = None
     pub limit: Option<u32>,
     /// The index of the first item which has to be returned
@@ -50,20 +53,388 @@ pub fn extract_pagination(params: HashMap<String, String>) -> Result<Pagination,
             .unwrap()
             .parse::<u32>()
             .map_err(Error::ParseError)?;
-        if let Some(limit_u32) = limit {
-            if offset > limit_u32 {
-                return Err(Error::StartGreaterThanEnd);
-            }
-        }
         return Ok(Pagination { limit, offset });
     }
 
     Err(Error::MissingParameters)
 }
 
+/// Items of a list paginated with `limit` and `offset`, along with the
+/// size of the whole list.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub limit: Option<u32>,
+    pub offset: u32,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, total: i64, pagination: Pagination) -> Self {
+        Page {
+            items,
+            total,
+            limit: pagination.limit,
+            offset: pagination.offset,
+        }
+    }
+
+    /// Paginations of the pages around this one, with their link relation:
+    /// `first` and `last` always, `prev` and `next` unless this page is at
+    /// an end of the list. Lists without a limit fit in a single page.
+    pub fn links(&self) -> Vec<(&'static str, Pagination)> {
+        let limit = match self.limit {
+            Some(limit) if limit > 0 => limit,
+            _ => return Vec::new(),
+        };
+        let total = u32::try_from(self.total).unwrap_or(u32::MAX);
+        let page = |offset| Pagination {
+            limit: Some(limit),
+            offset,
+        };
+        let mut links = vec![("first", page(0))];
+        if self.offset > 0 {
+            links.push(("prev", page(self.offset.saturating_sub(limit))));
+        }
+        if self.offset.saturating_add(limit) < total {
+            links.push(("next", page(self.offset + limit)));
+        }
+        links.push(("last", page(total.saturating_sub(1) / limit * limit)));
+        links
+    }
+}
+
+/// Most rows of a page of a list paginated with cursors.
+pub const MAX_KEYSET_LIMIT: u32 = 100;
+
+/// Position of a row in a list paginated with cursors: the sort key of
+/// the row, when the list isn't sorted by id only, and its id.
+/// Sent to the clients as an opaque token, along with the name of the order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub key: Option<i64>,
+    pub id: i32,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CursorToken {
+    s: String,
+    k: Option<i64>,
+    id: i32,
+}
+
+impl Cursor {
+    pub fn encode(&self, order: &KeysetOrder) -> String {
+        let token = CursorToken {
+            s: order.name.to_string(),
+            k: self.key,
+            id: self.id,
+        };
+        let json = serde_json::to_vec(&token).expect("cursors serialize to JSON");
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Fails when the token isn't a cursor of a list in this order.
+    pub fn decode(token: &str, order: &KeysetOrder) -> Result<Self, Error> {
+        let invalid = || Error::InvalidParameter(format!("invalid cursor {}", token));
+        let json = base64::decode_config(token, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let token: CursorToken = serde_json::from_slice(&json).map_err(|_| invalid())?;
+        if token.s != order.name {
+            return Err(Error::InvalidParameter(format!(
+                "cursor of a list sorted by {}, not by {}",
+                token.s, order.name
+            )));
+        }
+        Ok(Cursor {
+            key: token.k,
+            id: token.id,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            SortDirection::Ascending => ordering,
+            SortDirection::Descending => ordering.reverse(),
+        }
+    }
+}
+
+/// Order of a list paginated with cursors: by sort key, when there is one,
+/// and then by id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeysetOrder {
+    /// Name of the order in the cursors, which are only valid in it.
+    pub name: &'static str,
+    pub key: Option<SortDirection>,
+    pub id: SortDirection,
+}
+
+impl KeysetOrder {
+    /// Lists sorted by id only.
+    pub const BY_ID: KeysetOrder = KeysetOrder {
+        name: "id",
+        key: None,
+        id: SortDirection::Ascending,
+    };
+
+    /// Compares the positions of two rows in the list.
+    pub fn compare(&self, a: &Cursor, b: &Cursor) -> Ordering {
+        let key = match self.key {
+            Some(direction) => direction.apply(a.key.cmp(&b.key)),
+            None => Ordering::Equal,
+        };
+        key.then(self.id.apply(a.id.cmp(&b.id)))
+    }
+}
+
+/// Cursor pagination extracted from the `after` or `before` and `limit`
+/// query parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyset {
+    /// Rows are taken right after, or right before, this position.
+    /// `None` starts from the beginning of the list.
+    pub cursor: Option<Cursor>,
+    /// Whether the rows come before the cursor.
+    pub backward: bool,
+    pub limit: u32,
+    /// Order of the list, in which the cursors are encoded.
+    pub order: KeysetOrder,
+}
+
+/// Rows of a list paginated with cursors.
+/// `next` is passed as `after` to get the following rows, `prev` as
+/// `before` to get the preceding ones. They are `None` at the ends of the
+/// list.
+#[derive(Debug)]
+pub struct KeysetPage<T> {
+    pub items: Vec<T>,
+    pub next: Option<Cursor>,
+    pub prev: Option<Cursor>,
+    /// Order the cursors are encoded in.
+    pub order: KeysetOrder,
+}
+
+impl<T: Serialize> Serialize for KeysetPage<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let encode = |cursor: &Option<Cursor>| cursor.as_ref().map(|c| c.encode(&self.order));
+        let mut page = serializer.serialize_struct("KeysetPage", 3)?;
+        page.serialize_field("items", &self.items)?;
+        page.serialize_field("next", &encode(&self.next))?;
+        page.serialize_field("prev", &encode(&self.prev))?;
+        page.end()
+    }
+}
+
+impl<T> KeysetPage<T> {
+    /// Builds the page from up to `limit + 1` rows, the closest to the
+    /// cursor first. The extra row only tells that the list goes on.
+    pub fn from_rows(mut rows: Vec<(Cursor, T)>, keyset: &Keyset) -> Self {
+        let more = rows.len() > keyset.limit as usize;
+        rows.truncate(keyset.limit as usize);
+        if keyset.backward {
+            rows.reverse();
+        }
+        let first = rows.first().map(|(cursor, _)| cursor.clone());
+        let last = rows.last().map(|(cursor, _)| cursor.clone());
+        let (next, prev) = if keyset.backward {
+            (last, if more { first } else { None })
+        } else {
+            let prev = if keyset.cursor.is_some() { first } else { None };
+            (if more { last } else { None }, prev)
+        };
+        KeysetPage {
+            items: rows.into_iter().map(|(_, item)| item).collect(),
+            next,
+            prev,
+            order: keyset.order,
+        }
+    }
+}
+
+/// Extract the cursor pagination of a list in `order` from the query
+/// parameters, when `after` or `before` is given. An empty `after` starts
+/// from the beginning. Cursors of lists in other orders are rejected, and
+/// so are limits over `MAX_KEYSET_LIMIT`.
+/// # Example query
+/// `/questions?after=eyJzIjoiaWQiLCJrIjpudWxsLCJpZCI6NH0&limit=10`
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rust_web_dev::types::pagination::{extract_keyset, Cursor, KeysetOrder};
+///
+/// let mut query = HashMap::new();
+/// let cursor = Cursor { key: None, id: 4 };
+/// query.insert("after".to_string(), cursor.encode(&KeysetOrder::BY_ID));
+/// query.insert("limit".to_string(), "10".to_string());
+/// let keyset = extract_keyset(&mut query, KeysetOrder::BY_ID).unwrap().unwrap();
+/// assert_eq!(keyset.cursor, Some(Cursor { key: None, id: 4 }));
+/// assert_eq!(keyset.limit, 10);
+/// assert!(query.is_empty());
+/// ```
+pub fn extract_keyset(
+    params: &mut HashMap<String, String>,
+    order: KeysetOrder,
+) -> Result<Option<Keyset>, Error> {
+    let (cursor, backward) = match (params.remove("after"), params.remove("before")) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
+            return Err(Error::InvalidParameter(
+                "after and before can't be combined".to_string(),
+            ))
+        }
+        (Some(after), None) if after.is_empty() => (None, false),
+        (Some(after), None) => (Some(Cursor::decode(&after, &order)?), false),
+        (None, Some(before)) => (Some(Cursor::decode(&before, &order)?), true),
+    };
+    if params.contains_key("offset") {
+        return Err(Error::InvalidParameter(
+            "offset can't be combined with a cursor".to_string(),
+        ));
+    }
+    let limit = params
+        .remove("limit")
+        .ok_or(Error::MissingParameters)?
+        .parse::<u32>()
+        .map_err(Error::ParseError)?;
+    if limit > MAX_KEYSET_LIMIT {
+        return Err(Error::InvalidParameter(format!(
+            "limit is at most {}, got {}",
+            MAX_KEYSET_LIMIT, limit
+        )));
+    }
+    Ok(Some(Keyset {
+        cursor,
+        backward,
+        limit,
+        order,
+    }))
+}
+
+#[cfg(test)]
+mod keyset_tests {
+    use super::{extract_keyset, Cursor, HashMap, Keyset, KeysetOrder, KeysetPage, SortDirection};
+
+    fn rows(ids: &[i32]) -> Vec<(Cursor, i32)> {
+        ids.iter()
+            .map(|id| (Cursor { key: None, id: *id }, *id))
+            .collect()
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor {
+            key: Some(-3),
+            id: 12,
+        };
+        let order = KeysetOrder::BY_ID;
+        assert_eq!(
+            Cursor::decode(&cursor.encode(&order), &order).unwrap(),
+            cursor
+        );
+        let error = format!("{}", Cursor::decode("not a cursor", &order).unwrap_err());
+        assert_eq!(error, "Invalid parameter: invalid cursor not a cursor");
+    }
+
+    #[test]
+    fn cursor_of_another_order() {
+        let score = KeysetOrder {
+            name: "score",
+            key: Some(SortDirection::Descending),
+            id: SortDirection::Ascending,
+        };
+        let answers = KeysetOrder {
+            name: "answers",
+            ..score
+        };
+        let mut params = HashMap::new();
+        let cursor = Cursor {
+            key: Some(3),
+            id: 12,
+        };
+        params.insert(String::from("after"), cursor.encode(&score));
+        params.insert(String::from("limit"), String::from("10"));
+        let error = format!("{}", extract_keyset(&mut params, answers).unwrap_err());
+        assert_eq!(
+            error,
+            "Invalid parameter: cursor of a list sorted by score, not by answers"
+        );
+    }
+
+    #[test]
+    fn limit_is_capped() {
+        let mut params = HashMap::new();
+        params.insert(String::from("after"), String::new());
+        params.insert(String::from("limit"), String::from("1000"));
+        let error = format!(
+            "{}",
+            extract_keyset(&mut params, KeysetOrder::BY_ID).unwrap_err()
+        );
+        assert_eq!(error, "Invalid parameter: limit is at most 100, got 1000");
+    }
+
+    #[test]
+    fn offset_and_cursor() {
+        let mut params = HashMap::new();
+        params.insert(String::from("after"), String::new());
+        params.insert(String::from("limit"), String::from("1"));
+        params.insert(String::from("offset"), String::from("1"));
+        let error = format!(
+            "{}",
+            extract_keyset(&mut params, KeysetOrder::BY_ID).unwrap_err()
+        );
+        assert_eq!(
+            error,
+            "Invalid parameter: offset can't be combined with a cursor"
+        );
+    }
+
+    #[test]
+    fn pages_forward_and_backward() {
+        let first = Keyset {
+            cursor: None,
+            backward: false,
+            limit: 2,
+            order: KeysetOrder::BY_ID,
+        };
+        let page = KeysetPage::from_rows(rows(&[1, 2, 3]), &first);
+        assert_eq!(page.items, [1, 2]);
+        assert_eq!(page.next.as_ref().map(|cursor| cursor.id), Some(2));
+        assert_eq!(page.prev, None);
+
+        let last = Keyset {
+            cursor: page.next,
+            ..first.clone()
+        };
+        let page = KeysetPage::from_rows(rows(&[3]), &last);
+        assert_eq!(page.items, [3]);
+        assert_eq!(page.next, None);
+        assert_eq!(page.prev.as_ref().map(|cursor| cursor.id), Some(3));
+
+        // Rows before the cursor come closest first.
+        let previous = Keyset {
+            cursor: page.prev,
+            backward: true,
+            limit: 1,
+            order: KeysetOrder::BY_ID,
+        };
+        let page = KeysetPage::from_rows(rows(&[2, 1]), &previous);
+        assert_eq!(page.items, [2]);
+        assert_eq!(page.next.as_ref().map(|cursor| cursor.id), Some(2));
+        assert_eq!(page.prev.as_ref().map(|cursor| cursor.id), Some(2));
+    }
+}
+
 #[cfg(test)]
 mod pagination_tests {
-    use super::{extract_pagination, Error, HashMap, Pagination};
+    use super::{extract_pagination, Error, HashMap, Page, Pagination};
 
     #[test]
     fn valid_pagination() {
@@ -123,4 +494,37 @@ mod pagination_tests {
 
         assert_eq!(pagination_result, expected);
     }
+
+    #[test]
+    fn offset_beyond_limit() {
+        let mut params = HashMap::new();
+        params.insert(String::from("limit"), String::from("10"));
+        params.insert(String::from("offset"), String::from("30"));
+        let pagination = extract_pagination(params).unwrap();
+        assert_eq!(pagination.offset, 30);
+    }
+
+    #[test]
+    fn page_links() {
+        let links = |limit, offset, total| {
+            let page = Page::<()>::new(Vec::new(), total, Pagination { limit, offset });
+            page.links()
+                .into_iter()
+                .map(|(rel, pagination)| (rel, pagination.offset))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            links(Some(10), 10, 25),
+            [("first", 0), ("prev", 0), ("next", 20), ("last", 20)]
+        );
+        assert_eq!(
+            links(Some(10), 0, 20),
+            [("first", 0), ("next", 10), ("last", 10)]
+        );
+        assert_eq!(
+            links(Some(10), 5, 0),
+            [("first", 0), ("prev", 0), ("last", 0)]
+        );
+        assert_eq!(links(None, 0, 25), []);
+    }
 }
@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::types::answer::AnswerId;
+use crate::types::pagination::{KeysetOrder, SortDirection};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Question {
@@ -6,6 +10,11 @@ pub struct Question {
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    /// Answer marked by the owner as the one that solved the question.
+    pub accepted_answer_id: Option<AnswerId>,
+    /// Sum of the votes. Computed by the store, ignored on updates.
+    #[serde(default)]
+    pub score: i64,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
@@ -17,3 +26,59 @@ pub struct NewQuestion {
     pub content: String,
     pub tags: Option<Vec<String>>,
 }
+
+/// Order of the questions returned by `GET /questions`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuestionOrder {
+    /// Oldest first.
+    #[default]
+    Id,
+    /// Newest first.
+    CreatedOn,
+    /// Highest score first, the oldest first among equal scores.
+    Score,
+    /// Most answered first, the oldest first among equal counts.
+    AnswerCount,
+}
+
+impl QuestionOrder {
+    /// How cursors are compared in this order. The sort keys are the
+    /// creation time in microseconds, the score and the answer count.
+    pub fn keyset_order(self) -> KeysetOrder {
+        match self {
+            QuestionOrder::Id => KeysetOrder::BY_ID,
+            QuestionOrder::CreatedOn => KeysetOrder {
+                name: "created_on",
+                key: Some(SortDirection::Descending),
+                id: SortDirection::Descending,
+            },
+            QuestionOrder::Score => KeysetOrder {
+                name: "score",
+                key: Some(SortDirection::Descending),
+                id: SortDirection::Ascending,
+            },
+            QuestionOrder::AnswerCount => KeysetOrder {
+                name: "answers",
+                key: Some(SortDirection::Descending),
+                id: SortDirection::Ascending,
+            },
+        }
+    }
+}
+
+impl FromStr for QuestionOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "id" => Ok(QuestionOrder::Id),
+            "created_on" => Ok(QuestionOrder::CreatedOn),
+            "score" => Ok(QuestionOrder::Score),
+            "answers" => Ok(QuestionOrder::AnswerCount),
+            _ => Err(format!(
+                "expected id, created_on, score or answers for sort, got {}",
+                value
+            )),
+        }
+    }
+}
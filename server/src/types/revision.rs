@@ -0,0 +1,273 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use handle_errors::Error;
+
+use crate::types::account::AccountId;
+use crate::types::question::QuestionId;
+
+/// Title, content and tags of a question before one of its edits.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Revision {
+    /// Position of the edit among the edits of the question, from 1.
+    pub number: i32,
+    pub question_id: QuestionId,
+    pub title: String,
+    pub content: String,
+    /// Names of the tags at the time, which may since have been merged into
+    /// other tags.
+    pub tags: Option<Vec<String>>,
+    /// Account that made the edit.
+    pub account_id: AccountId,
+    /// Time of the edit, in UTC.
+    pub created_on: NaiveDateTime,
+}
+
+/// Changes between two versions of a question, returned by
+/// `GET /questions/{id}/revisions/diff`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RevisionDiff {
+    pub from: i32,
+    /// `None` when compared with the current version of the question.
+    pub to: Option<i32>,
+    pub title: Vec<DiffLine>,
+    pub content: Vec<DiffLine>,
+    pub added_tags: Vec<String>,
+    pub removed_tags: Vec<String>,
+}
+
+/// Line of a diff, serialized as `{"op": "added", "text": "..."}`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", content = "text", rename_all = "lowercase")]
+pub enum DiffLine {
+    Kept(String),
+    Added(String),
+    Removed(String),
+}
+
+impl RevisionDiff {
+    /// Changes from the revision `from` to the given title, content and
+    /// tags, which belong to the revision `to` or to the current question.
+    pub fn new(
+        from: &Revision,
+        to: Option<i32>,
+        title: &str,
+        content: &str,
+        tags: &Option<Vec<String>>,
+    ) -> Self {
+        let old_tags = from.tags.as_deref().unwrap_or_default();
+        let new_tags = tags.as_deref().unwrap_or_default();
+        RevisionDiff {
+            from: from.number,
+            to,
+            title: diff_lines(&from.title, title),
+            content: diff_lines(&from.content, content),
+            added_tags: new_tags
+                .iter()
+                .filter(|tag| !old_tags.contains(tag))
+                .cloned()
+                .collect(),
+            removed_tags: old_tags
+                .iter()
+                .filter(|tag| !new_tags.contains(tag))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Extract the revisions to compare from the query parameters of
+/// `/questions/{id}/revisions/diff`
+/// # Example query
+/// `/questions/1/revisions/diff?from=1&to=3` compares the first and third
+/// revisions, `/questions/1/revisions/diff?from=1` compares the first
+/// revision with the current question.
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rust_web_dev::types::revision::extract_diff_range;
+///
+/// let mut query = HashMap::new();
+/// query.insert("from".to_string(), "1".to_string());
+/// assert_eq!(extract_diff_range(&query).unwrap(), (1, None));
+/// query.insert("to".to_string(), "3".to_string());
+/// assert_eq!(extract_diff_range(&query).unwrap(), (1, Some(3)));
+/// ```
+pub fn extract_diff_range(params: &HashMap<String, String>) -> Result<(i32, Option<i32>), Error> {
+    let from = match params.get("from") {
+        Some(from) => from.parse::<i32>().map_err(Error::ParseError)?,
+        None => return Err(Error::MissingParameters),
+    };
+    let to = match params.get("to") {
+        Some(to) => Some(to.parse::<i32>().map_err(Error::ParseError)?),
+        None => None,
+    };
+    Ok((from, to))
+}
+
+/// Most lines between the common first and last lines of two texts that are
+/// matched line by line. The matching table grows with the square of the
+/// lines, so longer changes are shown as removed, then added.
+pub const MAX_DIFF_LINES: usize = 500;
+
+/// Line by line diff of two texts, following their longest common
+/// subsequence of lines.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::revision::{diff_lines, DiffLine, MAX_DIFF_LINES};
+///
+/// let old = "a\n".repeat(MAX_DIFF_LINES + 1);
+/// let new = "b\n".repeat(MAX_DIFF_LINES + 1);
+/// let lines = diff_lines(&format!("top\n{}", old), &format!("top\n{}", new));
+/// assert_eq!(lines[0], DiffLine::Kept("top".to_string()));
+/// assert_eq!(lines[1], DiffLine::Removed("a".to_string()));
+/// assert_eq!(lines.last(), Some(&DiffLine::Added("b".to_string())));
+/// ```
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old
+        .iter()
+        .zip(&new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    lines.extend(
+        old[..prefix]
+            .iter()
+            .map(|line| DiffLine::Kept(line.to_string())),
+    );
+    let (old_changed, new_changed) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    if old_changed.len().max(new_changed.len()) > MAX_DIFF_LINES {
+        lines.extend(
+            old_changed
+                .iter()
+                .map(|line| DiffLine::Removed(line.to_string())),
+        );
+        lines.extend(
+            new_changed
+                .iter()
+                .map(|line| DiffLine::Added(line.to_string())),
+        );
+    } else {
+        common_lines(old_changed, new_changed, &mut lines);
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Kept(line.to_string())),
+    );
+    lines
+}
+
+fn common_lines(old: &[&str], new: &[&str], lines: &mut Vec<DiffLine>) {
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..].
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(DiffLine::Kept(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[i..]
+            .iter()
+            .map(|line| DiffLine::Removed(line.to_string())),
+    );
+    lines.extend(
+        new[j..]
+            .iter()
+            .map(|line| DiffLine::Added(line.to_string())),
+    );
+}
+
+#[cfg(test)]
+mod revision_tests {
+    use super::*;
+
+    fn revision(title: &str, content: &str, tags: &[&str]) -> Revision {
+        Revision {
+            number: 1,
+            question_id: QuestionId(1),
+            title: title.to_string(),
+            content: content.to_string(),
+            tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+            account_id: AccountId(1),
+            created_on: NaiveDateTime::from_timestamp_opt(0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn changed_lines() {
+        let lines = diff_lines("a\nb\nc", "a\nc\nd");
+        assert_eq!(
+            lines,
+            vec![
+                DiffLine::Kept("a".to_string()),
+                DiffLine::Removed("b".to_string()),
+                DiffLine::Kept("c".to_string()),
+                DiffLine::Added("d".to_string()),
+            ]
+        );
+        assert!(diff_lines("", "").is_empty());
+        assert_eq!(diff_lines("", "a"), vec![DiffLine::Added("a".to_string())]);
+    }
+
+    #[test]
+    fn long_changes_are_not_matched() {
+        let old = format!("a\n{}z", "b\nc\n".repeat(MAX_DIFF_LINES));
+        let new = format!("a\n{}z", "c\n".repeat(MAX_DIFF_LINES));
+        let lines = diff_lines(&old, &new);
+        assert_eq!(lines.len(), 1 + 3 * MAX_DIFF_LINES);
+        assert_eq!(lines[0], DiffLine::Kept("a".to_string()));
+        assert!(lines[1..2 * MAX_DIFF_LINES]
+            .iter()
+            .all(|line| matches!(line, DiffLine::Removed(_))));
+        assert_eq!(lines.last(), Some(&DiffLine::Kept("z".to_string())));
+        // Shorter ones still are.
+        let old = format!("a\n{}z", "b\nc\n".repeat(10));
+        let new = format!("a\n{}z", "c\n".repeat(10));
+        let lines = diff_lines(&old, &new);
+        assert!(!lines.iter().any(|line| matches!(line, DiffLine::Added(_))));
+    }
+
+    #[test]
+    fn changed_tags() {
+        let from = revision("Title", "Content", &["rust", "warp"]);
+        let tags = Some(vec!["rust".to_string(), "tokio".to_string()]);
+        let diff = RevisionDiff::new(&from, None, "Title", "Content", &tags);
+        assert_eq!(diff.added_tags, vec!["tokio".to_string()]);
+        assert_eq!(diff.removed_tags, vec!["warp".to_string()]);
+        assert_eq!(diff.title, vec![DiffLine::Kept("Title".to_string())]);
+        let diff = RevisionDiff::new(&from, Some(2), "Title", "Content", &None);
+        assert_eq!(diff.removed_tags.len(), 2);
+    }
+}
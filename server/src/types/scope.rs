@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::types::account::Role;
+
+/// What a session may be used for. Access tokens have the scopes asked for
+/// at login and API keys the ones they were created with, both within the
+/// scopes of the role of the account.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Scope {
+    /// Asks, edits, deletes and restores questions, and accepts answers.
+    #[serde(rename = "questions:write")]
+    QuestionsWrite,
+    #[serde(rename = "answers:write")]
+    AnswersWrite,
+    #[serde(rename = "comments:write")]
+    CommentsWrite,
+    #[serde(rename = "votes:write")]
+    VotesWrite,
+    /// Moderates the tags, for moderators.
+    #[serde(rename = "tags:write")]
+    TagsWrite,
+    /// Changes the profile, password, two-factor authentication and API
+    /// keys of the account, or deletes it.
+    #[serde(rename = "account")]
+    Account,
+    /// Manages the roles and exports the data, for admins.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+pub const ALL_SCOPES: [Scope; 7] = [
+    Scope::QuestionsWrite,
+    Scope::AnswersWrite,
+    Scope::CommentsWrite,
+    Scope::VotesWrite,
+    Scope::TagsWrite,
+    Scope::Account,
+    Scope::Admin,
+];
+
+/// Scopes of sessions that don't say otherwise.
+pub fn all_scopes() -> Vec<Scope> {
+    ALL_SCOPES.to_vec()
+}
+
+/// Scopes the sessions of the role may have: tag moderation is for
+/// moderators and the admin scope for admins.
+pub fn role_scopes(role: Role) -> Vec<Scope> {
+    ALL_SCOPES
+        .iter()
+        .copied()
+        .filter(|scope| match scope {
+            Scope::TagsWrite => role >= Role::Moderator,
+            Scope::Admin => role >= Role::Admin,
+            _ => true,
+        })
+        .collect()
+}
+
+/// Scopes given to a session of the role: the ones asked for that the role
+/// may have, or all of them when none are asked for.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::account::Role;
+/// use rust_web_dev::types::scope::{grant_scopes, Scope};
+///
+/// let asked = [Scope::AnswersWrite, Scope::Admin];
+/// assert_eq!(grant_scopes(Some(&asked), Role::User), vec![Scope::AnswersWrite]);
+/// assert!(!grant_scopes(None, Role::User).contains(&Scope::TagsWrite));
+/// ```
+pub fn grant_scopes(asked: Option<&[Scope]>, role: Role) -> Vec<Scope> {
+    let allowed = role_scopes(role);
+    match asked {
+        Some(asked) => allowed
+            .into_iter()
+            .filter(|scope| asked.contains(scope))
+            .collect(),
+        None => allowed,
+    }
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::QuestionsWrite => "questions:write",
+            Scope::AnswersWrite => "answers:write",
+            Scope::CommentsWrite => "comments:write",
+            Scope::VotesWrite => "votes:write",
+            Scope::TagsWrite => "tags:write",
+            Scope::Account => "account",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        ALL_SCOPES
+            .iter()
+            .find(|scope| scope.as_str() == value)
+            .copied()
+            .ok_or_else(|| format!("unknown scope {}", value))
+    }
+}
+
+#[cfg(test)]
+mod scope_tests {
+    use super::*;
+
+    #[test]
+    fn roles_limit_the_scopes() {
+        assert_eq!(role_scopes(Role::Admin), all_scopes());
+        let moderator = role_scopes(Role::Moderator);
+        assert!(moderator.contains(&Scope::TagsWrite));
+        assert!(!moderator.contains(&Scope::Admin));
+        assert_eq!(role_scopes(Role::User).len(), ALL_SCOPES.len() - 2);
+        for scope in ALL_SCOPES {
+            assert_eq!(scope.to_string().parse::<Scope>(), Ok(scope));
+            assert_eq!(
+                serde_json::to_value(scope).unwrap(),
+                serde_json::json!(scope.as_str())
+            );
+        }
+        assert!("questions:read".parse::<Scope>().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use handle_errors::Error;
+
+use crate::types::answer::AnswerId;
+use crate::types::pagination::{extract_pagination, Pagination};
+use crate::types::question::QuestionId;
+
+/// Search extracted from the `/search` query parameters.
+#[derive(Debug, Default, PartialEq)]
+pub struct Search {
+    /// Words to look for, in the syntax of `websearch_to_tsquery`:
+    /// `"quoted phrases"`, `or` and `-excluded` words are understood.
+    pub text: String,
+    /// Only questions having all these tags are searched.
+    pub tags: Option<Vec<String>>,
+    pub pagination: Pagination,
+}
+
+/// Question, or answer of a question, matching a search.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SearchResult {
+    pub question_id: QuestionId,
+    /// Set when the match is in an answer rather than in the question.
+    pub answer_id: Option<AnswerId>,
+    /// Title of the question, matching words wrapped in `<b>` tags.
+    pub title: String,
+    /// Excerpt of the matching content, matching words wrapped in `<b>` tags.
+    pub snippet: String,
+    pub rank: f32,
+}
+
+/// Extract the search from the `/search` query parameters
+/// # Example query
+/// `/search?q=async+closures&tags=rust,tokio&limit=10&offset=0`
+/// # Example usage
+/// ```rust
+/// use std::collections::HashMap;
+/// use rust_web_dev::types::search::extract_search;
+///
+/// let mut query = HashMap::new();
+/// query.insert("q".to_string(), "async closures".to_string());
+/// query.insert("tags".to_string(), "rust,tokio".to_string());
+/// let search = extract_search(query).unwrap();
+/// assert_eq!(search.text, "async closures");
+/// assert_eq!(search.tags, Some(vec!["rust".to_string(), "tokio".to_string()]));
+/// assert_eq!(search.pagination.limit, None);
+/// ```
+pub fn extract_search(mut params: HashMap<String, String>) -> Result<Search, Error> {
+    let text = match params.remove("q") {
+        Some(text) if !text.trim().is_empty() => text,
+        Some(_) => return Err(Error::InvalidParameter("q is empty".to_string())),
+        None => return Err(Error::MissingParameters),
+    };
+    let tags = params.remove("tags").map(|tags| {
+        tags.split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()
+    });
+    let mut pagination = Pagination::default();
+    if !params.is_empty() {
+        pagination = extract_pagination(params)?;
+    }
+    Ok(Search {
+        text,
+        tags,
+        pagination,
+    })
+}
+
+#[cfg(test)]
+mod search_tests {
+    use super::{extract_search, Error, HashMap};
+
+    #[test]
+    fn missing_text() {
+        let mut params = HashMap::new();
+        params.insert(String::from("tags"), String::from("rust"));
+        let result = format!("{}", extract_search(params).unwrap_err());
+        assert_eq!(result, format!("{}", Error::MissingParameters));
+
+        let mut params = HashMap::new();
+        params.insert(String::from("q"), String::from("  "));
+        let result = format!("{}", extract_search(params).unwrap_err());
+        assert_eq!(result, "Invalid parameter: q is empty");
+    }
+
+    #[test]
+    fn search_with_pagination() {
+        let mut params = HashMap::new();
+        params.insert(String::from("q"), String::from("warp"));
+        params.insert(String::from("limit"), String::from("5"));
+        params.insert(String::from("offset"), String::from("2"));
+        let search = extract_search(params).unwrap();
+        assert_eq!(search.tags, None);
+        assert_eq!(search.pagination.limit, Some(5));
+        assert_eq!(search.pagination.offset, 2);
+    }
+}
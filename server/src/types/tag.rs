@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+use handle_errors::Error;
+
+// Tag names are sent as comma separated lists in the query parameters.
+const MAX_NAME_LENGTH: usize = 35;
+
+/// Tag of the catalogue, with the number of questions using it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Tag {
+    pub name: String,
+    pub description: Option<String>,
+    pub question_count: i64,
+    /// Other names of the tag, replaced by its name when questions are
+    /// tagged.
+    pub synonyms: Vec<String>,
+}
+
+/// Body of `POST /tags`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewTag {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Body of `PUT /tags/{name}`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TagUpdate {
+    pub description: Option<String>,
+}
+
+/// Body of `POST /tags/{name}/synonyms`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewSynonym {
+    pub name: String,
+}
+
+/// Body of `POST /tags/{name}/merge`: the tag is merged into `into`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TagMerge {
+    pub into: String,
+}
+
+/// Checks the name of a new tag or synonym.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::tag::check_tag_name;
+///
+/// assert!(check_tag_name("async-await").is_ok());
+/// assert!(check_tag_name("rust,warp").is_err());
+/// ```
+pub fn check_tag_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(Error::InvalidParameter(format!(
+            "tag names have 1 to {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    if name.chars().any(|c| c == ',' || c.is_whitespace()) {
+        return Err(Error::InvalidParameter(format!(
+            "tag names can't contain commas or spaces, got {}",
+            name
+        )));
+    }
+    Ok(())
+}
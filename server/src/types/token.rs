@@ -0,0 +1,57 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::types::account::AccountId;
+use crate::types::scope::Scope;
+
+/// Reply of `POST /login` and `POST /token/refresh`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    /// Used once to get the next pair from `POST /token/refresh`.
+    pub refresh_token: String,
+    /// Seconds until the access token expires.
+    pub expires_in: i64,
+}
+
+/// Body of `POST /token/refresh`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Refresh token as stored: only the hash of the token is kept.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub account_id: AccountId,
+    pub sid: String,
+    /// In UTC.
+    pub expires_at: NaiveDateTime,
+    /// Set when the token is exchanged for a new pair. Using it again
+    /// revokes the whole login.
+    pub used: bool,
+    /// Scopes asked for at login, `None` for all the ones of the role. They
+    /// are checked against the role again at each refresh.
+    pub scopes: Option<Vec<Scope>>,
+}
+
+/// Password reset token as stored, by hash like the refresh tokens. It can
+/// be used once, before it expires.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResetToken {
+    pub token_hash: String,
+    pub account_id: AccountId,
+    /// In UTC.
+    pub expires_at: NaiveDateTime,
+}
+
+/// Email verification token as stored, by hash. It can be used once, before
+/// it expires.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerificationToken {
+    pub token_hash: String,
+    pub account_id: AccountId,
+    /// In UTC.
+    pub expires_at: NaiveDateTime,
+}
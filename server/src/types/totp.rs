@@ -0,0 +1,282 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+
+use crate::types::account::AccountId;
+use crate::types::scope::Scope;
+
+/// Name of the service shown by authenticator apps.
+pub const TOTP_ISSUER: &str = "Q&A";
+/// Seconds a code is valid for, the default of RFC 6238.
+pub const TOTP_PERIOD_SECONDS: i64 = 30;
+pub const TOTP_DIGITS: usize = 6;
+/// Recovery codes given when the factor is confirmed, each usable once.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+/// Value of the `purpose` claim of challenge tokens.
+pub const TOTP_CHALLENGE_PURPOSE: &str = "totp";
+// RFC 4648 base32, the encoding of secrets in otpauth URIs.
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+// 160 bits, the size recommended by RFC 4226 for HMAC-SHA1.
+const SECRET_BYTES: usize = 20;
+// Codes of the step before and after the current one are accepted too, to
+// allow for clock drift and slow typing.
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+
+/// Time based one-time password factor of an account, as stored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpFactor {
+    pub account_id: AccountId,
+    /// Base32, as given to the authenticator app.
+    pub secret: String,
+    /// Set when the enrollment is confirmed with a first code. Until then
+    /// login doesn't ask for codes.
+    pub enabled: bool,
+    /// Time step of the last code used, which can't be used again.
+    pub last_used_step: Option<i64>,
+    /// SHA-256 hashes of the recovery codes not used yet.
+    pub recovery_code_hashes: Vec<String>,
+}
+
+/// Reply of `POST /me/totp`: the secret to add to an authenticator app,
+/// also as a URI for QR codes.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Body of `POST /me/totp/confirm` and `DELETE /me/totp`: a code of the
+/// authenticator app, or a recovery code.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TotpCode {
+    pub code: String,
+}
+
+/// Reply of `POST /me/totp/confirm`. The codes are only shown once.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Reply of `POST /login` for accounts with two-factor authentication: the
+/// token is exchanged for a session with a code at `POST /login/totp`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LoginChallenge {
+    pub challenge_token: String,
+    /// Seconds until the challenge token expires.
+    pub expires_in: i64,
+}
+
+/// Body of `POST /login/totp`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TotpLogin {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+/// Claims of a challenge token. Its purpose keeps it from being taken for
+/// an access token, and the other way round.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TotpChallenge {
+    pub exp: DateTime<Utc>,
+    pub account_id: AccountId,
+    pub purpose: String,
+    /// Scopes asked for at login, given to the session.
+    #[serde(default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+/// Random secret, in base32.
+pub fn new_secret() -> String {
+    let bytes = rand::thread_rng().gen::<[u8; SECRET_BYTES]>();
+    base32_encode(&bytes)
+}
+
+/// URI read by authenticator apps, usually from a QR code.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::totp::otpauth_uri;
+///
+/// assert_eq!(
+///     otpauth_uri("foo@bar.com", "JBSWY3DPEHPK3PXP"),
+///     "otpauth://totp/Q%26A:foo%40bar%2Ecom?secret=JBSWY3DPEHPK3PXP&issuer=Q%26A&algorithm=SHA1&digits=6&period=30"
+/// );
+/// ```
+pub fn otpauth_uri(email: &str, secret: &str) -> String {
+    let issuer = utf8_percent_encode(TOTP_ISSUER, NON_ALPHANUMERIC);
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer,
+        utf8_percent_encode(email, NON_ALPHANUMERIC),
+        secret,
+        issuer,
+        TOTP_DIGITS,
+        TOTP_PERIOD_SECONDS
+    )
+}
+
+/// Time step of the instant.
+pub fn time_step(at: DateTime<Utc>) -> i64 {
+    at.timestamp().div_euclid(TOTP_PERIOD_SECONDS)
+}
+
+/// Code of the time step, as shown by authenticator apps. `None` when the
+/// secret isn't base32.
+pub fn code_at(secret: &str, step: i64) -> Option<String> {
+    let key = base32_decode(secret)?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key).expect("HMAC takes keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    // Dynamic truncation of RFC 4226.
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    Some(format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS as u32),
+        width = TOTP_DIGITS
+    ))
+}
+
+/// Time step of the code when it's valid at `now`, allowing for a step of
+/// drift. Whether it was already used is up to the caller.
+/// # Example usage
+/// ```rust
+/// use chrono::{TimeZone, Utc};
+/// use rust_web_dev::types::totp::verify_code;
+///
+/// // Secret "12345678901234567890" of the RFC 6238 test vectors.
+/// let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+/// let now = Utc.timestamp_opt(59, 0).unwrap();
+/// assert_eq!(verify_code(secret, "287082", now), Some(1));
+/// assert_eq!(verify_code(secret, "287083", now), None);
+/// ```
+pub fn verify_code(secret: &str, code: &str, now: DateTime<Utc>) -> Option<i64> {
+    let code = code.trim();
+    if !is_totp_code(code) {
+        return None;
+    }
+    let step = time_step(now);
+    (step - ALLOWED_DRIFT_STEPS..=step + ALLOWED_DRIFT_STEPS)
+        .find(|step| code_at(secret, *step).as_deref() == Some(code))
+}
+
+/// Whether the code looks like one of an authenticator app rather than a
+/// recovery code.
+pub fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+/// Random recovery code, like `k7d2m-q9xfa`.
+pub fn new_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let chars: String = (0..10)
+        .map(|_| BASE32_ALPHABET[rng.gen_range(0..32)].to_ascii_lowercase() as char)
+        .collect();
+    format!("{}-{}", &chars[..5], &chars[5..])
+}
+
+/// Form of the recovery code that is hashed, so that the case and the
+/// separators don't matter.
+/// # Example usage
+/// ```rust
+/// use rust_web_dev::types::totp::normalize_recovery_code;
+///
+/// assert_eq!(normalize_recovery_code(" K7D2M-Q9XFA "), "k7d2mq9xfa");
+/// ```
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+/// Case and padding are ignored, like authenticator apps do.
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in encoded.trim_end_matches('=').bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|letter| *letter == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod totp_tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn codes_match_the_rfc_6238_test_vectors() {
+        let secret = base32_encode(b"12345678901234567890");
+        assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(
+            base32_decode(&secret.to_lowercase()).unwrap(),
+            b"12345678901234567890"
+        );
+        // The last 6 of the 8 digits of the SHA1 vectors.
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ] {
+            let step = time_step(Utc.timestamp_opt(time, 0).unwrap());
+            assert_eq!(code_at(&secret, step).unwrap(), code);
+        }
+        assert_eq!(code_at("not base32!", 1), None);
+        assert_eq!(new_secret().len(), 32);
+    }
+
+    #[test]
+    fn codes_are_accepted_a_step_early_or_late() {
+        let secret = new_secret();
+        let now = Utc.timestamp_opt(1111111111, 0).unwrap();
+        let step = time_step(now);
+        for drift in [-1, 0, 1] {
+            let code = code_at(&secret, step + drift).unwrap();
+            assert_eq!(verify_code(&secret, &code, now), Some(step + drift));
+        }
+        let code = code_at(&secret, step + 2).unwrap();
+        assert_eq!(verify_code(&secret, &code, now), None);
+        let code = new_recovery_code();
+        assert!(!is_totp_code(&code));
+        assert_eq!(normalize_recovery_code(&code).len(), 10);
+    }
+}
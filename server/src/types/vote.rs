@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::answer::AnswerId;
+use crate::types::question::QuestionId;
+
+/// Post a vote is cast on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VoteTarget {
+    Question(QuestionId),
+    Answer(AnswerId),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+impl VoteDirection {
+    /// Value added to the score of the post.
+    pub fn value(self) -> i16 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+}
+
+/// Body of `POST /questions/{id}/vote` and `POST /answers/{id}/vote`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewVote {
+    pub direction: VoteDirection,
+}
+
+/// Score of a post after a vote was cast or withdrawn.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Score {
+    pub score: i64,
+}